@@ -0,0 +1,81 @@
+use std::fmt::Write;
+
+use nom_toml_parser::{Table, TomlValue};
+
+/// Writes a document as JSON, indented by two spaces. Date and time values become strings
+/// written the way TOML writes them, and floats JSON has no way to write, like `nan`, become
+/// `null`.
+pub fn to_json(table: &Table) -> String {
+    let mut out = String::new();
+    write_table(&mut out, table, 0);
+    out.push('\n');
+    out
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn indent(out: &mut String, depth: usize) {
+    out.push('\n');
+    out.push_str(&"  ".repeat(depth));
+}
+
+fn write_table(out: &mut String, table: &Table, depth: usize) {
+    if table.is_empty() {
+        out.push_str("{}");
+        return;
+    }
+    out.push('{');
+    for (i, (key, value)) in table.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        indent(out, depth + 1);
+        write_string(out, key);
+        out.push_str(": ");
+        write_value(out, value, depth + 1);
+    }
+    indent(out, depth);
+    out.push('}');
+}
+
+fn write_value(out: &mut String, value: &TomlValue, depth: usize) {
+    match value {
+        TomlValue::Str(s) => write_string(out, s),
+        TomlValue::Integer(i) => write!(out, "{}", i).unwrap(),
+        TomlValue::Float(x) if x.is_finite() => write!(out, "{:?}", x).unwrap(),
+        TomlValue::Float(_) => out.push_str("null"),
+        TomlValue::Boolean(b) => write!(out, "{}", b).unwrap(),
+        TomlValue::OffsetDateTime(_)
+        | TomlValue::LocalDateTime(_)
+        | TomlValue::LocalDate(_)
+        | TomlValue::LocalTime(_) => write_string(out, &value.to_string()),
+        TomlValue::Array(values) if values.is_empty() => out.push_str("[]"),
+        TomlValue::Array(values) => {
+            out.push('[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                indent(out, depth + 1);
+                write_value(out, value, depth + 1);
+            }
+            indent(out, depth);
+            out.push(']');
+        }
+        TomlValue::Table(table) => write_table(out, table, depth),
+    }
+}
@@ -0,0 +1,467 @@
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use nom_toml_parser::{
+    parse, parse_cst, parse_recovering, CargoManifest, CstDocument, DependencyKind,
+    DependencyTable, Diagnostic, Error, ErrorKind, FormatOptions, LintConfig, Linter,
+    NewDependency, Severity, TomlValue,
+};
+
+mod json;
+
+const USAGE: &str = "\
+usage: manager <command> [arguments]
+
+commands:
+    check <file>               checks the file and prints what is wrong with it
+    get <file> <path>          prints the value at a path like `package.version`
+    set <file> <path> <value>  sets a value, keeping the formatting of the rest of the file
+    fmt <file>                 formats the file, in the style of the `toml-fmt.toml` in its
+                               directory or the closest one above it
+    sort <file>                sorts the dependencies of a manifest by name
+    lint <file>                checks a manifest for common mistakes, with the rules set in
+                               the closest `toml-lint.toml` like `fmt` does
+    convert --to json <file>   prints the file as JSON
+    add <file> <name>[@<version>]
+                               adds a dependency, or replaces the one with that name
+    rm <file> <name>           removes a dependency
+    upgrade <file> <name> <version>
+                               sets the version requirement of a dependency
+
+A <value> is read as TOML, like `true`, `[1, 2]` or `\"1.0\"`, and as a string if it is not
+valid TOML. A `Cargo.toml` is checked as a manifest as well.
+
+options of `fmt` and `sort`:
+    --check                    changes nothing, and fails if the file would change
+    --tables                   for `sort`, puts the tables in the order Cargo documents too
+
+options of `add`, `rm` and `upgrade`:
+    --dev, --build             the dev or build dependencies instead of the normal ones
+    --target <target>          the dependencies of a platform, like `cfg(unix)`
+
+options of `add`:
+    --features <a,b>           the features to turn on
+    --no-default-features      turns the default features off
+    --optional                 makes the dependency optional
+    --path <path>              a dependency in a directory
+    --git <url>                a dependency in a git repository, with --branch, --tag
+                               or --rev <name> to pick the commit
+    --registry <name>          a dependency from another registry
+    --package <name>           the name of the package, to rename the dependency
+    --workspace                takes the dependency from the workspace
+
+exit codes:
+    0  success
+    1  the file is not valid TOML
+    2  the command line is wrong
+    3  the file is valid TOML, but its tables contradict each other, it is not a valid
+       manifest, or the path is not in it
+    4  the file could not be read or written
+    5  `--check` found that the file is not formatted or sorted
+    6  `lint` found something a rule set to `deny` does not allow";
+
+const COMMANDS: &[&str] =
+    &["check", "get", "set", "fmt", "sort", "lint", "convert", "add", "rm", "upgrade"];
+
+/// Why a command failed, which the exit code tells apart
+enum Failure {
+    Parse,
+    Usage(String),
+    Validation,
+    Io,
+    Check,
+    Lint,
+}
+
+impl Failure {
+    fn exit_code(&self) -> ExitCode {
+        ExitCode::from(match self {
+            Failure::Parse => 1,
+            Failure::Usage(_) => 2,
+            Failure::Validation => 3,
+            Failure::Io => 4,
+            Failure::Check => 5,
+            Failure::Lint => 6,
+        })
+    }
+}
+
+/// Whether to print the diagnostics in color
+fn color() -> bool {
+    std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
+/// A file the command works on, to point the diagnostics at
+struct Input {
+    path: String,
+    source: String,
+}
+
+impl Input {
+    fn read(path: &str) -> Result<Self, Failure> {
+        match std::fs::read_to_string(path) {
+            Ok(source) => Ok(Input {
+                path: path.to_string(),
+                source,
+            }),
+            Err(e) => {
+                eprintln!("error: could not read {}: {}", path, e);
+                Err(Failure::Io)
+            }
+        }
+    }
+
+    fn write(&self, contents: &str) -> Result<(), Failure> {
+        std::fs::write(&self.path, contents).map_err(|e| {
+            eprintln!("error: could not write {}: {}", self.path, e);
+            Failure::Io
+        })
+    }
+
+    fn is_manifest(&self) -> bool {
+        Path::new(&self.path).file_name().is_some_and(|name| name == "Cargo.toml")
+    }
+
+    /// Prints the errors and tells whether the file is not valid TOML at all, or only
+    /// contradicts itself
+    fn report(&self, errors: &[Error]) -> Failure {
+        let color = color();
+        for error in errors {
+            eprintln!("{}", Diagnostic::new(error, &self.path, &self.source).with_color(color));
+        }
+        if errors.iter().any(|error| error.kind() == &ErrorKind::Syntax) {
+            Failure::Parse
+        } else {
+            Failure::Validation
+        }
+    }
+}
+
+fn check(file: &str) -> Result<(), Failure> {
+    let input = Input::read(file)?;
+    let (document, errors) = parse_recovering(&input.source);
+    if !errors.is_empty() {
+        return Err(input.report(&errors));
+    }
+    if input.is_manifest() {
+        if let Err(error) = CargoManifest::from_document(&document) {
+            return Err(input.report(&[error]));
+        }
+    }
+    Ok(())
+}
+
+fn get(file: &str, path: &str) -> Result<(), Failure> {
+    let input = Input::read(file)?;
+    let document = parse(&input.source).map_err(|error| input.report(&[error]))?;
+    match document.get(path) {
+        Some(TomlValue::Str(s)) => println!("{}", s),
+        Some(TomlValue::Table(table)) => print!("{}", table),
+        Some(value) => println!("{}", value),
+        None => {
+            eprintln!("error: `{}` is not in {}", path, file);
+            return Err(Failure::Validation);
+        }
+    }
+    Ok(())
+}
+
+/// Reads a value given on the command line as TOML, or as a string if it is not valid TOML
+fn parse_value(raw: &str) -> TomlValue {
+    match parse(&format!("value = {}", raw)) {
+        Ok(document) if document.root().len() == 1 => document.root()["value"].clone(),
+        _ => TomlValue::Str(raw.to_string()),
+    }
+}
+
+/// Writes an edited document back, if it is still valid
+fn save(input: &Input, cst: &CstDocument) -> Result<(), Failure> {
+    let output = cst.to_string();
+    let changed = Input {
+        path: input.path.clone(),
+        source: output,
+    };
+    let document = cst.to_document().map_err(|error| changed.report(&[error]))?;
+    if input.is_manifest() {
+        if let Err(error) = CargoManifest::from_document(&document) {
+            return Err(changed.report(&[error]));
+        }
+    }
+    input.write(&changed.source)
+}
+
+fn set(file: &str, path: &str, value: &str) -> Result<(), Failure> {
+    let input = Input::read(file)?;
+    let mut cst = parse_cst(&input.source).map_err(|error| input.report(&[error]))?;
+    if !cst.set(path, parse_value(value)) {
+        eprintln!("error: `{}` cannot be set in {}", path, file);
+        return Err(Failure::Validation);
+    }
+    save(&input, &cst)
+}
+
+/// Reads the configuration file called `name` in the directory of `file` or the closest one
+/// above it, if there is one
+fn config(file: &str, name: &str) -> Result<Option<Input>, Failure> {
+    let file = std::path::absolute(file).unwrap_or_else(|_| PathBuf::from(file));
+    match file.ancestors().skip(1).map(|dir| dir.join(name)).find(|config| config.is_file()) {
+        Some(config) => Input::read(&config.to_string_lossy()).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Reads the formatting options from the `toml-fmt.toml` closest to `file`, if there is one
+fn format_options(file: &str) -> Result<FormatOptions, Failure> {
+    match config(file, "toml-fmt.toml")? {
+        Some(input) => input.source.parse().map_err(|error| input.report(&[error])),
+        None => Ok(FormatOptions::default()),
+    }
+}
+
+/// Writes a file that changed, or with `--check` fails if it has, saying what it is not
+fn rewrite(input: &Input, output: String, check: bool, not: &str) -> Result<(), Failure> {
+    if output == input.source {
+        Ok(())
+    } else if check {
+        eprintln!("error: {} is not {}", input.path, not);
+        Err(Failure::Check)
+    } else {
+        input.write(&output)
+    }
+}
+
+fn fmt(file: &str, check: bool) -> Result<(), Failure> {
+    let options = format_options(file)?;
+    let input = Input::read(file)?;
+    let mut cst = parse_cst(&input.source).map_err(|error| input.report(&[error]))?;
+    cst.format_with(&options);
+    rewrite(&input, cst.to_string(), check, "formatted")
+}
+
+fn sort(file: &str, tables: bool, check: bool) -> Result<(), Failure> {
+    let input = Input::read(file)?;
+    let mut cst = parse_cst(&input.source).map_err(|error| input.report(&[error]))?;
+    if tables {
+        cst.sort_tables();
+    }
+    cst.sort_dependencies();
+    rewrite(&input, cst.to_string(), check, "sorted")
+}
+
+fn lint(file: &str) -> Result<(), Failure> {
+    let linter = match config(file, "toml-lint.toml")? {
+        Some(input) => input
+            .source
+            .parse::<LintConfig>()
+            .and_then(|config| Linter::new().with_config(config))
+            .map_err(|error| input.report(&[error]))?,
+        None => Linter::new(),
+    };
+    let input = Input::read(file)?;
+    let lints = linter.lint(&input.source).map_err(|error| input.report(&[error]))?;
+    let color = color();
+    for lint in &lints {
+        eprintln!("{}", Diagnostic::for_lint(lint, &input.path, &input.source).with_color(color));
+    }
+    if lints.iter().any(|lint| lint.severity() == Severity::Deny) {
+        Err(Failure::Lint)
+    } else {
+        Ok(())
+    }
+}
+
+fn convert(format: &str, file: &str) -> Result<(), Failure> {
+    if format != "json" {
+        return Err(Failure::Usage(format!("cannot convert to `{}`, only to `json`", format)));
+    }
+    let input = Input::read(file)?;
+    let document = parse(&input.source).map_err(|error| input.report(&[error]))?;
+    print!("{}", json::to_json(document.root()));
+    Ok(())
+}
+
+fn add(file: &str, spec: &str, options: Options) -> Result<(), Failure> {
+    let mut dependency = options.dependency;
+    match spec.split_once('@') {
+        Some((name, version)) => {
+            dependency.name = name.to_string();
+            dependency.version = Some(version.to_string());
+        }
+        None => dependency.name = spec.to_string(),
+    }
+    let has_source = dependency.git.is_some() || dependency.path.is_some();
+    if dependency.version.is_none() && !has_source && !dependency.workspace {
+        return Err(Failure::Usage(format!(
+            "`{}` needs a version, like `{}@1`, a `--path`, a `--git` or `--workspace`",
+            dependency.name, dependency.name
+        )));
+    }
+    let input = Input::read(file)?;
+    let mut cst = parse_cst(&input.source).map_err(|error| input.report(&[error]))?;
+    if !cst.add_dependency(&options.table, &dependency) {
+        eprintln!("error: `{}` cannot be added to {}", dependency.name, file);
+        return Err(Failure::Validation);
+    }
+    save(&input, &cst)
+}
+
+fn rm(file: &str, name: &str, table: &DependencyTable) -> Result<(), Failure> {
+    let input = Input::read(file)?;
+    let mut cst = parse_cst(&input.source).map_err(|error| input.report(&[error]))?;
+    if !cst.remove_dependency(table, name) {
+        eprintln!("error: `{}` is not a dependency in {}", name, file);
+        return Err(Failure::Validation);
+    }
+    save(&input, &cst)
+}
+
+fn upgrade(file: &str, name: &str, version: &str, table: &DependencyTable) -> Result<(), Failure> {
+    let input = Input::read(file)?;
+    let mut cst = parse_cst(&input.source).map_err(|error| input.report(&[error]))?;
+    if !cst.upgrade_dependency(table, name, version) {
+        eprintln!(
+            "error: `{}` is not a dependency in {}, or takes its version from the workspace",
+            name, file
+        );
+        return Err(Failure::Validation);
+    }
+    save(&input, &cst)
+}
+
+/// The options given on the command line
+#[derive(Default)]
+struct Options {
+    to: Option<String>,
+    check: bool,
+    tables: bool,
+    table: DependencyTable,
+    dependency: NewDependency,
+    /// The names of the options, to tell whether the command takes them
+    given: Vec<String>,
+}
+
+impl Options {
+    /// Reads an option and its value, which is either after a `=` or the next argument
+    fn read(&mut self, arg: &str, args: &mut std::slice::Iter<String>) -> Result<(), Failure> {
+        let (name, inline) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg, None),
+        };
+        let mut value = || match inline {
+            Some(value) => Ok(value.to_string()),
+            None => args
+                .next()
+                .cloned()
+                .ok_or_else(|| Failure::Usage(format!("`{}` needs a value", name))),
+        };
+        const FLAGS: &[&str] = &[
+            "--check",
+            "--tables",
+            "--dev",
+            "--build",
+            "--no-default-features",
+            "--optional",
+            "--workspace",
+        ];
+        if inline.is_some() && FLAGS.contains(&name) {
+            return Err(Failure::Usage(format!("`{}` takes no value", name)));
+        }
+        let dependency = &mut self.dependency;
+        match name {
+            "--to" => self.to = Some(value()?),
+            "--check" => self.check = true,
+            "--tables" => self.tables = true,
+            "--dev" => self.table.kind = DependencyKind::Development,
+            "--build" => self.table.kind = DependencyKind::Build,
+            "--target" => self.table.target = Some(value()?),
+            "--features" => {
+                dependency.features = value()?
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|feature| !feature.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }
+            "--no-default-features" => dependency.default_features = Some(false),
+            "--optional" => dependency.optional = true,
+            "--path" => dependency.path = Some(value()?),
+            "--git" => dependency.git = Some(value()?),
+            "--branch" => dependency.branch = Some(value()?),
+            "--tag" => dependency.tag = Some(value()?),
+            "--rev" => dependency.rev = Some(value()?),
+            "--registry" => dependency.registry = Some(value()?),
+            "--package" => dependency.package = Some(value()?),
+            "--workspace" => dependency.workspace = true,
+            _ => return Err(Failure::Usage(format!("unknown option `{}`", name))),
+        }
+        self.given.push(name.to_string());
+        Ok(())
+    }
+
+    /// Whether a command takes an option
+    fn takes(command: &str, option: &str) -> bool {
+        match option {
+            "--to" => command == "convert",
+            "--check" => matches!(command, "fmt" | "sort"),
+            "--tables" => command == "sort",
+            "--dev" | "--build" | "--target" => matches!(command, "add" | "rm" | "upgrade"),
+            _ => command == "add",
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<(), Failure> {
+    let mut positional = vec![];
+    let mut options = Options::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            println!("{}", USAGE);
+            return Ok(());
+        } else if arg.starts_with("--") {
+            options.read(arg, &mut args)?;
+        } else {
+            positional.push(arg.as_str());
+        }
+    }
+
+    if let Some(command) = positional.first() {
+        if let Some(option) = options.given.iter().find(|option| !Options::takes(command, option)) {
+            return Err(Failure::Usage(format!("`{}` does not take `{}`", command, option)));
+        }
+    }
+    match positional.as_slice() {
+        ["check", file] => check(file),
+        ["get", file, path] => get(file, path),
+        ["set", file, path, value] => set(file, path, value),
+        ["fmt", file] => fmt(file, options.check),
+        ["sort", file] => sort(file, options.tables, options.check),
+        ["lint", file] => lint(file),
+        ["convert", file] => match &options.to {
+            Some(format) => convert(format, file),
+            None => Err(Failure::Usage("`convert` needs `--to <format>`".to_string())),
+        },
+        ["add", file, spec] => add(file, spec, options),
+        ["rm", file, name] => rm(file, name, &options.table),
+        ["upgrade", file, name, version] => upgrade(file, name, version, &options.table),
+        [] => Err(Failure::Usage("no command given".to_string())),
+        [command, ..] if COMMANDS.contains(command) => {
+            Err(Failure::Usage(format!("wrong arguments for `{}`", command)))
+        }
+        [command, ..] => Err(Failure::Usage(format!("unknown command `{}`", command))),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => {
+            if let Failure::Usage(message) = &failure {
+                eprintln!("error: {}\n\n{}", message, USAGE);
+            }
+            failure.exit_code()
+        }
+    }
+}
@@ -0,0 +1,595 @@
+use crate::error::{Error, ErrorKind, Label, Location};
+use crate::parsers::{KeyValue, Section, TableKind};
+use crate::span::{KeyValueSpans, LineIndex, Span};
+
+/// How a key of the document got defined. Tables can be created in several ways, and TOML
+/// only allows some of them to be extended later on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Definition {
+    /// Any value, including arrays and inline tables, none of which can be extended
+    Value,
+    /// A table created as the parent of a header, like `a` by `[a.b]`. It can still be
+    /// defined by its own header later on.
+    ImplicitTable,
+    /// A table defined by its own `[header]`
+    HeaderTable,
+    /// A table created by a dotted key, like `a` by `a.b = 1`
+    DottedTable,
+    ArrayOfTables,
+}
+
+impl Definition {
+    fn describe(self) -> &'static str {
+        match self {
+            Definition::Value => "a value",
+            Definition::ArrayOfTables => "an array of tables",
+            _ => "a table",
+        }
+    }
+}
+
+/// Where a key or a table was defined, both to describe it in messages and to point at it
+#[derive(Debug, Clone)]
+struct Site {
+    location: Location,
+    /// The key, or the whole header
+    span: Span,
+}
+
+/// A node of the tree the consistency check builds while walking the document. It records
+/// how and where each key was first defined, which is all that is needed to tell whether a
+/// later definition contradicts it.
+#[derive(Debug)]
+struct Node {
+    definition: Definition,
+    site: Site,
+    children: Vec<(String, Node)>,
+    /// The elements of an array of tables. Headers nested below an array of tables belong to
+    /// its most recent element.
+    elements: Vec<Node>,
+}
+
+impl Node {
+    fn new(definition: Definition, site: Site) -> Self {
+        Node {
+            definition,
+            site,
+            children: vec![],
+            elements: vec![],
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.children
+            .iter()
+            .position(|(child_name, _)| child_name == name)
+    }
+
+    fn insert(&mut self, name: &str, node: Node) -> &mut Node {
+        self.children.push((name.to_string(), node));
+        &mut self.children.last_mut().unwrap().1
+    }
+}
+
+/// Points at the second definition as the mistake, and at the first one for reference
+fn labels(first: &Site, second: &Site, first_label: String, second_label: String) -> Vec<Label> {
+    vec![
+        Label::primary(second.span, second_label),
+        Label::secondary(first.span, first_label),
+    ]
+}
+
+/// Says which definitions of something defined twice are meant. Where both are written the
+/// same way, like a key repeated in its table, only the line of the first one tells them apart.
+/// The error adds where the second one is.
+fn definitions(first: &Site, second: &Site) -> String {
+    if first.location == second.location {
+        format!(" (first defined on line {})", first.span.line())
+    } else {
+        format!(
+            ", by {} at line {} and by {}",
+            first.location,
+            first.span.line(),
+            second.location
+        )
+    }
+}
+
+fn duplicate_key(path: &[&str], first: &Site, second: &Site) -> Error {
+    Error::consistency(
+        ErrorKind::DuplicateKey {
+            first: first.location.clone(),
+            second: second.location.clone(),
+        },
+        format!(
+            "`{}` is defined more than once{}",
+            path.join("."),
+            definitions(first, second)
+        ),
+        labels(
+            first,
+            second,
+            "first defined here".to_string(),
+            "defined again here".to_string(),
+        ),
+    )
+}
+
+fn duplicate_table(path: &[&str], first: &Site, second: &Site) -> Error {
+    Error::consistency(
+        ErrorKind::DuplicateTable {
+            first: first.location.clone(),
+            second: second.location.clone(),
+        },
+        format!(
+            "table `{}` is defined more than once{}",
+            path.join("."),
+            definitions(first, second)
+        ),
+        labels(
+            first,
+            second,
+            "first defined here".to_string(),
+            "defined again here".to_string(),
+        ),
+    )
+    .with_note("a table can only be defined once, by a header or by dotted keys")
+}
+
+fn conflicting_definitions(
+    path: &[&str],
+    first: &Node,
+    definition: Definition,
+    second: &Site,
+) -> Error {
+    Error::consistency(
+        ErrorKind::ConflictingDefinitions {
+            first: first.site.location.clone(),
+            second: second.location.clone(),
+        },
+        format!(
+            "`{}` is defined as {} by {} at line {} and as {} by {}",
+            path.join("."),
+            first.definition.describe(),
+            first.site.location,
+            first.site.span.line(),
+            definition.describe(),
+            second.location
+        ),
+        labels(
+            &first.site,
+            second,
+            format!("defined as {} here", first.definition.describe()),
+            format!("defined as {} here", definition.describe()),
+        ),
+    )
+}
+
+/// Walks into the parent table `path` of a header, creating it if it does not exist yet
+fn parent_table<'n>(
+    node: &'n mut Node,
+    path: &[&str],
+    site: &Site,
+) -> Result<&'n mut Node, Error> {
+    let name = path[path.len() - 1];
+    let child = match node.position(name) {
+        Some(index) => &mut node.children[index].1,
+        None => node.insert(name, Node::new(Definition::ImplicitTable, site.clone())),
+    };
+    match child.definition {
+        Definition::Value => Err(conflicting_definitions(
+            path,
+            child,
+            Definition::ImplicitTable,
+            site,
+        )),
+        Definition::ArrayOfTables => Ok(child.elements.last_mut().unwrap()),
+        _ => Ok(child),
+    }
+}
+
+/// Walks into the table `path` of a dotted key, creating it if it does not exist yet.
+/// Tables that were defined by a header cannot be extended by dotted keys.
+fn dotted_table<'n>(
+    node: &'n mut Node,
+    path: &[&str],
+    site: &Site,
+) -> Result<&'n mut Node, Error> {
+    let name = path[path.len() - 1];
+    let child = match node.position(name) {
+        Some(index) => &mut node.children[index].1,
+        None => node.insert(name, Node::new(Definition::DottedTable, site.clone())),
+    };
+    match child.definition {
+        Definition::DottedTable | Definition::ImplicitTable => Ok(child),
+        Definition::HeaderTable => Err(duplicate_table(path, &child.site, site)),
+        Definition::Value | Definition::ArrayOfTables => Err(conflicting_definitions(
+            path,
+            child,
+            Definition::DottedTable,
+            site,
+        )),
+    }
+}
+
+/// Defines the table of a header and returns its node, so its key value pairs can be added
+fn define_table<'n>(
+    tree: &'n mut Node,
+    path: &[&str],
+    kind: TableKind,
+    site: &Site,
+) -> Result<&'n mut Node, Error> {
+    let mut node = tree;
+    for end in 1..path.len() {
+        node = parent_table(node, &path[..end], site)?;
+    }
+
+    let name = path[path.len() - 1];
+    let index = match (node.position(name), kind) {
+        (Some(index), _) => index,
+        (None, TableKind::Standard) => {
+            return Ok(node.insert(name, Node::new(Definition::HeaderTable, site.clone())));
+        }
+        (None, TableKind::ArrayOfTables) => {
+            let array = node.insert(
+                name,
+                Node::new(Definition::ArrayOfTables, site.clone()),
+            );
+            array
+                .elements
+                .push(Node::new(Definition::HeaderTable, site.clone()));
+            return Ok(array.elements.last_mut().unwrap());
+        }
+    };
+
+    let child = &mut node.children[index].1;
+    match (child.definition, kind) {
+        (Definition::ImplicitTable, TableKind::Standard) => {
+            child.definition = Definition::HeaderTable;
+            child.site = site.clone();
+            Ok(child)
+        }
+        (Definition::HeaderTable | Definition::DottedTable, TableKind::Standard) => {
+            Err(duplicate_table(path, &child.site, site))
+        }
+        (Definition::ArrayOfTables, TableKind::ArrayOfTables) => {
+            child
+                .elements
+                .push(Node::new(Definition::HeaderTable, site.clone()));
+            Ok(child.elements.last_mut().unwrap())
+        }
+        (_, TableKind::Standard) => Err(conflicting_definitions(
+            path,
+            child,
+            Definition::HeaderTable,
+            site,
+        )),
+        (_, TableKind::ArrayOfTables) => Err(conflicting_definitions(
+            path,
+            child,
+            Definition::ArrayOfTables,
+            site,
+        )),
+    }
+}
+
+fn define_key_value(
+    index: &LineIndex,
+    node: &mut Node,
+    table_name: &str,
+    table_path: &[&str],
+    key_val: &KeyValue,
+    spans: &KeyValueSpans,
+) -> Result<(), Error> {
+    let site = Site {
+        location: Location::Key {
+            table: table_name.to_string(),
+            key: key_val.0.to_string(),
+        },
+        span: index.resolve(spans.key),
+    };
+    let mut path = table_path.to_vec();
+    path.extend(key_val.0.segments().iter().map(String::as_str));
+
+    let mut current = &mut *node;
+    for end in table_path.len() + 1..path.len() {
+        current = dotted_table(current, &path[..end], &site)?;
+    }
+
+    let name = path[path.len() - 1];
+    match current.position(name) {
+        None => {
+            current.insert(name, Node::new(Definition::Value, site));
+            Ok(())
+        }
+        Some(index) => {
+            let existing = &current.children[index].1;
+            Err(match existing.definition {
+                Definition::Value => duplicate_key(&path, &existing.site, &site),
+                _ => conflicting_definitions(&path, existing, Definition::Value, &site),
+            })
+        }
+    }
+}
+
+/// Defines the key value pairs of a table, and removes the ones that contradict what was
+/// defined before them
+fn define_key_values(
+    index: &LineIndex,
+    node: &mut Node,
+    table: &mut Section,
+    table_path: &[&str],
+    errors: &mut Vec<Error>,
+) {
+    let table_name = match (table.header.is_empty(), table.kind) {
+        (true, _) => String::new(),
+        (false, TableKind::Standard) => format!("[{}]", table.header),
+        (false, TableKind::ArrayOfTables) => format!("[[{}]]", table.header),
+    };
+
+    let mut keep = vec![];
+    for (key_val, spans) in table.key_val_vec.iter().zip(&table.spans.key_values) {
+        match define_key_value(index, node, &table_name, table_path, key_val, spans) {
+            Ok(()) => keep.push(true),
+            Err(error) => {
+                errors.push(error);
+                keep.push(false);
+            }
+        }
+    }
+
+    let mut kept = keep.iter();
+    table.key_val_vec.retain(|_| *kept.next().unwrap());
+    let mut kept = keep.iter();
+    table.spans.key_values.retain(|_| *kept.next().unwrap());
+}
+
+/// Checks that the tables and keys of a document do not contradict each other: keys can only
+/// be defined once, tables can only be defined once, and a key that holds a value cannot be
+/// used as a table later on, or the other way around. The errors point into the source the
+/// sections were parsed from, which `index` covers.
+///
+/// Every contradiction is reported, in the order of the document. The key value pairs and
+/// tables that caused them are removed, so what is left is consistent. A key or header only
+/// adds to the tree once it is known to fit, so removing it does not affect the others.
+pub(crate) fn remove_inconsistencies(
+    index: &LineIndex,
+    root: &mut Section,
+    tables: &mut Vec<Section>,
+) -> Vec<Error> {
+    let mut errors = vec![];
+    let mut tree = Node::new(
+        Definition::HeaderTable,
+        Site {
+            location: Location::Header {
+                header: String::new(),
+                kind: TableKind::Standard,
+            },
+            span: Span::default(),
+        },
+    );
+    define_key_values(index, &mut tree, root, &[], &mut errors);
+
+    tables.retain_mut(|table| {
+        let header = table
+            .spans
+            .header
+            .as_ref()
+            .expect("sections after the root have a header");
+        let site = Site {
+            location: Location::Header {
+                header: table.header.to_string(),
+                kind: table.kind,
+            },
+            span: index.resolve(header.header),
+        };
+        let header = table.header.clone();
+        let path: Vec<&str> = header.segments().iter().map(String::as_str).collect();
+        match define_table(&mut tree, &path, table.kind, &site) {
+            Ok(node) => {
+                define_key_values(index, node, table, &path, &mut errors);
+                true
+            }
+            Err(error) => {
+                errors.push(error);
+                false
+            }
+        }
+    });
+
+    errors
+}
+
+#[cfg(test)]
+mod tests_consistency {
+    use std::fs::read_to_string;
+
+    use crate::{parse, parse_recovering, TomlValue};
+
+    use super::*;
+
+    fn header(header: &str, kind: TableKind) -> Location {
+        Location::Header {
+            header: header.to_string(),
+            kind,
+        }
+    }
+
+    fn key(table: &str, key: &str) -> Location {
+        Location::Key {
+            table: table.to_string(),
+            key: key.to_string(),
+        }
+    }
+
+    fn error_kind(input: &str) -> ErrorKind {
+        parse(input).unwrap_err().kind().clone()
+    }
+
+    #[test]
+    fn test_repeated_table_def() {
+        let input = read_to_string("assets/consistency_examples/repeated_table_def.toml").unwrap();
+        let error = parse(&input).unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::DuplicateTable {
+                first: key("[fruit]", "apple.color"),
+                second: header("fruit.apple", TableKind::Standard),
+            }
+        );
+        assert_eq!(
+            error.message(),
+            "table `fruit.apple` is defined more than once, by `apple.color` in `[fruit]` \
+             at line 2 and by `[fruit.apple]`"
+        );
+    }
+
+    #[test]
+    fn test_duplicate_key() {
+        assert_eq!(
+            error_kind("[table]\nkey = 1\nkey = 2\n"),
+            ErrorKind::DuplicateKey {
+                first: key("[table]", "key"),
+                second: key("[table]", "key"),
+            }
+        );
+        assert_eq!(
+            error_kind("a.b = 1\na.b = 2\n"),
+            ErrorKind::DuplicateKey {
+                first: key("", "a.b"),
+                second: key("", "a.b"),
+            }
+        );
+    }
+
+    #[test]
+    fn test_labels_point_at_both_definitions() {
+        let input = "[table]\nkey = 1\nkey = 2\n";
+        let error = parse(input).unwrap_err();
+        let labels = error.labels();
+        assert_eq!(labels.len(), 2);
+        assert!(labels[0].is_primary());
+        assert_eq!(labels[0].span().range(), 16..19);
+        assert_eq!(labels[0].message(), "defined again here");
+        assert_eq!(labels[1].span().range(), 8..11);
+        assert_eq!((error.line(), error.column()), (Some(3), Some(1)));
+
+        let error = parse("a = 1\n[a.b]\n").unwrap_err();
+        assert_eq!(error.labels()[0].message(), "defined as a table here");
+        assert_eq!(error.labels()[1].message(), "defined as a value here");
+        assert_eq!(error.labels()[0].span().range(), 6..11);
+    }
+
+    #[test]
+    fn test_remove_inconsistencies() {
+        let input = "a = 1\na = 2\n[a.b]\nx = 1\n[c]\nd.e = 1\nd = 2\n[c]\ny = 1\n[f]\ng = 1\n";
+        let (doc, errors) = parse_recovering(input);
+        let kinds: Vec<&ErrorKind> = errors.iter().map(Error::kind).collect();
+        assert!(matches!(
+            kinds[..],
+            [
+                ErrorKind::DuplicateKey { .. },
+                ErrorKind::ConflictingDefinitions { .. },
+                ErrorKind::ConflictingDefinitions { .. },
+                ErrorKind::DuplicateTable { .. },
+            ]
+        ));
+        let headers: Vec<String> = doc.sections().iter().map(|s| s.header().to_string()).collect();
+        assert_eq!(headers, vec!["c", "f"]);
+        assert_eq!(doc.get("a"), Some(&TomlValue::Integer(1)));
+        assert_eq!(doc.get("c.d.e"), Some(&TomlValue::Integer(1)));
+        assert_eq!(doc.get("c.y"), None);
+        assert_eq!(doc.get("f.g"), Some(&TomlValue::Integer(1)));
+        assert_eq!(doc.spans("f.g").unwrap().key.unwrap().line(), 11);
+    }
+
+    #[test]
+    fn test_duplicate_table() {
+        assert_eq!(
+            error_kind("[a]\nx = 1\n\n[b]\n\n[a]\ny = 2\n"),
+            ErrorKind::DuplicateTable {
+                first: header("a", TableKind::Standard),
+                second: header("a", TableKind::Standard),
+            }
+        );
+    }
+
+    #[test]
+    fn test_dotted_key_into_header_table() {
+        assert_eq!(
+            error_kind("[a.b]\nx = 1\n\n[a]\nb.y = 2\n"),
+            ErrorKind::DuplicateTable {
+                first: header("a.b", TableKind::Standard),
+                second: key("[a]", "b.y"),
+            }
+        );
+    }
+
+    #[test]
+    fn test_value_used_as_table() {
+        let error = parse("fruit.apple = 1\nfruit.apple.smooth = true\n").unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::ConflictingDefinitions {
+                first: key("", "fruit.apple"),
+                second: key("", "fruit.apple.smooth"),
+            }
+        );
+        assert_eq!(
+            error.message(),
+            "`fruit.apple` is defined as a value by `fruit.apple` in the root table at line 1 \
+             and as a table by `fruit.apple.smooth` in the root table"
+        );
+        assert_eq!(
+            error_kind("a = 1\n[a.b]\n"),
+            ErrorKind::ConflictingDefinitions {
+                first: key("", "a"),
+                second: header("a.b", TableKind::Standard),
+            }
+        );
+    }
+
+    #[test]
+    fn test_table_used_as_value() {
+        assert_eq!(
+            error_kind("[a.b]\nx = 1\n[a]\nb = 2\n"),
+            ErrorKind::ConflictingDefinitions {
+                first: header("a.b", TableKind::Standard),
+                second: key("[a]", "b"),
+            }
+        );
+    }
+
+    #[test]
+    fn test_array_of_tables_conflicts() {
+        assert_eq!(
+            error_kind("[bin]\n[[bin]]\n"),
+            ErrorKind::ConflictingDefinitions {
+                first: header("bin", TableKind::Standard),
+                second: header("bin", TableKind::ArrayOfTables),
+            }
+        );
+        assert_eq!(
+            error_kind("[[bin]]\n[bin]\n"),
+            ErrorKind::ConflictingDefinitions {
+                first: header("bin", TableKind::ArrayOfTables),
+                second: header("bin", TableKind::Standard),
+            }
+        );
+        assert_eq!(
+            error_kind("bin = [1, 2]\n[[bin]]\n"),
+            ErrorKind::ConflictingDefinitions {
+                first: key("", "bin"),
+                second: header("bin", TableKind::ArrayOfTables),
+            }
+        );
+    }
+
+    #[test]
+    fn test_consistent_tables() {
+        assert!(parse("[a.b]\nx = 1\n[a]\ny = 2\n").is_ok());
+        assert!(parse("[fruit]\napple.color = 1\napple.taste.sweet = true\n[fruit.apple.texture]\nsmooth = true\n").is_ok());
+        assert!(parse("[[a]]\nx = 1\n[[a]]\nx = 2\n").is_ok());
+        assert!(parse(&read_to_string("assets/array-of-tables.toml").unwrap()).is_ok());
+    }
+}
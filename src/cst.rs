@@ -0,0 +1,839 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use crate::document::Document;
+use crate::error::Error;
+use crate::parsers::{parse_path, Key, LiteralKey, PathSegment, TableKind, TomlValue};
+use crate::value::Table;
+
+/// A document that keeps everything the input was made of: comments, empty lines, the
+/// whitespace around keys and values, and the way each value was written. Writing an
+/// unchanged tree back out with `to_string()` reproduces the input byte for byte.
+///
+/// Returned by [`parse_cst`](crate::parse_cst). For the values themselves, use
+/// [`to_document`](CstDocument::to_document).
+#[derive(Debug, Clone, PartialEq)]
+pub struct CstDocument {
+    /// The root section comes first and is the only one without a header
+    pub(crate) sections: Vec<CstSection>,
+    /// Empty lines and comments after the last key value pair or header
+    pub(crate) trailing: String,
+}
+
+/// A table header, or the start of the document, and the key value pairs that follow it
+#[derive(Debug, Clone, PartialEq)]
+pub struct CstSection {
+    pub(crate) header: Option<CstHeader>,
+    pub(crate) entries: Vec<CstKeyValue>,
+}
+
+/// A `[header]` or `[[header]]` line
+#[derive(Debug, Clone, PartialEq)]
+pub struct CstHeader {
+    /// Empty lines and comments above the header
+    pub(crate) leading: String,
+    /// The header as written, from the start of its line to the closing bracket
+    pub(crate) raw: String,
+    pub(crate) key: Key,
+    pub(crate) kind: TableKind,
+    /// Whitespace and a comment after the closing bracket
+    pub(crate) suffix: String,
+    /// The line ending, empty on the last line of the input
+    pub(crate) newline: String,
+}
+
+/// A `key = value` line. Each field holds a piece of the line in the order it was written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CstKeyValue {
+    /// Empty lines and comments above the key value pair
+    pub(crate) leading: String,
+    pub(crate) indent: String,
+    pub(crate) key_raw: String,
+    pub(crate) key: Key,
+    /// The `=` with the whitespace around it
+    pub(crate) separator: String,
+    pub(crate) value_raw: CstValue,
+    pub(crate) value: TomlValue,
+    /// Whitespace and a comment after the value
+    pub(crate) suffix: String,
+    /// The line ending, empty on the last line of the input
+    pub(crate) newline: String,
+}
+
+/// A value as it was written. Arrays and inline tables keep what is written between their
+/// values, so that they can be laid out again without reading the value a second time.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum CstValue {
+    /// A string, number, boolean or date, as it was written
+    Scalar(String),
+    Array(CstArray),
+    InlineTable(CstInlineTable),
+}
+
+/// The values of an array, with the whitespace, line endings and comments between them
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CstArray {
+    pub(crate) items: Vec<CstItem>,
+    /// Whether the last value is followed by a comma
+    pub(crate) comma: bool,
+    /// Whitespace and comments before the `]`, after the last value and its comma
+    pub(crate) trailing: String,
+}
+
+/// A value of an array
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CstItem {
+    /// Whitespace and comments between the `[` or the comma before the value and the value
+    pub(crate) leading: String,
+    pub(crate) value: CstValue,
+    /// Whitespace and comments between the value and the comma or `]` after it
+    pub(crate) suffix: String,
+}
+
+/// The key value pairs of an inline table, which cannot have comments or line endings
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CstInlineTable {
+    pub(crate) entries: Vec<CstInlineKeyValue>,
+    /// The whitespace between the braces of an empty table
+    pub(crate) trailing: String,
+}
+
+/// A `key = value` in an inline table
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CstInlineKeyValue {
+    /// Whitespace between the `{` or the comma before the key and the key
+    pub(crate) leading: String,
+    pub(crate) key_raw: String,
+    pub(crate) key: Key,
+    /// The `=` with the whitespace around it
+    pub(crate) separator: String,
+    pub(crate) value: CstValue,
+    /// Whitespace between the value and the comma or `}` after it
+    pub(crate) suffix: String,
+}
+
+impl CstValue {
+    /// A value written the way [`TomlValue`] displays it
+    pub(crate) fn new(value: &TomlValue) -> Self {
+        match value {
+            TomlValue::Array(values) => CstValue::Array(CstArray {
+                items: values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| CstItem {
+                        leading: if i == 0 { String::new() } else { " ".to_string() },
+                        value: CstValue::new(value),
+                        suffix: String::new(),
+                    })
+                    .collect(),
+                comma: false,
+                trailing: String::new(),
+            }),
+            TomlValue::Table(table) => {
+                let len = table.len();
+                CstValue::InlineTable(CstInlineTable {
+                    entries: table
+                        .iter()
+                        .enumerate()
+                        .map(|(i, (key, value))| {
+                            let key = Key::new(vec![key.clone()]);
+                            CstInlineKeyValue {
+                                leading: " ".to_string(),
+                                key_raw: LiteralKey(&key).to_string(),
+                                key,
+                                separator: " = ".to_string(),
+                                value: CstValue::new(value),
+                                suffix: if i + 1 == len { " ".to_string() } else { String::new() },
+                            }
+                        })
+                        .collect(),
+                    trailing: String::new(),
+                })
+            }
+            value => CstValue::Scalar(value.to_string()),
+        }
+    }
+}
+
+/// Returns the text of a comment at the end of a line, without the `#`
+fn suffix_comment(suffix: &str) -> Option<&str> {
+    suffix.trim_start().strip_prefix('#')
+}
+
+impl CstDocument {
+    /// All sections of the document, starting with the root section
+    pub fn sections(&self) -> &[CstSection] {
+        &self.sections
+    }
+
+    pub fn sections_mut(&mut self) -> &mut [CstSection] {
+        &mut self.sections
+    }
+
+    /// The section holding the key value pairs defined before the first table header
+    pub fn root(&self) -> &CstSection {
+        &self.sections[0]
+    }
+
+    pub fn root_mut(&mut self) -> &mut CstSection {
+        &mut self.sections[0]
+    }
+
+    /// Returns the section with the standard table header `[header]`
+    pub fn section(&self, header: &Key) -> Option<&CstSection> {
+        self.sections
+            .iter()
+            .find(|section| section.is_standard_table(header))
+    }
+
+    pub fn section_mut(&mut self, header: &Key) -> Option<&mut CstSection> {
+        self.sections
+            .iter_mut()
+            .find(|section| section.is_standard_table(header))
+    }
+
+    /// Empty lines and comments at the end of the document
+    pub fn trailing(&self) -> &str {
+        &self.trailing
+    }
+
+    /// Sets the value at a path like the ones [`Document::get`] takes. The value of a key
+    /// value pair that is already there is replaced, keeping the rest of its line, and a value
+    /// inside an inline table is replaced by writing the inline table anew. A new key is added
+    /// to the end of the table it belongs to, which is added to the end of the document if
+    /// there is none.
+    ///
+    /// Returns `false` if the path is not valid or goes through a value that is neither a
+    /// table nor an array. Whether the document is still consistent, say after a key that was
+    /// a table is given a string, is up to [`to_document`](CstDocument::to_document) to check.
+    pub fn set(&mut self, path: &str, value: TomlValue) -> bool {
+        match parse_path(path) {
+            Some(path) => self.set_at(&path, value),
+            None => false,
+        }
+    }
+
+    /// Removes the value at a path like the ones [`Document::get`] takes, along with the
+    /// comments above it: a key value pair, a key of an inline table or an element of an
+    /// array, or a whole table with its header. Removing `dependencies.nom` removes both
+    /// `nom = "7"` in `[dependencies]` and a `[dependencies.nom]` table.
+    ///
+    /// Returns `false` if there is nothing at the path.
+    pub fn remove(&mut self, path: &str) -> bool {
+        match parse_path(path) {
+            Some(path) => self.remove_at(&path),
+            None => false,
+        }
+    }
+
+    pub(crate) fn set_at(&mut self, path: &[PathSegment], value: TomlValue) -> bool {
+        let paths = self.section_paths();
+
+        for (section, section_path) in self.sections.iter_mut().zip(&paths) {
+            let Some(rest) = path.strip_prefix(section_path.as_slice()) else {
+                continue;
+            };
+            for entry in &mut section.entries {
+                let Some(inner) = rest.strip_prefix(key_path(&entry.key).as_slice()) else {
+                    continue;
+                };
+                let mut new = entry.value.clone();
+                if !set_in_value(&mut new, inner, value) {
+                    return false;
+                }
+                entry.set_value(new);
+                return true;
+            }
+        }
+
+        let Some((PathSegment::Key(name), parent)) = path.split_last() else {
+            return false;
+        };
+        // The section that holds the most of the path, the root section at least
+        let (best, section_path) = paths
+            .iter()
+            .enumerate()
+            .filter(|(_, section_path)| parent.starts_with(section_path))
+            .max_by_key(|(_, section_path)| section_path.len())
+            .expect("the root section holds every path");
+        let rest = &parent[section_path.len()..];
+        let mut segments = vec![];
+        for segment in rest {
+            match segment {
+                PathSegment::Key(key) => segments.push(key.clone()),
+                PathSegment::Index(_) => return false,
+            }
+        }
+        segments.push(name.clone());
+
+        let newline = self.line_ending();
+        let all_keys = parent
+            .iter()
+            .all(|segment| matches!(segment, PathSegment::Key(_)));
+        // A table defined by dotted keys cannot get a header of its own
+        let dotted = self.sections[best]
+            .entries
+            .iter()
+            .any(|entry| key_path(&entry.key).starts_with(rest));
+        if rest.is_empty() || !all_keys || dotted {
+            let section = &mut self.sections[best];
+            let indent = match section.entries.last_mut() {
+                Some(last) => {
+                    if last.newline.is_empty() {
+                        last.newline = newline.clone();
+                    }
+                    last.indent.clone()
+                }
+                None => String::new(),
+            };
+            if let Some(header) = &mut section.header {
+                if header.newline.is_empty() {
+                    header.newline = newline.clone();
+                }
+            }
+            let entry = CstKeyValue::new(indent, Key::new(segments), value, newline);
+            section.entries.push(entry);
+        } else {
+            let key = Key::new(
+                parent
+                    .iter()
+                    .map(|segment| match segment {
+                        PathSegment::Key(key) => key.clone(),
+                        PathSegment::Index(_) => unreachable!("all segments are keys"),
+                    })
+                    .collect(),
+            );
+            let leading = if self.to_string().trim().is_empty() {
+                String::new()
+            } else {
+                newline.clone()
+            };
+            self.end_last_line();
+            let name = Key::new(vec![name.clone()]);
+            let entry = CstKeyValue::new(String::new(), name, value, newline.clone());
+            self.sections.push(CstSection {
+                header: Some(CstHeader {
+                    leading,
+                    raw: format!("[{}]", LiteralKey(&key)),
+                    key,
+                    kind: TableKind::Standard,
+                    suffix: String::new(),
+                    newline,
+                }),
+                entries: vec![entry],
+            });
+        }
+        true
+    }
+
+    pub(crate) fn remove_at(&mut self, path: &[PathSegment]) -> bool {
+        if path.is_empty() {
+            return false;
+        }
+        // Tables of their own, like `[dependencies.nom]`, go with their header
+        let mut paths = self.section_paths();
+        let keep: Vec<bool> = paths
+            .iter()
+            .map(|section_path| !section_path.starts_with(path))
+            .collect();
+        let mut removed = keep.contains(&false);
+        let mut flags = keep.iter();
+        self.sections.retain(|_| *flags.next().unwrap());
+        let mut flags = keep.iter();
+        paths.retain(|_| *flags.next().unwrap());
+
+        for (section, section_path) in self.sections.iter_mut().zip(&paths) {
+            let Some(rest) = path.strip_prefix(section_path.as_slice()) else {
+                continue;
+            };
+            let mut i = 0;
+            while i < section.entries.len() {
+                let key = key_path(&section.entries[i].key);
+                if key.starts_with(rest) {
+                    let entry = section.entries.remove(i);
+                    // The empty lines above the key value pair keep the ones below apart
+                    if let Some(next) = section.entries.get_mut(i) {
+                        let blank = blank_lines(&entry.leading);
+                        if !blank.is_empty() && blank_lines(&next.leading).is_empty() {
+                            next.leading.insert_str(0, blank);
+                        }
+                    }
+                    removed = true;
+                    continue;
+                }
+                if let Some(inner) = rest.strip_prefix(key.as_slice()) {
+                    let entry = &mut section.entries[i];
+                    let mut value = entry.value.clone();
+                    if remove_in_value(&mut value, inner) {
+                        entry.set_value(value);
+                        removed = true;
+                    }
+                }
+                i += 1;
+            }
+        }
+        removed
+    }
+
+    /// The path of each section, with the index of each `[[header]]` in its array, so the
+    /// second `[[bin]]` is at `bin[1]`
+    pub(crate) fn section_paths(&self) -> Vec<Vec<PathSegment>> {
+        let mut counts: HashMap<Vec<String>, usize> = HashMap::new();
+        self.sections
+            .iter()
+            .map(|section| {
+                let Some(header) = &section.header else {
+                    return vec![];
+                };
+                let segments = header.key.segments();
+                if header.kind == TableKind::ArrayOfTables {
+                    // The arrays nested in the previous element start over
+                    counts.retain(|key, _| {
+                        !(key.len() > segments.len() && key.starts_with(segments))
+                    });
+                    *counts.entry(segments.to_vec()).or_default() += 1;
+                }
+                let mut path = vec![];
+                for i in 0..segments.len() {
+                    path.push(PathSegment::Key(segments[i].clone()));
+                    if let Some(count) = counts.get(&segments[..=i]) {
+                        path.push(PathSegment::Index(count - 1));
+                    }
+                }
+                path
+            })
+            .collect()
+    }
+
+    /// The line ending the document uses, `\n` unless its first line ends with `\r\n`
+    pub(crate) fn line_ending(&self) -> String {
+        self.sections
+            .iter()
+            .flat_map(|section| {
+                let header = section.header.iter().map(|header| &header.newline);
+                header.chain(section.entries.iter().map(|entry| &entry.newline))
+            })
+            .find(|newline| !newline.is_empty())
+            .cloned()
+            .unwrap_or_else(|| "\n".to_string())
+    }
+
+    /// Ends the last line of the document, so that another one can follow it
+    pub(crate) fn end_last_line(&mut self) {
+        if !self.trailing.is_empty() {
+            return;
+        }
+        let newline = self.line_ending();
+        let Some(section) = self.sections.last_mut() else {
+            return;
+        };
+        let last = match section.entries.last_mut() {
+            Some(entry) => &mut entry.newline,
+            None => match &mut section.header {
+                Some(header) => &mut header.newline,
+                None => return,
+            },
+        };
+        if last.is_empty() {
+            *last = newline;
+        }
+    }
+
+    /// Drops the formatting and checks the tables and keys for consistency, like
+    /// [`parse`](crate::parse) does. The spans of the document point into the tree as it
+    /// is written now, including any values changed since it was parsed.
+    pub fn to_document(&self) -> Result<Document, Error> {
+        crate::parse(&self.to_string())
+    }
+}
+
+impl CstSection {
+    /// The header of the section, `None` for the root section
+    pub fn header(&self) -> Option<&CstHeader> {
+        self.header.as_ref()
+    }
+
+    pub fn header_mut(&mut self) -> Option<&mut CstHeader> {
+        self.header.as_mut()
+    }
+
+    /// The key value pairs of the section in the order they were written
+    pub fn entries(&self) -> &[CstKeyValue] {
+        &self.entries
+    }
+
+    pub fn entries_mut(&mut self) -> &mut Vec<CstKeyValue> {
+        &mut self.entries
+    }
+
+    /// Returns the key value pair with the given key, which may be dotted
+    pub fn get(&self, key: &Key) -> Option<&CstKeyValue> {
+        self.entries.iter().find(|entry| &entry.key == key)
+    }
+
+    pub fn get_mut(&mut self, key: &Key) -> Option<&mut CstKeyValue> {
+        self.entries.iter_mut().find(|entry| &entry.key == key)
+    }
+
+    fn is_standard_table(&self, header: &Key) -> bool {
+        matches!(&self.header, Some(h) if &h.key == header && h.kind == TableKind::Standard)
+    }
+}
+
+impl CstHeader {
+    pub fn key(&self) -> &Key {
+        &self.key
+    }
+
+    pub fn kind(&self) -> TableKind {
+        self.kind
+    }
+
+    /// Empty lines and comments above the header
+    pub fn leading(&self) -> &str {
+        &self.leading
+    }
+
+    /// The comment after the header on the same line, without the `#`
+    pub fn comment(&self) -> Option<&str> {
+        suffix_comment(&self.suffix)
+    }
+}
+
+impl CstKeyValue {
+    /// A new line `key = value`
+    pub(crate) fn new(indent: String, key: Key, value: TomlValue, newline: String) -> Self {
+        CstKeyValue {
+            leading: String::new(),
+            indent,
+            key_raw: LiteralKey(&key).to_string(),
+            key,
+            separator: " = ".to_string(),
+            value_raw: CstValue::new(&value),
+            value,
+            suffix: String::new(),
+            newline,
+        }
+    }
+
+    pub fn key(&self) -> &Key {
+        &self.key
+    }
+
+    pub fn value(&self) -> &TomlValue {
+        &self.value
+    }
+
+    /// The value exactly as it was written, e.g. `0x10` or `'literal'`
+    pub fn raw_value(&self) -> String {
+        self.value_raw.to_string()
+    }
+
+    /// Empty lines and comments above the key value pair
+    pub fn leading(&self) -> &str {
+        &self.leading
+    }
+
+    /// The comment after the value on the same line, without the `#`
+    pub fn comment(&self) -> Option<&str> {
+        suffix_comment(&self.suffix)
+    }
+
+    /// Replaces the value. The rest of the line, including its comment, is kept as it was.
+    pub fn set_value(&mut self, value: TomlValue) {
+        self.value_raw = CstValue::new(&value);
+        self.value = value;
+    }
+}
+
+pub(crate) fn key_path(key: &Key) -> Vec<PathSegment> {
+    key.segments()
+        .iter()
+        .map(|segment| PathSegment::Key(segment.clone()))
+        .collect()
+}
+
+/// The empty lines at the start of the trivia above a line
+pub(crate) fn blank_lines(leading: &str) -> &str {
+    let mut end = 0;
+    for line in leading.split_inclusive('\n') {
+        if !line.trim().is_empty() {
+            break;
+        }
+        end += line.len();
+    }
+    &leading[..end]
+}
+
+/// Removes the value at `path` inside `value`
+fn remove_in_value(value: &mut TomlValue, path: &[PathSegment]) -> bool {
+    let Some((last, parents)) = path.split_last() else {
+        return false;
+    };
+    let mut value = value;
+    for segment in parents {
+        value = match (value, segment) {
+            (TomlValue::Table(table), PathSegment::Key(key)) => match table.get_mut(key) {
+                Some(value) => value,
+                None => return false,
+            },
+            (TomlValue::Array(values), PathSegment::Index(index)) => match values.get_mut(*index) {
+                Some(value) => value,
+                None => return false,
+            },
+            _ => return false,
+        };
+    }
+    match (value, last) {
+        (TomlValue::Table(table), PathSegment::Key(key)) => table.remove(key).is_some(),
+        (TomlValue::Array(values), PathSegment::Index(index)) if *index < values.len() => {
+            values.remove(*index);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Sets the value at `path` inside `value`, adding the tables that are missing on the way
+fn set_in_value(value: &mut TomlValue, path: &[PathSegment], new: TomlValue) -> bool {
+    let Some((last, parents)) = path.split_last() else {
+        *value = new;
+        return true;
+    };
+    let mut value = value;
+    for segment in parents {
+        value = match (value, segment) {
+            (TomlValue::Table(table), PathSegment::Key(key)) => {
+                if !table.contains_key(key) {
+                    table.insert(key.as_str(), TomlValue::Table(Table::new()));
+                }
+                table.get_mut(key).expect("inserted above")
+            }
+            (TomlValue::Array(values), PathSegment::Index(index)) => match values.get_mut(*index) {
+                Some(value) => value,
+                None => return false,
+            },
+            _ => return false,
+        };
+    }
+    match (value, last) {
+        (TomlValue::Table(table), PathSegment::Key(key)) => {
+            table.insert(key.as_str(), new);
+            true
+        }
+        (TomlValue::Array(values), PathSegment::Index(index)) if *index < values.len() => {
+            values[*index] = new;
+            true
+        }
+        _ => false,
+    }
+}
+
+impl Display for CstDocument {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for section in &self.sections {
+            write!(f, "{}", section)?;
+        }
+        f.write_str(&self.trailing)
+    }
+}
+
+impl Display for CstSection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(header) = &self.header {
+            write!(f, "{}", header)?;
+        }
+        for entry in &self.entries {
+            write!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for CstHeader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.leading)?;
+        f.write_str(&self.raw)?;
+        f.write_str(&self.suffix)?;
+        f.write_str(&self.newline)
+    }
+}
+
+impl Display for CstKeyValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.leading)?;
+        f.write_str(&self.indent)?;
+        f.write_str(&self.key_raw)?;
+        f.write_str(&self.separator)?;
+        write!(f, "{}", self.value_raw)?;
+        f.write_str(&self.suffix)?;
+        f.write_str(&self.newline)
+    }
+}
+
+impl Display for CstValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CstValue::Scalar(raw) => f.write_str(raw),
+            CstValue::Array(array) => {
+                f.write_str("[")?;
+                for (i, item) in array.items.iter().enumerate() {
+                    write!(f, "{}{}{}", item.leading, item.value, item.suffix)?;
+                    if i + 1 < array.items.len() || array.comma {
+                        f.write_str(",")?;
+                    }
+                }
+                write!(f, "{}]", array.trailing)
+            }
+            CstValue::InlineTable(table) => {
+                f.write_str("{")?;
+                for (i, entry) in table.entries.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    f.write_str(&entry.leading)?;
+                    f.write_str(&entry.key_raw)?;
+                    f.write_str(&entry.separator)?;
+                    write!(f, "{}", entry.value)?;
+                    f.write_str(&entry.suffix)?;
+                }
+                write!(f, "{}}}", table.trailing)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_cst {
+    use std::fs::read_to_string;
+
+    use crate::parse_cst;
+
+    use super::*;
+
+    fn assert_lossless(input: &str) {
+        let cst = parse_cst(input).unwrap();
+        assert_eq!(cst.to_string(), input);
+    }
+
+    #[test]
+    fn test_round_trip_assets() {
+        for path in [
+            "assets/array-of-tables.toml",
+            "assets/dotted-keys.toml",
+            "assets/empty-table.toml",
+            "assets/quoted-keys.toml",
+            "assets/root-table.toml",
+            "assets/single-table.toml",
+            "assets/table-key-last-line.toml",
+            "assets/two-tables.toml",
+        ] {
+            assert_lossless(&read_to_string(path).unwrap());
+        }
+        for entry in std::fs::read_dir("assets/cargo_examples").unwrap() {
+            assert_lossless(&read_to_string(entry.unwrap().path()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_round_trip_trivia() {
+        assert_lossless("");
+        assert_lossless("\n\n# only a comment");
+        assert_lossless("# top\r\nkey = 'literal' # why\r\n\r\n  [ table . sub ]   # sub\r\n\tx=1_000\r\n");
+        assert_lossless("list = [\n  1, # one\n  2,\n]\n\n\n[[bin]]\nname = \"\"\"\nmulti\nline\"\"\"   ");
+        assert_lossless("f = 1e1_0\nd = 1979-05-27 07:32:00Z\nh = 0xDEAD_beef\n");
+    }
+
+    #[test]
+    fn test_to_document() {
+        let cst = parse_cst("a = 1\n[t]\nb.c = 'x'\n").unwrap();
+        let doc = cst.to_document().unwrap();
+        assert_eq!(doc.get("t.b.c"), Some(&TomlValue::Str("x".to_string())));
+        assert_eq!(doc, crate::parse("a = 1\n[t]\nb.c = 'x'\n").unwrap());
+    }
+
+    #[test]
+    fn test_comments() {
+        let cst = parse_cst("[package] # the crate\nname = \"x\" # its name\nedition = \"2021\"\n")
+            .unwrap();
+        let package = cst.section(&Key::from(["package"])).unwrap();
+        assert_eq!(package.header().unwrap().comment(), Some(" the crate"));
+        assert_eq!(package.entries()[0].comment(), Some(" its name"));
+        assert_eq!(package.entries()[1].comment(), None);
+    }
+
+    #[test]
+    fn test_set_value_keeps_formatting() {
+        let input = "# deps\n[dependencies]\n# parsing\nnom   =   \"7\"  # keep me\nchrono = \"0.4\"\n";
+        let mut cst = parse_cst(input).unwrap();
+        cst.section_mut(&Key::from(["dependencies"]))
+            .unwrap()
+            .get_mut(&Key::from(["nom"]))
+            .unwrap()
+            .set_value(TomlValue::Str("8".to_string()));
+        assert_eq!(
+            cst.to_string(),
+            "# deps\n[dependencies]\n# parsing\nnom   =   \"8\"  # keep me\nchrono = \"0.4\"\n"
+        );
+    }
+
+    fn set(input: &str, path: &str, value: TomlValue) -> String {
+        let mut cst = parse_cst(input).unwrap();
+        assert!(cst.set(path, value));
+        cst.to_string()
+    }
+
+    #[test]
+    fn test_set() {
+        let input = "[package]\nname = \"x\" # its name\n\n[dependencies]\n  nom = \"7\"\nserde = { version = \"1\" }\n";
+        let string = |s: &str| TomlValue::Str(s.to_string());
+        assert_eq!(
+            set(input, "package.name", string("y")),
+            input.replace("\"x\"", "\"y\"")
+        );
+        assert_eq!(
+            set(input, "dependencies.serde.features", TomlValue::Array(vec![string("derive")])),
+            input.replace(
+                "{ version = \"1\" }",
+                "{ version = \"1\", features = [\"derive\"] }"
+            )
+        );
+        assert_eq!(
+            set(input, "package.edition", string("2021")),
+            input.replace("# its name\n", "# its name\nedition = \"2021\"\n")
+        );
+        assert_eq!(
+            set(input, "dependencies.chrono", string("0.4")),
+            format!("{}chrono = \"0.4\"\n", input)
+        );
+        assert_eq!(
+            set(input, "profile.release.lto", TomlValue::Boolean(true)),
+            format!("{}\n[profile.release]\nlto = true\n", input)
+        );
+        assert_eq!(set("", "name", string("x")), "name = \"x\"\n");
+        assert_eq!(set("a = 1", "b", string("x")), "a = 1\nb = \"x\"\n");
+    }
+
+    #[test]
+    fn test_set_in_array_of_tables() {
+        let input = "[[bin]]\nname = \"a\"\n\n[[bin]]\nname = \"b\"\n";
+        assert_eq!(
+            set(input, "bin[1].name", TomlValue::Str("c".to_string())),
+            input.replace("\"b\"", "\"c\"")
+        );
+        assert_eq!(
+            set(input, "bin[0].test", TomlValue::Boolean(false)),
+            input.replace("\"a\"\n", "\"a\"\ntest = false\n")
+        );
+        let mut cst = parse_cst(input).unwrap();
+        assert!(!cst.set("bin[2].name", TomlValue::Integer(1)));
+        assert!(!cst.set("bin[0].name.x", TomlValue::Integer(1)));
+        assert!(!cst.set("not a path", TomlValue::Integer(1)));
+    }
+
+    #[test]
+    fn test_inconsistent_document() {
+        assert!(parse_cst("a = 1\na = 2\n").is_err());
+        assert!(parse_cst("a = \n").is_err());
+    }
+}
@@ -0,0 +1,525 @@
+use std::fmt::Display;
+
+use serde::de::value::StrDeserializer;
+use serde::de::{
+    DeserializeOwned, Error as _, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    Unexpected, VariantAccess, Visitor,
+};
+use serde::{forward_to_deserialize_any, Deserialize, Deserializer};
+
+use crate::document::Document;
+use crate::error::Error;
+use crate::parsers::{DisplayPath, PathSegment};
+use crate::value::{Table, TomlValue};
+
+/// Deserializes a whole TOML document into `T`. Errors tell the path of the value that did
+/// not fit, like `package.edition`, and point at it in the input.
+pub fn from_str<T: DeserializeOwned>(input: &str) -> Result<T, Error> {
+    from_document(&crate::parse(input)?)
+}
+
+/// Deserializes an already parsed document into `T`, which can borrow strings from it
+pub fn from_document<'de, T: Deserialize<'de>>(document: &'de Document) -> Result<T, Error> {
+    let deserializer = ValueDeserializer {
+        value: Item::Table(document.root()),
+        path: vec![],
+    };
+    T::deserialize(deserializer).map_err(|e| {
+        let path = e.path.unwrap_or_default();
+        let span = document.spans_at(&path).map(|spans| spans.value);
+        Error::deserialize(e.message, DisplayPath(&path).to_string(), span)
+    })
+}
+
+/// The error the serializer and deserializer work with. It is only turned into an [`Error`]
+/// once it leaves them, when the path it was raised at is known.
+#[derive(Debug)]
+pub(crate) struct SerdeError {
+    pub(crate) message: String,
+    /// Set by the innermost value the error passes through
+    pub(crate) path: Option<Vec<PathSegment>>,
+}
+
+impl SerdeError {
+    pub(crate) fn at(mut self, path: &[PathSegment]) -> Self {
+        if self.path.is_none() {
+            self.path = Some(path.to_vec());
+        }
+        self
+    }
+}
+
+impl Display for SerdeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for SerdeError {}
+
+impl serde::de::Error for SerdeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        SerdeError {
+            message: msg.to_string(),
+            path: None,
+        }
+    }
+}
+
+/// The root of a document is a [`Table`] rather than a [`TomlValue`]
+#[derive(Clone, Copy)]
+enum Item<'de> {
+    Value(&'de TomlValue),
+    Table(&'de Table),
+}
+
+impl<'de> Item<'de> {
+    fn new(value: &'de TomlValue) -> Self {
+        match value {
+            TomlValue::Table(table) => Item::Table(table),
+            value => Item::Value(value),
+        }
+    }
+
+    fn unexpected(self) -> Unexpected<'de> {
+        match self {
+            Item::Value(TomlValue::Str(s)) => Unexpected::Str(s),
+            Item::Value(TomlValue::Integer(i)) => Unexpected::Signed(*i),
+            Item::Value(TomlValue::Float(x)) => Unexpected::Float(*x),
+            Item::Value(TomlValue::Boolean(b)) => Unexpected::Bool(*b),
+            Item::Value(TomlValue::Array(_)) => Unexpected::Seq,
+            Item::Value(value) => Unexpected::Other(value.type_name()),
+            Item::Table(_) => Unexpected::Map,
+        }
+    }
+}
+
+/// Deserializes a value along with the path it was found at, to report it with errors
+struct ValueDeserializer<'de> {
+    value: Item<'de>,
+    path: Vec<PathSegment>,
+}
+
+impl<'de> ValueDeserializer<'de> {
+    fn child(&self, value: &'de TomlValue, segment: PathSegment) -> Self {
+        let mut path = self.path.clone();
+        path.push(segment);
+        ValueDeserializer {
+            value: Item::new(value),
+            path,
+        }
+    }
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        let result = match self.value {
+            Item::Table(table) => visitor.visit_map(TableAccess {
+                entries: table.iter(),
+                value: None,
+                parent: &self,
+            }),
+            Item::Value(value) => match value {
+                TomlValue::Str(s) => visitor.visit_borrowed_str(s),
+                TomlValue::Integer(i) => visitor.visit_i64(*i),
+                TomlValue::Float(x) => visitor.visit_f64(*x),
+                TomlValue::Boolean(b) => visitor.visit_bool(*b),
+                // Dates and times are read as the strings they were written as
+                TomlValue::OffsetDateTime(_)
+                | TomlValue::LocalDateTime(_)
+                | TomlValue::LocalDate(_)
+                | TomlValue::LocalTime(_) => visitor.visit_string(value.to_string()),
+                TomlValue::Array(values) => visitor.visit_seq(ArrayAccess {
+                    values: values.iter().enumerate(),
+                    parent: &self,
+                }),
+                TomlValue::Table(_) => unreachable!("tables are an Item::Table"),
+            },
+        };
+        result.map_err(|e| e.at(&self.path))
+    }
+
+    /// TOML has no null, so a value that is there is always `Some`. Missing fields are
+    /// `None`.
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        let path = self.path.clone();
+        visitor.visit_some(self).map_err(|e| e.at(&path))
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        let path = self.path.clone();
+        visitor.visit_newtype_struct(self).map_err(|e| e.at(&path))
+    }
+
+    /// Unit variants are strings, `edition = "2021"`. Variants with data are tables with a
+    /// single key, the name of the variant: `source = { git = "https://..." }`.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        let result = match self.value {
+            Item::Value(TomlValue::Str(s)) => {
+                let variant: StrDeserializer<SerdeError> = s.as_str().into_deserializer();
+                visitor.visit_enum(variant)
+            }
+            Item::Table(table) if table.len() == 1 => {
+                let (name, value) = table.iter().next().unwrap();
+                visitor.visit_enum(Variant {
+                    name,
+                    value: self.child(value, PathSegment::Key(name.clone())),
+                })
+            }
+            item => Err(SerdeError::invalid_type(
+                item.unexpected(),
+                &"a string or a table with a single key",
+            )),
+        };
+        result.map_err(|e| e.at(&self.path))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier
+    }
+}
+
+struct TableAccess<'p, 'de, I> {
+    entries: I,
+    /// The value of the key that was just returned
+    value: Option<(&'de String, &'de TomlValue)>,
+    parent: &'p ValueDeserializer<'de>,
+}
+
+impl<'de, I: Iterator<Item = (&'de String, &'de TomlValue)>> MapAccess<'de>
+    for TableAccess<'_, 'de, I>
+{
+    type Error = SerdeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, SerdeError> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some((key, value));
+                let key: StrDeserializer<SerdeError> = key.as_str().into_deserializer();
+                seed.deserialize(key).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, SerdeError> {
+        let (key, value) = self.value.take().expect("a key is read before its value");
+        seed.deserialize(self.parent.child(value, PathSegment::Key(key.clone())))
+    }
+}
+
+struct ArrayAccess<'p, 'de, I> {
+    values: I,
+    parent: &'p ValueDeserializer<'de>,
+}
+
+impl<'de, I: Iterator<Item = (usize, &'de TomlValue)>> SeqAccess<'de>
+    for ArrayAccess<'_, 'de, I>
+{
+    type Error = SerdeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, SerdeError> {
+        match self.values.next() {
+            Some((index, value)) => seed
+                .deserialize(self.parent.child(value, PathSegment::Index(index)))
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// An enum variant written as a table with a single key
+struct Variant<'de> {
+    name: &'de str,
+    value: ValueDeserializer<'de>,
+}
+
+impl<'de> EnumAccess<'de> for Variant<'de> {
+    type Error = SerdeError;
+    type Variant = ValueDeserializer<'de>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), SerdeError> {
+        let name: StrDeserializer<SerdeError> = self.name.into_deserializer();
+        Ok((seed.deserialize(name)?, self.value))
+    }
+}
+
+impl<'de> VariantAccess<'de> for ValueDeserializer<'de> {
+    type Error = SerdeError;
+
+    /// Unit variants can also be written as a key with an empty table, `variant = {}`
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        match self.value {
+            Item::Table(table) if table.is_empty() => Ok(()),
+            item => Err(SerdeError::invalid_type(item.unexpected(), &"an empty table").at(&self.path)),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, SerdeError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.deserialize_map(visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests_de {
+    use std::collections::{BTreeMap, HashMap};
+
+    use serde::Deserialize;
+
+    use crate::{parse, ErrorKind};
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Manifest {
+        package: Package,
+        #[serde(default)]
+        dependencies: BTreeMap<String, Dependency>,
+        #[serde(default)]
+        bin: Vec<Target>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Package {
+        name: String,
+        edition: Option<Edition>,
+        authors: Option<Vec<String>>,
+        publish: Option<bool>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Edition {
+        #[serde(rename = "2018")]
+        E2018,
+        #[serde(rename = "2021")]
+        E2021,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(untagged)]
+    enum Dependency {
+        Version(String),
+        Detailed {
+            version: Option<String>,
+            features: Option<Vec<String>>,
+        },
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Target {
+        name: String,
+        path: Option<String>,
+    }
+
+    #[test]
+    fn test_manifest() {
+        let input = r#"
+[package]
+name = "manager"
+edition = "2021"
+authors = ["someone"]
+
+[dependencies]
+nom = "7"
+serde = { version = "1", features = ["derive"] }
+
+[[bin]]
+name = "manager"
+
+[[bin]]
+name = "other"
+path = "src/other.rs"
+"#;
+        let manifest: Manifest = from_str(input).unwrap();
+        assert_eq!(
+            manifest,
+            Manifest {
+                package: Package {
+                    name: "manager".to_string(),
+                    edition: Some(Edition::E2021),
+                    authors: Some(vec!["someone".to_string()]),
+                    publish: None,
+                },
+                dependencies: BTreeMap::from([
+                    ("nom".to_string(), Dependency::Version("7".to_string())),
+                    (
+                        "serde".to_string(),
+                        Dependency::Detailed {
+                            version: Some("1".to_string()),
+                            features: Some(vec!["derive".to_string()]),
+                        }
+                    ),
+                ]),
+                bin: vec![
+                    Target {
+                        name: "manager".to_string(),
+                        path: None,
+                    },
+                    Target {
+                        name: "other".to_string(),
+                        path: Some("src/other.rs".to_string()),
+                    },
+                ],
+            }
+        );
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "kebab-case")]
+    enum Source {
+        Registry,
+        Git { url: String, branch: Option<String> },
+        Path(String),
+        Pinned(String, i64),
+    }
+
+    #[test]
+    fn test_enums() {
+        let input = r#"
+a = "registry"
+b = { git = { url = "https://example.com" } }
+c = { path = "../c" }
+d = { pinned = ["x", 1] }
+e = { registry = {} }
+"#;
+        let sources: HashMap<String, Source> = from_str(input).unwrap();
+        assert_eq!(sources["a"], Source::Registry);
+        assert_eq!(
+            sources["b"],
+            Source::Git {
+                url: "https://example.com".to_string(),
+                branch: None
+            }
+        );
+        assert_eq!(sources["c"], Source::Path("../c".to_string()));
+        assert_eq!(sources["d"], Source::Pinned("x".to_string(), 1));
+        assert_eq!(sources["e"], Source::Registry);
+    }
+
+    #[test]
+    fn test_values() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Values {
+            small: u8,
+            float: f32,
+            whole: f64,
+            date: String,
+            tuple: (i32, String),
+            nested: Vec<Vec<i64>>,
+        }
+        let input = "small = 7\nfloat = 1.5\nwhole = 2\ndate = 1979-05-27\ntuple = [1, \"a\"]\nnested = [[1], []]\n";
+        assert_eq!(
+            from_str::<Values>(input).unwrap(),
+            Values {
+                small: 7,
+                float: 1.5,
+                whole: 2.0,
+                date: "1979-05-27".to_string(),
+                tuple: (1, "a".to_string()),
+                nested: vec![vec![1], vec![]],
+            }
+        );
+    }
+
+    #[test]
+    fn test_borrowed_strings() {
+        #[derive(Deserialize)]
+        struct Borrowed<'a> {
+            name: &'a str,
+        }
+        let document = parse("name = \"borrowed\"\n").unwrap();
+        let borrowed: Borrowed = from_document(&document).unwrap();
+        assert_eq!(borrowed.name, "borrowed");
+    }
+
+    #[test]
+    fn test_invalid_type() {
+        let input = "[package]\nname = \"x\"\nedition = 2021\n";
+        let error = from_str::<Manifest>(input).unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::Deserialize {
+                path: "package.edition".to_string()
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "invalid type: integer `2021`, expected a string or a table with a single key \
+             for `package.edition` at line 3, column 11"
+        );
+    }
+
+    #[test]
+    fn test_error_paths() {
+        let input = "[package]\nname = \"x\"\n\n[[bin]]\nname = \"a\"\n\n[[bin]]\npath = \"b\"\n";
+        let error = from_str::<Manifest>(input).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "missing field `name` for `bin[1]` at line 7, column 1"
+        );
+
+        let error = from_str::<Manifest>("[package]\nname = \"x\"\nedition = \"2015\"\n").unwrap_err();
+        assert_eq!(
+            error.message(),
+            "unknown variant `2015`, expected `2018` or `2021` for `package.edition`"
+        );
+        assert_eq!((error.line(), error.column()), (Some(3), Some(11)));
+
+        let error = from_str::<Manifest>("[package]\nname = [\"x\", 1]\n").unwrap_err();
+        assert_eq!(error.message(), "invalid type: sequence, expected a string for `package.name`");
+
+        let error = from_str::<Package>("authors = [\"a\", 1]\nname = \"x\"\n").unwrap_err();
+        assert_eq!(
+            error.message(),
+            "invalid type: integer `1`, expected a string for `authors[1]`"
+        );
+        assert_eq!((error.line(), error.column()), (Some(1), Some(17)));
+
+        let error = from_str::<Manifest>("").unwrap_err();
+        assert_eq!(error.to_string(), "missing field `package`");
+        assert!(error.labels().is_empty());
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let error = from_str::<Manifest>("[package\n").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::Syntax);
+    }
+}
@@ -0,0 +1,299 @@
+use std::fmt::{Display, Formatter};
+
+use crate::error::{Error, Label};
+use crate::lint::{Lint, Severity};
+use crate::span::{LineIndex, Span};
+
+const ERROR: &str = "\x1b[1;31m";
+const WARNING: &str = "\x1b[1;33m";
+const SECONDARY: &str = "\x1b[1;34m";
+const GUTTER: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// An error or a lint rendered the way compilers report them: the message, the file, line and
+/// column, and the lines of the input the error points at with the spans underlined.
+///
+/// ```text
+/// error: `a` is defined more than once (first defined on line 1)
+///  --> Cargo.toml:2:1
+///   |
+/// 1 | a = 1
+///   | - first defined here
+/// 2 | a = 2
+///   | ^ defined again here
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Diagnostic<'a> {
+    /// `error` or `warning`
+    level: &'static str,
+    message: &'a str,
+    span: Option<Span>,
+    labels: &'a [Label],
+    notes: &'a [String],
+    file_name: &'a str,
+    source: &'a str,
+    color: bool,
+}
+
+impl<'a> Diagnostic<'a> {
+    /// Renders `error`, which was returned for parsing `source` read from `file_name`
+    pub fn new(error: &'a Error, file_name: &'a str, source: &'a str) -> Self {
+        Diagnostic {
+            level: "error",
+            message: error.message(),
+            span: error.span(),
+            labels: error.labels(),
+            notes: error.notes(),
+            file_name,
+            source,
+            color: false,
+        }
+    }
+
+    /// Renders `lint`, which was found in `source` read from `file_name`, as an error if
+    /// its rule is set to deny and as a warning otherwise
+    pub fn for_lint(lint: &'a Lint, file_name: &'a str, source: &'a str) -> Self {
+        Diagnostic {
+            level: match lint.severity() {
+                Severity::Deny => "error",
+                Severity::Allow | Severity::Warn => "warning",
+            },
+            message: lint.message(),
+            span: Some(lint.span()),
+            labels: lint.labels(),
+            notes: lint.notes(),
+            file_name,
+            source,
+            color: false,
+        }
+    }
+
+    /// The color of the level and of the primary labels
+    fn style(&self) -> &'static str {
+        if self.level == "error" {
+            ERROR
+        } else {
+            WARNING
+        }
+    }
+
+    /// Whether to highlight the output with ANSI colors, for printing to a terminal
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    fn paint(&self, f: &mut Formatter<'_>, style: &str, text: &str) -> std::fmt::Result {
+        if self.color {
+            write!(f, "{}{}{}", style, text, RESET)
+        } else {
+            f.write_str(text)
+        }
+    }
+
+    /// The underline below a line of the input, and the message of the label
+    fn write_label(
+        &self,
+        f: &mut Formatter<'_>,
+        index: &LineIndex,
+        label: &Label,
+        gutter: &str,
+    ) -> std::fmt::Result {
+        let span = label.span();
+        let text = index.line(span.line());
+        // Tabs are kept, so the underline lines up however wide the terminal shows them
+        let before: String = text
+            .chars()
+            .take(span.column() - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        // Spans over several lines are only underlined up to the end of their first line
+        let end = span.end().min(index.line_start(span.line()) + text.len());
+        let width = self.source[span.start()..end].chars().count().max(1);
+        let (style, mark) = if label.is_primary() {
+            (self.style(), "^")
+        } else {
+            (SECONDARY, "-")
+        };
+
+        self.paint(f, GUTTER, &format!("{} |", gutter))?;
+        write!(f, " {}", before)?;
+        self.paint(f, style, &format!("{} {}", mark.repeat(width), label.message()))?;
+        writeln!(f)
+    }
+}
+
+impl Display for Diagnostic<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.paint(f, self.style(), self.level)?;
+        self.paint(f, BOLD, &format!(": {}", self.message))?;
+        writeln!(f)?;
+
+        let mut labels: Vec<&Label> = self.labels.iter().collect();
+        let width = labels
+            .iter()
+            .map(|label| label.span().line().to_string().len())
+            .max()
+            .unwrap_or(0);
+        let gutter = " ".repeat(width);
+
+        if let Some(span) = self.span {
+            self.paint(f, GUTTER, &format!("{}-->", gutter))?;
+            writeln!(f, " {}:{}:{}", self.file_name, span.line(), span.column())?;
+            self.paint(f, GUTTER, &format!("{} |", gutter))?;
+            writeln!(f)?;
+        }
+
+        let index = LineIndex::new(self.source);
+        labels.sort_by_key(|label| label.span().start());
+        let mut previous: Option<usize> = None;
+        for label in labels {
+            let line = label.span().line();
+            if previous != Some(line) {
+                if matches!(previous, Some(previous) if line > previous + 1) {
+                    self.paint(f, GUTTER, "...")?;
+                    writeln!(f)?;
+                }
+                self.paint(f, GUTTER, &format!("{:>width$} |", line, width = width))?;
+                writeln!(f, " {}", index.line(line))?;
+            }
+            self.write_label(f, &index, label, &gutter)?;
+            previous = Some(line);
+        }
+
+        for note in self.notes {
+            self.paint(f, GUTTER, &format!("{} =", gutter))?;
+            writeln!(f, " note: {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests_diagnostic {
+    use crate::parse;
+
+    use super::*;
+
+    fn render(input: &str) -> String {
+        let error = parse(input).unwrap_err();
+        Diagnostic::new(&error, "Cargo.toml", input).to_string()
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        assert_eq!(
+            render("[package]\nname \"x\"\n"),
+            "\
+error: expected `=` in key value pair
+ --> Cargo.toml:2:6
+  |
+2 | name \"x\"
+  |      ^ expected `=`
+"
+        );
+    }
+
+    #[test]
+    fn test_duplicate_key() {
+        assert_eq!(
+            render("[dependencies]\nnom = \"7\"\nserde = \"1\"\nnom = \"6\"\n"),
+            "\
+error: `dependencies.nom` is defined more than once (first defined on line 2)
+ --> Cargo.toml:4:1
+  |
+2 | nom = \"7\"
+  | --- first defined here
+...
+4 | nom = \"6\"
+  | ^^^ defined again here
+"
+        );
+    }
+
+    #[test]
+    fn test_notes_and_wide_gutter() {
+        let mut input = "[a]\nx = 1\n".to_string();
+        input.push_str(&"\n".repeat(8));
+        input.push_str("[a]\n");
+        assert_eq!(
+            render(&input),
+            "\
+error: table `a` is defined more than once (first defined on line 1)
+  --> Cargo.toml:11:1
+   |
+ 1 | [a]
+   | --- first defined here
+...
+11 | [a]
+   | ^^^ defined again here
+   = note: a table can only be defined once, by a header or by dotted keys
+"
+        );
+    }
+
+    #[test]
+    fn test_tabs_and_end_of_input() {
+        assert_eq!(
+            render("\tkey = [1,"),
+            "\
+error: expected a value or `]` in array
+ --> Cargo.toml:1:11
+  |
+1 | \tkey = [1,
+  | \t         ^ expected a value or `]`
+"
+        );
+    }
+
+    #[test]
+    fn test_color() {
+        let error = parse("a = \n").unwrap_err();
+        let plain = Diagnostic::new(&error, "x.toml", "a = \n").to_string();
+        let colored = Diagnostic::new(&error, "x.toml", "a = \n")
+            .with_color(true)
+            .to_string();
+        assert!(!plain.contains('\x1b'));
+        assert!(colored.starts_with("\x1b[1;31merror\x1b[0m"));
+        assert!(colored.contains("\x1b[1;31m^ expected a value\x1b[0m"));
+    }
+
+    #[test]
+    fn test_lint() {
+        let input = "[dependencies]\nnom = \"7\"\n\n[dev-dependencies]\nnom = \"*\"\n";
+        let config = crate::LintConfig::default().with_severity("wildcard-version", Severity::Deny);
+        let linter = crate::Linter::new().with_config(config).unwrap();
+        let lints = linter.lint(input).unwrap();
+        let rendered: Vec<_> = lints
+            .iter()
+            .map(|lint| Diagnostic::for_lint(lint, "Cargo.toml", input).to_string())
+            .collect();
+        assert_eq!(
+            rendered,
+            vec![
+                "\
+warning: `nom` is already a dependency in `[dependencies]`
+ --> Cargo.toml:5:1
+  |
+2 | nom = \"7\"
+  | --- first declared here
+...
+5 | nom = \"*\"
+  | ^^^ `nom` is already a dependency in `[dependencies]`
+  = note: `duplicate-dependency` is set to `warn`, `# lint: allow(duplicate-dependency)` \
+allows it on a line
+",
+                "\
+error: `nom` accepts any version
+ --> Cargo.toml:5:7
+  |
+5 | nom = \"*\"
+  |       ^^^ `nom` accepts any version
+  = note: `wildcard-version` is set to `deny`, `# lint: allow(wildcard-version)` allows it \
+on a line
+",
+            ]
+        );
+    }
+}
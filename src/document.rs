@@ -0,0 +1,502 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use crate::consistency::remove_inconsistencies;
+use crate::error::Error;
+use crate::parsers::{parse_path, Key, KeyValue, PathSegment, Section, TableKind, TomlValue};
+use crate::span::{KeyValueSpans, LineIndex, NestedSpans, Spans, ValueSpan};
+use crate::value::Table;
+
+/// A parsed TOML document, as returned by [`parse`](crate::parse).
+#[derive(Debug)]
+pub struct Document {
+    root_section: Section,
+    sections: Vec<Section>,
+    root: Table,
+    spans: HashMap<Vec<PathSegment>, Spans>,
+}
+
+/// Documents are equal if they define the same sections, wherever they are in the input
+impl PartialEq for Document {
+    fn eq(&self, other: &Self) -> bool {
+        self.root_section == other.root_section
+            && self.sections == other.sections
+            && self.root == other.root
+    }
+}
+
+impl Document {
+    /// Builds the document from the sections parsed from `source`
+    pub(crate) fn new(
+        source: &str,
+        root_section: Section,
+        sections: Vec<Section>,
+    ) -> Result<Self, Error> {
+        let (document, errors) = Document::recovering(source, root_section, sections);
+        match errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(document),
+        }
+    }
+
+    /// Builds the document from the sections parsed from `source`, leaving out the key value
+    /// pairs and tables that contradict the ones before them. Returns an error for each.
+    pub(crate) fn recovering(
+        source: &str,
+        mut root_section: Section,
+        mut sections: Vec<Section>,
+    ) -> (Self, Vec<Error>) {
+        let index = LineIndex::new(source);
+        let errors = remove_inconsistencies(&index, &mut root_section, &mut sections);
+        let root = build_tree(&root_section, &sections);
+        let spans = collect_spans(&index, &root_section, &sections);
+        let document = Document {
+            root_section,
+            sections,
+            root,
+            spans,
+        };
+        (document, errors)
+    }
+
+    /// Returns the root table, with the tables of the document nested by their headers and
+    /// dotted keys, so `a.b = 1` and `[a]` followed by `b = 1` result in the same table.
+    /// Each `[[header]]` appends a table to a [`TomlValue::Array`].
+    pub fn root(&self) -> &Table {
+        &self.root
+    }
+
+    /// Returns the value at a dotted path such as `dependencies.serde.version`. Segments can
+    /// be quoted like in keys, `target."cfg(unix)".dependencies`, and followed by array
+    /// indices, `bin[0].name`. Returns `None` if nothing is found or the path is not valid.
+    pub fn get(&self, path: &str) -> Option<&TomlValue> {
+        self.root.lookup(&parse_path(path)?)
+    }
+
+    /// Like [`get`](Document::get), but allows changing the value. Only the nested tables are
+    /// changed, the sections keep what was parsed.
+    pub fn get_mut(&mut self, path: &str) -> Option<&mut TomlValue> {
+        self.root.lookup_mut(&parse_path(path)?)
+    }
+
+    /// Returns where the key, value or table at a path like the ones [`get`](Document::get)
+    /// takes was defined in the input. Changes made through [`get_mut`](Document::get_mut)
+    /// do not move the spans.
+    pub fn spans(&self, path: &str) -> Option<&Spans> {
+        self.spans_at(&parse_path(path)?)
+    }
+
+    /// Like [`spans`](Document::spans), for a path that is already split into its segments
+    pub(crate) fn spans_at(&self, path: &[PathSegment]) -> Option<&Spans> {
+        self.spans.get(path)
+    }
+
+    /// Returns the section holding the key value pairs defined before the first table
+    /// header. Its header is always empty.
+    pub fn root_section(&self) -> &Section {
+        &self.root_section
+    }
+
+    /// Returns the sections that start with a table header, in the order they were defined
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+}
+
+/// Writes the document as TOML. Comments and formatting are not kept, and every table is
+/// written with a header of its own.
+impl Display for Document {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.root)
+    }
+}
+
+/// Walks into the tables a dotted key is made of and adds the value to the last one
+fn insert_key_value(table: &mut Table, key_val: &KeyValue) {
+    let inserted = table.insert_dotted(key_val.0.segments(), key_val.1.clone());
+    debug_assert!(inserted, "rejected by the consistency check");
+}
+
+/// Nests the sections by their headers and dotted keys. Expects sections that passed the
+/// consistency check.
+fn build_tree(root_section: &Section, sections: &[Section]) -> Table {
+    let mut root = Table::new();
+    for key_val in &root_section.key_val_vec {
+        insert_key_value(&mut root, key_val);
+    }
+
+    for section in sections {
+        let (name, parents) = section
+            .header
+            .segments()
+            .split_last()
+            .expect("table headers are not empty");
+        let mut parent = &mut root;
+        for segment in parents {
+            parent = parent
+                .subtable_mut(segment)
+                .expect("rejected by the consistency check");
+        }
+
+        let table = match section.kind {
+            TableKind::Standard => parent
+                .subtable_mut(name)
+                .expect("rejected by the consistency check"),
+            TableKind::ArrayOfTables => {
+                if !parent.contains_key(name) {
+                    parent.insert(name.as_str(), TomlValue::Array(vec![]));
+                }
+                match parent.get_mut(name) {
+                    Some(TomlValue::Array(elements)) => {
+                        elements.push(TomlValue::Table(Table::new()));
+                        match elements.last_mut() {
+                            Some(TomlValue::Table(table)) => table,
+                            _ => unreachable!("a table was just pushed"),
+                        }
+                    }
+                    _ => unreachable!("rejected by the consistency check"),
+                }
+            }
+        };
+        for key_val in &section.key_val_vec {
+            insert_key_value(table, key_val);
+        }
+    }
+
+    root
+}
+
+/// Finds the spans of every table and value, by the same paths [`build_tree`] nests them at
+fn collect_spans(
+    index: &LineIndex,
+    root_section: &Section,
+    sections: &[Section],
+) -> HashMap<Vec<PathSegment>, Spans> {
+    let mut spans = HashMap::new();
+    // The number of elements each array of tables has so far
+    let mut array_lengths: HashMap<Vec<PathSegment>, usize> = HashMap::new();
+
+    collect_key_value_spans(
+        index,
+        &mut spans,
+        &[],
+        key_value_pairs(root_section),
+    );
+
+    for section in sections {
+        let header = match (&section.spans.header, section.spans.whole()) {
+            (Some(header), Some(whole)) => Spans {
+                key: Some(index.resolve(header.key)),
+                value: index.resolve(whole),
+                header: Some(index.resolve(header.header)),
+            },
+            _ => unreachable!("sections after the root have a header"),
+        };
+
+        let segments = section.header.segments();
+        let mut path = Vec::with_capacity(segments.len());
+        for (i, segment) in segments.iter().enumerate() {
+            path.push(PathSegment::Key(segment.clone()));
+            let is_last = i + 1 == segments.len();
+            if is_last && section.kind == TableKind::ArrayOfTables {
+                spans.entry(path.clone()).or_insert(header);
+                let length = array_lengths.entry(path.clone()).or_insert(0);
+                *length += 1;
+                path.push(PathSegment::Index(*length - 1));
+            } else if let Some(length) = array_lengths.get(&path) {
+                path.push(PathSegment::Index(length - 1));
+            }
+
+            if is_last {
+                spans.insert(path.clone(), header);
+            } else {
+                spans.entry(path.clone()).or_insert(header);
+            }
+        }
+
+        collect_key_value_spans(
+            index,
+            &mut spans,
+            &path,
+            key_value_pairs(section),
+        );
+    }
+
+    spans
+}
+
+/// The keys of a section along with their spans
+fn key_value_pairs(section: &Section) -> impl Iterator<Item = (&Key, &KeyValueSpans)> {
+    let keys = section.key_val_vec.iter().map(|key_val| &key_val.0);
+    keys.zip(&section.spans.key_values)
+}
+
+/// Adds the spans of the key value pairs of a table at `path`. The tables dotted keys define
+/// implicitly point at the first key value pair that mentions them.
+fn collect_key_value_spans<'a>(
+    index: &LineIndex,
+    spans: &mut HashMap<Vec<PathSegment>, Spans>,
+    path: &[PathSegment],
+    key_values: impl Iterator<Item = (&'a Key, &'a KeyValueSpans)>,
+) {
+    for (key, key_value) in key_values {
+        let key_span = Some(index.resolve(key_value.key));
+        let mut path = path.to_vec();
+        let (last, parents) = key.segments().split_last().expect("keys are not empty");
+        for segment in parents {
+            path.push(PathSegment::Key(segment.clone()));
+            spans.entry(path.clone()).or_insert(Spans {
+                key: key_span,
+                value: index.resolve(key_value.whole()),
+                header: None,
+            });
+        }
+        path.push(PathSegment::Key(last.clone()));
+        spans.insert(
+            path.clone(),
+            Spans {
+                key: key_span,
+                value: index.resolve(key_value.value.span),
+                header: None,
+            },
+        );
+        collect_nested_spans(index, spans, &path, &key_value.value);
+    }
+}
+
+/// Adds the spans of the elements of an array, or of the key value pairs of an inline table
+fn collect_nested_spans(
+    index: &LineIndex,
+    spans: &mut HashMap<Vec<PathSegment>, Spans>,
+    path: &[PathSegment],
+    value: &ValueSpan,
+) {
+    match &value.nested {
+        NestedSpans::None => {}
+        NestedSpans::Array(elements) => {
+            for (i, element) in elements.iter().enumerate() {
+                let mut path = path.to_vec();
+                path.push(PathSegment::Index(i));
+                spans.insert(
+                    path.clone(),
+                    Spans {
+                        key: None,
+                        value: index.resolve(element.span),
+                        header: None,
+                    },
+                );
+                collect_nested_spans(index, spans, &path, element);
+            }
+        }
+        NestedSpans::Table(key_values) => {
+            let key_values = key_values.iter().map(|(key, spans)| (key, spans));
+            collect_key_value_spans(index, spans, path, key_values);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_document {
+    use std::fs::read_to_string;
+
+    use crate::parse;
+    use crate::span::Span;
+
+    use super::*;
+
+    fn table<'a>(table: &'a Table, name: &str) -> &'a Table {
+        match table.get(name) {
+            Some(TomlValue::Table(table)) => table,
+            other => panic!("`{}` is not a table: {:?}", name, other),
+        }
+    }
+
+    fn array_of_tables<'a>(table: &'a Table, name: &str) -> Vec<&'a Table> {
+        match table.get(name) {
+            Some(TomlValue::Array(values)) => values
+                .iter()
+                .map(|value| match value {
+                    TomlValue::Table(table) => table,
+                    other => panic!("`{}` holds a value that is not a table: {:?}", name, other),
+                })
+                .collect(),
+            other => panic!("`{}` is not an array: {:?}", name, other),
+        }
+    }
+
+    fn name(table: &Table) -> &TomlValue {
+        table.get("name").unwrap()
+    }
+
+    #[test]
+    fn test_array_of_tables() {
+        let input = read_to_string("assets/array-of-tables.toml").unwrap();
+        let doc = parse(&input).unwrap();
+
+        let fruits = array_of_tables(doc.root(), "fruits");
+        assert_eq!(fruits.len(), 2);
+        assert_eq!(name(fruits[0]), &TomlValue::Str("apple".to_string()));
+        assert_eq!(name(fruits[1]), &TomlValue::Str("banana".to_string()));
+
+        let physical = table(fruits[0], "physical");
+        assert_eq!(physical.len(), 2);
+
+        let apple_varieties = array_of_tables(fruits[0], "varieties");
+        assert_eq!(apple_varieties.len(), 2);
+        assert_eq!(
+            name(apple_varieties[1]),
+            &TomlValue::Str("granny smith".to_string())
+        );
+
+        let banana_varieties = array_of_tables(fruits[1], "varieties");
+        assert_eq!(banana_varieties.len(), 1);
+        assert_eq!(
+            name(banana_varieties[0]),
+            &TomlValue::Str("plantain".to_string())
+        );
+    }
+
+    #[test]
+    fn test_nested_standard_tables() {
+        let doc = parse("[a.b]\nkey = 1\n[a]\nkey = 2\n").unwrap();
+        let a = table(doc.root(), "a");
+        assert_eq!(a.get("key"), Some(&TomlValue::Integer(2)));
+        assert_eq!(table(a, "b").get("key"), Some(&TomlValue::Integer(1)));
+    }
+
+    #[test]
+    fn test_dotted_keys_expand_into_tables() {
+        let dotted = parse("a.b = 1\n").unwrap();
+        let header = parse("[a]\nb = 1\n").unwrap();
+        assert_eq!(dotted.root(), header.root());
+        assert_eq!(
+            table(dotted.root(), "a").get("b"),
+            Some(&TomlValue::Integer(1))
+        );
+    }
+
+    #[test]
+    fn test_dotted_keys_in_tables() {
+        let input = read_to_string("assets/dotted-keys.toml").unwrap();
+        let doc = parse(&input).unwrap();
+        let apple = table(table(doc.root(), "fruit"), "apple");
+        assert_eq!(apple.get("color"), Some(&TomlValue::Str("red".to_string())));
+        assert_eq!(
+            table(apple, "taste").get("sweet"),
+            Some(&TomlValue::Boolean(true))
+        );
+        assert_eq!(
+            table(apple, "texture").get("smooth"),
+            Some(&TomlValue::Boolean(true))
+        );
+    }
+
+    #[test]
+    fn test_quoted_key_segments() {
+        let input = read_to_string("assets/quoted-keys.toml").unwrap();
+        let doc = parse(&input).unwrap();
+        assert_eq!(
+            table(doc.root(), "site").get("google.com"),
+            Some(&TomlValue::Boolean(true))
+        );
+        let dependencies = table(table(table(doc.root(), "target"), "cfg(windows)"), "dependencies");
+        assert_eq!(
+            dependencies.get("winapi"),
+            Some(&TomlValue::Str("0.3".to_string()))
+        );
+        assert_eq!(
+            doc.root().get("quoted \"value\""),
+            Some(&TomlValue::Integer(1))
+        );
+    }
+
+    #[test]
+    fn test_get_path() {
+        let input = read_to_string("assets/quoted-keys.toml").unwrap();
+        let doc = parse(&input).unwrap();
+        assert_eq!(doc.get("site.\"google.com\""), Some(&TomlValue::Boolean(true)));
+        assert_eq!(
+            doc.get("target.'cfg(windows)'.dependencies.winapi"),
+            Some(&TomlValue::Str("0.3".to_string()))
+        );
+        assert_eq!(doc.get("site.google"), None);
+        assert_eq!(doc.get("site..google"), None);
+    }
+
+    #[test]
+    fn test_get_indexed_path() {
+        let input = read_to_string("assets/array-of-tables.toml").unwrap();
+        let mut doc = parse(&input).unwrap();
+        assert_eq!(
+            doc.get("fruits[1].varieties[0].name"),
+            Some(&TomlValue::Str("plantain".to_string()))
+        );
+        assert_eq!(doc.get("fruits[2]"), None);
+        assert_eq!(doc.get("fruits[0].name[0]"), None);
+
+        *doc.get_mut("fruits[0].name").unwrap() = TomlValue::Str("pear".to_string());
+        assert_eq!(doc.root()["fruits"][0]["name"], TomlValue::Str("pear".to_string()));
+    }
+
+    #[test]
+    fn test_inline_tables_in_document() {
+        let doc = parse("[dependencies]\nserde = { version = \"1.0\", features = [\"derive\"] }\n")
+            .unwrap();
+        let serde = table(table(doc.root(), "dependencies"), "serde");
+        assert_eq!(serde.get("version"), Some(&TomlValue::Str("1.0".to_string())));
+        assert_eq!(
+            serde.get("features"),
+            Some(&TomlValue::Array(vec![TomlValue::Str("derive".to_string())]))
+        );
+    }
+
+    #[test]
+    fn test_spans() {
+        let input = "title = \"x\"\n\n[dependencies]\nserde = { version = \"1.0\" }\nnom.version = \"7\"\n";
+        let doc = parse(input).unwrap();
+        let text = |span: Span| &input[span.range()];
+
+        let title = doc.spans("title").unwrap();
+        assert_eq!(text(title.key.unwrap()), "title");
+        assert_eq!(text(title.value), "\"x\"");
+        assert_eq!(title.header, None);
+
+        let dependencies = doc.spans("dependencies").unwrap();
+        assert_eq!(text(dependencies.header.unwrap()), "[dependencies]");
+        assert_eq!(text(dependencies.key.unwrap()), "dependencies");
+        assert_eq!(
+            text(dependencies.value),
+            "[dependencies]\nserde = { version = \"1.0\" }\nnom.version = \"7\""
+        );
+
+        let version = doc.spans("dependencies.serde.version").unwrap();
+        assert_eq!(text(version.value), "\"1.0\"");
+        assert_eq!((version.value.line(), version.value.column()), (4, 21));
+
+        // `nom` is only defined by the dotted key
+        let nom = doc.spans("dependencies.nom").unwrap();
+        assert_eq!(text(nom.key.unwrap()), "nom.version");
+        assert_eq!(text(nom.value), "nom.version = \"7\"");
+
+        assert_eq!(doc.spans("dependencies.other"), None);
+    }
+
+    #[test]
+    fn test_spans_of_arrays() {
+        let input = read_to_string("assets/array-of-tables.toml").unwrap();
+        let doc = parse(&input).unwrap();
+        let text = |span: Span| &input[span.range()];
+
+        let banana = doc.spans("fruits[1]").unwrap();
+        assert!(text(banana.value).contains("banana"));
+        let plantain = doc.spans("fruits[1].varieties[0].name").unwrap();
+        assert_eq!(text(plantain.value), "\"plantain\"");
+
+        let doc = parse("[[bin]]\nname = 'a'\n  [ bin . x ]\nports = [80, 443]\n").unwrap();
+        let port = doc.spans("bin[0].x.ports[1]").unwrap();
+        assert_eq!(port.key, None);
+        assert_eq!(port.value.to_string(), "4:14");
+        let x = doc.spans("bin[0].x").unwrap();
+        assert_eq!(x.header.unwrap().to_string(), "3:3");
+    }
+}
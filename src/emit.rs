@@ -0,0 +1,278 @@
+use std::fmt::{Result, Write};
+
+use crate::parsers::Key;
+use crate::value::{Table, TomlValue};
+
+/// Writes a string as a TOML basic string, escaping quotes, backslashes and control
+/// characters. Everything else is written as is, so the output stays readable.
+pub(crate) fn write_string<W: Write + ?Sized>(out: &mut W, s: &str) -> Result {
+    out.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\t' => out.write_str("\\t")?,
+            '\r' => out.write_str("\\r")?,
+            '\u{08}' => out.write_str("\\b")?,
+            '\u{0C}' => out.write_str("\\f")?,
+            c if c.is_control() => write!(out, "\\u{:04X}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+/// Floats are written so that they read back as floats: Rust's debug format always has a
+/// fraction or an exponent, and infinities and NaNs are spelled the TOML way.
+fn write_float<W: Write + ?Sized>(out: &mut W, x: f64) -> Result {
+    if x.is_nan() {
+        out.write_str("nan")
+    } else if x.is_infinite() {
+        out.write_str(if x > 0.0 { "inf" } else { "-inf" })
+    } else {
+        write!(out, "{:?}", x)
+    }
+}
+
+/// Writes a value the way it appears on the right side of a key value pair. Tables are
+/// written as inline tables.
+pub(crate) fn write_value<W: Write + ?Sized>(out: &mut W, value: &TomlValue) -> Result {
+    match value {
+        TomlValue::Str(s) => write_string(out, s),
+        TomlValue::Integer(i) => write!(out, "{}", i),
+        TomlValue::Float(x) => write_float(out, *x),
+        TomlValue::Boolean(b) => write!(out, "{}", b),
+        TomlValue::OffsetDateTime(dt) => out.write_str(&dt.to_rfc3339()),
+        TomlValue::LocalDateTime(dt) => write!(out, "{}", dt.format("%Y-%m-%dT%H:%M:%S%.f")),
+        TomlValue::LocalDate(d) => write!(out, "{}", d.format("%Y-%m-%d")),
+        TomlValue::LocalTime(t) => write!(out, "{}", t.format("%H:%M:%S%.f")),
+        TomlValue::Array(values) => {
+            out.write_char('[')?;
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.write_str(", ")?;
+                }
+                write_value(out, value)?;
+            }
+            out.write_char(']')
+        }
+        TomlValue::Table(table) => write_inline_table(out, table),
+    }
+}
+
+fn write_inline_table<W: Write + ?Sized>(out: &mut W, table: &Table) -> Result {
+    if table.is_empty() {
+        return out.write_str("{}");
+    }
+    out.write_str("{ ")?;
+    for (i, (key, value)) in table.iter().enumerate() {
+        if i > 0 {
+            out.write_str(", ")?;
+        }
+        write_key_value(out, &Key::new(vec![key.clone()]), value)?;
+    }
+    out.write_str(" }")
+}
+
+pub(crate) fn write_key_value<W: Write + ?Sized>(out: &mut W, key: &Key, value: &TomlValue) -> Result {
+    write!(out, "{} = ", key)?;
+    write_value(out, value)
+}
+
+/// A non-empty array that only holds tables is written as an array of tables
+fn is_array_of_tables(value: &TomlValue) -> bool {
+    match value {
+        TomlValue::Array(values) => {
+            !values.is_empty() && values.iter().all(|v| matches!(v, TomlValue::Table(_)))
+        }
+        _ => false,
+    }
+}
+
+/// Values that can be written as a key value pair of the table they are in
+fn is_plain(value: &TomlValue) -> bool {
+    !matches!(value, TomlValue::Table(_)) && !is_array_of_tables(value)
+}
+
+/// Writes tables as a whole document, with a `[header]` or `[[header]]` for each subtable
+struct DocumentWriter<'w, W: Write + ?Sized> {
+    out: &'w mut W,
+    /// Headers after the first line are separated from what came before by an empty line
+    started: bool,
+}
+
+impl<'w, W: Write + ?Sized> DocumentWriter<'w, W> {
+    fn header(&mut self, path: &[String], kind: (&str, &str)) -> Result {
+        if self.started {
+            self.out.write_char('\n')?;
+        }
+        self.started = true;
+        writeln!(self.out, "{}{}{}", kind.0, Key::new(path.to_vec()), kind.1)
+    }
+
+    /// All of a table's plain values have to come before its first subtable, otherwise they
+    /// would be read back as part of the subtable
+    fn table(&mut self, path: &mut Vec<String>, table: &Table) -> Result {
+        for (key, value) in table.iter().filter(|(_, value)| is_plain(value)) {
+            write_key_value(self.out, &Key::new(vec![key.clone()]), value)?;
+            self.out.write_char('\n')?;
+            self.started = true;
+        }
+
+        for (key, value) in table {
+            path.push(key.clone());
+            match value {
+                TomlValue::Table(subtable) => {
+                    // Tables that only hold other tables are defined by their subtables' headers
+                    if subtable.is_empty() || subtable.values().any(is_plain) {
+                        self.header(path, ("[", "]"))?;
+                    }
+                    self.table(path, subtable)?;
+                }
+                TomlValue::Array(elements) if is_array_of_tables(value) => {
+                    for element in elements {
+                        self.header(path, ("[[", "]]"))?;
+                        if let TomlValue::Table(element) = element {
+                            self.table(path, element)?;
+                        }
+                    }
+                }
+                _ => {}
+            }
+            path.pop();
+        }
+        Ok(())
+    }
+}
+
+/// Writes a table as a TOML document, the root table first, followed by its subtables
+pub(crate) fn write_document<W: Write + ?Sized>(out: &mut W, root: &Table) -> Result {
+    DocumentWriter {
+        out,
+        started: false,
+    }
+    .table(&mut Vec::new(), root)
+}
+
+#[cfg(test)]
+mod tests_emit {
+    use std::fs::read_to_string;
+
+    use chrono::{FixedOffset, NaiveDate, NaiveTime, TimeZone};
+
+    use crate::parse;
+
+    use super::*;
+
+    fn emit(root: &Table) -> String {
+        let mut out = String::new();
+        write_document(&mut out, root).unwrap();
+        out
+    }
+
+    fn assert_round_trip(input: &str) {
+        let doc = parse(input).unwrap();
+        let emitted = emit(doc.root());
+        let reparsed = parse(&emitted)
+            .unwrap_or_else(|e| panic!("emitted an invalid document ({}):\n{}", e, emitted));
+        assert_eq!(doc.root(), reparsed.root(), "emitted:\n{}", emitted);
+    }
+
+    #[test]
+    fn test_write_string() {
+        let mut out = String::new();
+        write_string(&mut out, "quote \" slash \\ tab \t bell \u{7} é").unwrap();
+        assert_eq!(out, r#""quote \" slash \\ tab \t bell \u0007 é""#);
+    }
+
+    #[test]
+    fn test_write_values() {
+        let value = |v: TomlValue| {
+            let mut out = String::new();
+            write_value(&mut out, &v).unwrap();
+            out
+        };
+        assert_eq!(value(TomlValue::Float(1.0)), "1.0");
+        assert_eq!(value(TomlValue::Float(1.5e-7)), "1.5e-7");
+        assert_eq!(value(TomlValue::Float(f64::NEG_INFINITY)), "-inf");
+        assert_eq!(value(TomlValue::Float(f64::NAN)), "nan");
+        assert_eq!(
+            value(TomlValue::LocalDate(NaiveDate::from_ymd_opt(1979, 5, 27).unwrap())),
+            "1979-05-27"
+        );
+        assert_eq!(
+            value(TomlValue::LocalTime(
+                NaiveTime::from_hms_micro_opt(7, 32, 0, 999999).unwrap()
+            )),
+            "07:32:00.999999"
+        );
+        assert_eq!(
+            value(TomlValue::OffsetDateTime(
+                FixedOffset::west_opt(7 * 3600)
+                    .unwrap()
+                    .with_ymd_and_hms(1979, 5, 27, 0, 32, 0)
+                    .unwrap()
+            )),
+            "1979-05-27T00:32:00-07:00"
+        );
+        assert_eq!(
+            value(TomlValue::Table(Table::from_iter([
+                ("version", TomlValue::Str("1.0".to_string())),
+                ("default-features", TomlValue::Boolean(false)),
+            ]))),
+            r#"{ version = "1.0", default-features = false }"#
+        );
+    }
+
+    #[test]
+    fn test_write_document() {
+        let doc = parse(
+            "title = \"x\"\n[a.b]\nc = 1\n[[bin]]\nname = \"one\"\n[[bin]]\nname = \"two\"\n[empty]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            emit(doc.root()),
+            "title = \"x\"\n\n[a.b]\nc = 1\n\n[[bin]]\nname = \"one\"\n\n[[bin]]\nname = \"two\"\n\n[empty]\n"
+        );
+    }
+
+    #[test]
+    fn test_values_before_subtables() {
+        let mut root = Table::new();
+        root.insert("table", TomlValue::Table(Table::from_iter([("a", TomlValue::Integer(1))])));
+        root.insert("key", TomlValue::Integer(2));
+        assert_eq!(emit(&root), "key = 2\n\n[table]\na = 1\n");
+    }
+
+    #[test]
+    fn test_round_trip_values() {
+        assert_round_trip(concat!(
+            "str = \"line\\nbreak \\\"quoted\\\" \\u00E9\"\n",
+            "'quoted key' = 1\n",
+            "\"\" = 2\n",
+            "floats = [1.0, -0.5, 6.626e-34, inf, -inf]\n",
+            "odt = 1979-05-27T07:32:00Z\n",
+            "ldt = 1979-05-27T07:32:00.999\n",
+            "ld = 1979-05-27\n",
+            "lt = 07:32:00\n",
+            "nested = [[1, 2], [\"a\"], []]\n",
+            "inline = { a = { b = 1 }, c = [{ d = true }] }\n",
+        ));
+    }
+
+    #[test]
+    fn test_round_trip_documents() {
+        for path in [
+            "assets/array-of-tables.toml",
+            "assets/dotted-keys.toml",
+            "assets/quoted-keys.toml",
+            "assets/root-table.toml",
+            "assets/cargo_examples/self.toml",
+            "assets/cargo_examples/cargo-expand.toml",
+            "assets/cargo_examples/lalrpop.toml",
+        ] {
+            assert_round_trip(&read_to_string(path).unwrap());
+        }
+    }
+}
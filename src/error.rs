@@ -0,0 +1,368 @@
+use std::fmt::{Display, Formatter};
+
+use crate::parsers::TableKind;
+use crate::span::{LineIndex, Span};
+
+/// The error returned when a TOML document cannot be parsed, or a value cannot be converted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    inner: Box<ErrorInner>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ErrorInner {
+    kind: ErrorKind,
+    message: String,
+    /// The parts of the input the error points at, the primary one first
+    labels: Vec<Label>,
+    notes: Vec<String>,
+    expected: Vec<&'static str>,
+    context: Vec<&'static str>,
+}
+
+/// A part of the input an error points at, with a short note on what is found there
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    span: Span,
+    message: String,
+    primary: bool,
+}
+
+impl Label {
+    /// Where the error is
+    pub(crate) fn primary(span: Span, message: impl Into<String>) -> Self {
+        Label {
+            span,
+            message: message.into(),
+            primary: true,
+        }
+    }
+
+    /// Another place that explains the error, like the first of two definitions of a key
+    pub(crate) fn secondary(span: Span, message: impl Into<String>) -> Self {
+        Label {
+            span,
+            message: message.into(),
+            primary: false,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Whether the label points at the error itself, rather than at something related to it
+    pub fn is_primary(&self) -> bool {
+        self.primary
+    }
+}
+
+/// What kind of mistake the document makes. Syntax errors stop the parser, the duplicate and
+/// conflicting definitions are found by the consistency check on a document that parsed
+/// successfully, and point at both of the definitions that contradict each other. The others
+/// come from converting a [`TomlValue`](crate::TomlValue) into another type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    Syntax,
+    /// A key is given a value more than once
+    DuplicateKey { first: Location, second: Location },
+    /// A table is defined more than once, by headers or dotted keys
+    DuplicateTable { first: Location, second: Location },
+    /// A key is used as two different things, e.g. as a value and then as a table
+    ConflictingDefinitions { first: Location, second: Location },
+    /// A value has a different type than the one it is converted into
+    InvalidType {
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// An integer does not fit into the type it is converted into
+    OutOfRange { expected: &'static str, value: i64 },
+    /// A key that has to be there is not, like the name of a package, or a key a member of
+    /// a workspace inherits that the workspace root does not define
+    MissingKey { path: String },
+    /// A value has the right type but makes no sense where it is, like a dependency with
+    /// both a `git` and a `path`
+    InvalidValue { path: String },
+    /// A value could not be deserialized with serde, at the `path` it was found at in the
+    /// document. The path is empty for the document itself.
+    Deserialize { path: String },
+    /// A value could not be serialized with serde, as TOML has no way to write it
+    Serialize { path: String },
+}
+
+/// Where a key or a table was defined in the document
+#[derive(Debug, Clone, PartialEq)]
+pub enum Location {
+    /// A `[header]` or a `[[header]]`
+    Header { header: String, kind: TableKind },
+    /// A key value pair, along with the header of the table it belongs to. The header is
+    /// empty for the root table.
+    Key { table: String, key: String },
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Location::Header {
+                header,
+                kind: TableKind::Standard,
+            } => write!(f, "`[{}]`", header),
+            Location::Header {
+                header,
+                kind: TableKind::ArrayOfTables,
+            } => write!(f, "`[[{}]]`", header),
+            Location::Key { table, key } if table.is_empty() => {
+                write!(f, "`{}` in the root table", key)
+            }
+            Location::Key { table, key } => write!(f, "`{}` in `{}`", key, table),
+        }
+    }
+}
+
+impl Error {
+    /// A syntax error at `offset` into `source`, where one of the `expected` tokens would
+    /// have been accepted. The `context` labels the parts of the document the parser was in,
+    /// innermost first. An error that is about the text at `offset` rather than a missing
+    /// token says what is `invalid` about it and how long it is instead.
+    pub(crate) fn syntax(
+        source: &str,
+        offset: usize,
+        expected: Vec<&'static str>,
+        context: Vec<&'static str>,
+        invalid: Option<(&'static str, usize)>,
+    ) -> Self {
+        let found = match (invalid, expected.split_last()) {
+            (Some((message, _)), _) => message.to_string(),
+            (None, None) if offset == source.len() => "unexpected end of input".to_string(),
+            (None, None) => "unexpected input".to_string(),
+            (None, Some((last, []))) => format!("expected {}", last),
+            (None, Some((last, others))) => {
+                format!("expected {} or {}", others.join(", "), last)
+            }
+        };
+        let message = match context.first() {
+            Some(label) => format!("{} in {}", found, label),
+            None => found.clone(),
+        };
+        let length = invalid.map_or(0, |(_, length)| length);
+        let span = LineIndex::new(source).span(offset, offset + length);
+        Error {
+            inner: Box::new(ErrorInner {
+                kind: ErrorKind::Syntax,
+                message,
+                labels: vec![Label::primary(span, found)],
+                notes: vec![],
+                expected,
+                context,
+            }),
+        }
+    }
+
+    /// An error in a document that is syntactically valid, but defines its tables or keys
+    /// in a way that contradicts itself. The labels point at both of the definitions.
+    pub(crate) fn consistency(kind: ErrorKind, message: String, labels: Vec<Label>) -> Self {
+        Error {
+            inner: Box::new(ErrorInner {
+                kind,
+                message,
+                labels,
+                notes: vec![],
+                expected: vec![],
+                context: vec![],
+            }),
+        }
+    }
+
+    pub(crate) fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.inner.notes.push(note.into());
+        self
+    }
+
+    /// A value that cannot be converted into the `expected` type
+    pub(crate) fn conversion(kind: ErrorKind) -> Self {
+        let message = match &kind {
+            ErrorKind::InvalidType { expected, found } => {
+                format!("expected {}, found {}", expected, found)
+            }
+            ErrorKind::OutOfRange { expected, value } => {
+                format!("expected {}, found {} which is out of range", expected, value)
+            }
+            _ => unreachable!("not a conversion error"),
+        };
+        Error {
+            inner: Box::new(ErrorInner {
+                kind,
+                message,
+                labels: vec![],
+                notes: vec![],
+                expected: vec![],
+                context: vec![],
+            }),
+        }
+    }
+
+    /// A key that has to be defined in the table at `span`
+    pub(crate) fn missing_key(path: String, span: Span) -> Self {
+        Error {
+            inner: Box::new(ErrorInner {
+                message: format!("missing key `{}`", path),
+                labels: vec![Label::primary(span, format!("`{}` is not defined in here", path))],
+                kind: ErrorKind::MissingKey { path },
+                notes: vec![],
+                expected: vec![],
+                context: vec![],
+            }),
+        }
+    }
+
+    /// A key a member of a workspace inherits at `span`, but the workspace root does not
+    /// define at `path`
+    pub(crate) fn missing_inherited_key(path: String, span: Span) -> Self {
+        Error {
+            inner: Box::new(ErrorInner {
+                message: format!("missing key `{}` in the workspace root", path),
+                labels: vec![Label::primary(span, "inherited from the workspace here")],
+                kind: ErrorKind::MissingKey { path },
+                notes: vec![],
+                expected: vec![],
+                context: vec![],
+            }),
+        }
+    }
+
+    /// A value at `path` that cannot be used the way it is, as `message` explains
+    pub(crate) fn invalid_value(path: String, message: String, span: Span) -> Self {
+        Error {
+            inner: Box::new(ErrorInner {
+                labels: vec![Label::primary(span, message.clone())],
+                message: with_path(message, &path),
+                kind: ErrorKind::InvalidValue { path },
+                notes: vec![],
+                expected: vec![],
+                context: vec![],
+            }),
+        }
+    }
+
+    /// Points a conversion error at the value at `path` it was raised for
+    pub(crate) fn at(mut self, path: &str, span: Span) -> Self {
+        let message = std::mem::take(&mut self.inner.message);
+        self.inner.labels.push(Label::primary(span, message.clone()));
+        self.inner.message = with_path(message, path);
+        self
+    }
+
+    /// A value at `path` that serde could not deserialize, pointing at it if it is known
+    /// where in the input the value is
+    #[cfg(feature = "serde")]
+    pub(crate) fn deserialize(message: String, path: String, span: Option<Span>) -> Self {
+        let labels = span
+            .map(|span| vec![Label::primary(span, message.clone())])
+            .unwrap_or_default();
+        let message = with_path(message, &path);
+        Error::serde(ErrorKind::Deserialize { path }, message, labels)
+    }
+
+    /// A value at `path` that serde could not write as TOML
+    #[cfg(feature = "serde")]
+    pub(crate) fn serialize(message: String, path: String) -> Self {
+        let message = with_path(message, &path);
+        Error::serde(ErrorKind::Serialize { path }, message, vec![])
+    }
+
+    #[cfg(feature = "serde")]
+    fn serde(kind: ErrorKind, message: String, labels: Vec<Label>) -> Self {
+        Error {
+            inner: Box::new(ErrorInner {
+                kind,
+                message,
+                labels,
+                notes: vec![],
+                expected: vec![],
+                context: vec![],
+            }),
+        }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.inner.kind
+    }
+
+    /// A short description of what went wrong
+    pub fn message(&self) -> &str {
+        &self.inner.message
+    }
+
+    /// Where the error is in the input. For syntax errors this is where parsing stopped,
+    /// for duplicate or conflicting definitions the second one. Conversion errors have none.
+    pub fn span(&self) -> Option<Span> {
+        self.inner.labels.first().map(Label::span)
+    }
+
+    /// The byte offset into the input the error is at, see [`span`](Error::span)
+    pub fn offset(&self) -> Option<usize> {
+        self.span().map(|span| span.start())
+    }
+
+    /// The line the error is at, starting at 1
+    pub fn line(&self) -> Option<usize> {
+        self.span().map(|span| span.line())
+    }
+
+    /// The column the error is at, starting at 1 and counted in characters
+    pub fn column(&self) -> Option<usize> {
+        self.span().map(|span| span.column())
+    }
+
+    /// The parts of the input the error points at, the primary one first
+    pub fn labels(&self) -> &[Label] {
+        &self.inner.labels
+    }
+
+    /// Further explanations of the error, if there are any
+    pub fn notes(&self) -> &[String] {
+        &self.inner.notes
+    }
+
+    /// What the parser would have accepted where it stopped, like `` `=` `` or `a value`.
+    /// Empty if it cannot tell.
+    pub fn expected(&self) -> &[&'static str] {
+        &self.inner.expected
+    }
+
+    /// The parts of the document the parser was in when it stopped, innermost first, like
+    /// `array` and then `key value pair` for a mistake in an array value
+    pub fn context(&self) -> &[&'static str] {
+        &self.inner.context
+    }
+}
+
+/// Tells which value an error is about, unless it is about the whole document
+fn with_path(message: String, path: &str) -> String {
+    if path.is_empty() {
+        message
+    } else {
+        format!("{} for `{}`", message, path)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.span() {
+            Some(span) => write!(
+                f,
+                "{} at line {}, column {}",
+                self.inner.message,
+                span.line(),
+                span.column()
+            ),
+            None => f.write_str(&self.inner.message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,348 @@
+use crate::cst::{CstDocument, CstHeader, CstKeyValue};
+use crate::parsers::{trim_key, TableKind};
+
+use value::Layout;
+
+mod options;
+mod sort;
+mod value;
+
+pub use options::FormatOptions;
+
+/// Formats the empty lines and comments above a line. Comments are indented by `indent`,
+/// runs of empty lines are cut down to `blank_lines`, and the lines at the start of the
+/// document are dropped. `separate` adds an empty line above the comments if there is none.
+fn format_leading(
+    leading: &str,
+    newline: &str,
+    first: bool,
+    separate: bool,
+    indent: &str,
+    blank_lines: usize,
+) -> String {
+    let mut lines: Vec<&str> = vec![];
+    for line in leading.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            let blank = lines.iter().rev().take_while(|line| line.is_empty()).count();
+            if (first && blank == lines.len()) || blank >= blank_lines {
+                continue;
+            }
+        }
+        lines.push(line);
+    }
+    if separate && !first && lines.first().is_none_or(|line| !line.is_empty()) {
+        lines.insert(0, "");
+    }
+    lines
+        .iter()
+        .map(|line| {
+            if line.is_empty() {
+                newline.to_string()
+            } else {
+                format!("{}{}{}", indent, line, newline)
+            }
+        })
+        .collect()
+}
+
+/// Leaves one space between a value or header and the comment after it, and drops trailing
+/// whitespace
+fn format_suffix(suffix: &str) -> String {
+    match suffix.trim() {
+        "" => String::new(),
+        comment => format!(" {}", comment),
+    }
+}
+
+impl CstHeader {
+    fn format(&mut self, newline: &str, first: bool, indent: &str, options: &FormatOptions) {
+        let blank_lines = options.allowed_blank_lines;
+        self.leading = format_leading(&self.leading, newline, first, true, indent, blank_lines);
+        // The key keeps its quotes, like the ones of `[target.'cfg(unix)'.dependencies]`
+        let (open, close) = match self.kind {
+            TableKind::Standard => ("[", "]"),
+            TableKind::ArrayOfTables => ("[[", "]]"),
+        };
+        let raw = self.raw.trim();
+        let key = raw.strip_prefix(open).and_then(|raw| raw.strip_suffix(close));
+        self.raw = format!("{}{}{}{}", indent, open, trim_key(key.unwrap_or(raw)), close);
+        self.suffix = format_suffix(&self.suffix);
+        self.newline = newline.to_string();
+    }
+}
+
+impl CstKeyValue {
+    fn format(&mut self, newline: &str, first: bool, indent: &str, options: &FormatOptions) {
+        let blank_lines = options.allowed_blank_lines;
+        self.leading = format_leading(&self.leading, newline, first, false, indent, blank_lines);
+        self.indent = indent.to_string();
+        self.key_raw = trim_key(&self.key_raw);
+        self.separator = " = ".to_string();
+        self.suffix = format_suffix(&self.suffix);
+        self.newline = newline.to_string();
+    }
+
+    /// The width of the line up to the value
+    fn value_column(&self) -> usize {
+        self.indent.chars().count()
+            + self.key_raw.chars().count()
+            + self.separator.chars().count()
+    }
+}
+
+/// Splits the key value pairs of a section into runs that no empty line separates
+fn runs(entries: &mut [CstKeyValue]) -> Vec<&mut [CstKeyValue]> {
+    let mut runs = vec![];
+    let mut rest = entries;
+    while !rest.is_empty() {
+        let len = 1 + rest[1..]
+            .iter()
+            .take_while(|entry| !entry.leading.lines().any(|line| line.trim().is_empty()))
+            .count();
+        let (run, next) = rest.split_at_mut(len);
+        runs.push(run);
+        rest = next;
+    }
+    runs
+}
+
+/// Lines up the `=` of a run of key value pairs
+fn align_entries(run: &mut [CstKeyValue]) {
+    let width = run.iter().map(|entry| entry.key_raw.chars().count()).max().unwrap_or(0);
+    for entry in run {
+        let padding = width - entry.key_raw.chars().count();
+        entry.separator = format!("{} = ", " ".repeat(padding));
+    }
+}
+
+/// Lines up the comments after the values of a run of key value pairs. Values that span
+/// several lines keep their comment right after them.
+fn align_comments(run: &mut [CstKeyValue]) {
+    let aligned = |entry: &CstKeyValue| {
+        !entry.suffix.is_empty() && !entry.value_raw.to_string().contains('\n')
+    };
+    let width = run
+        .iter()
+        .filter(|entry| aligned(entry))
+        .map(|entry| entry.value_column() + entry.value_raw.to_string().chars().count())
+        .max()
+        .unwrap_or(0);
+    for entry in run.iter_mut().filter(|entry| aligned(entry)) {
+        let padding =
+            width - entry.value_column() - entry.value_raw.to_string().chars().count();
+        entry.suffix = format!("{}{}", " ".repeat(padding), entry.suffix);
+    }
+}
+
+impl CstDocument {
+    /// Formats the document the way most TOML files are written. Keys and headers lose
+    /// their indentation and the whitespace around their dots, but keep their quotes, with
+    /// ` = ` between a key and its value and an empty line above each header. Runs of empty lines
+    /// are cut down to one, trailing whitespace is dropped and the document ends with a
+    /// single line ending.
+    ///
+    /// Comments are kept where they are, and values are kept the way they were written.
+    pub fn format(&mut self) {
+        self.layout(&FormatOptions::default(), false);
+    }
+
+    /// Formats the document like [`format`](CstDocument::format), in the style `options`
+    /// describe. Arrays and inline tables are laid out again: on one line if they fit in
+    /// the column width and have no comments, and with one value per line otherwise.
+    /// Comments are always kept, and the other values are kept the way they were written.
+    pub fn format_with(&mut self, options: &FormatOptions) {
+        self.layout(options, true);
+    }
+
+    fn layout(&mut self, options: &FormatOptions, values: bool) {
+        let newline = self.line_ending();
+        let headers: Vec<Vec<String>> = self
+            .sections
+            .iter()
+            .filter_map(|section| Some(section.header.as_ref()?.key.segments().to_vec()))
+            .collect();
+        let mut first = true;
+        for section in &mut self.sections {
+            // The number of tables with a header of their own that this one is in
+            let depth = match &section.header {
+                Some(header) if options.indent_tables => {
+                    let key = header.key.segments();
+                    (1..key.len())
+                        .filter(|&len| headers.iter().any(|header| header[..] == key[..len]))
+                        .count()
+                }
+                _ => 0,
+            };
+            let indent = options.indent_string.repeat(depth);
+            if let Some(header) = &mut section.header {
+                header.format(&newline, first, &indent, options);
+                first = false;
+            }
+            for entry in &mut section.entries {
+                entry.format(&newline, first, &indent, options);
+                first = false;
+            }
+            for run in runs(&mut section.entries) {
+                if options.align_entries {
+                    align_entries(run);
+                }
+                if values {
+                    let layout = Layout {
+                        options,
+                        newline: &newline,
+                    };
+                    for entry in run.iter_mut() {
+                        let start = entry.value_column();
+                        entry.value_raw = layout.write(&entry.value_raw, start, depth);
+                    }
+                }
+                if options.align_comments {
+                    align_comments(run);
+                }
+            }
+        }
+        let blank_lines = options.allowed_blank_lines;
+        let trailing = format_leading(&self.trailing, &newline, first, false, "", blank_lines);
+        self.trailing = trailing.trim_end().to_string();
+        if !self.trailing.is_empty() {
+            self.trailing.push_str(&newline);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_format {
+    use std::fs::read_to_string;
+
+    use crate::parse_cst;
+
+    use super::*;
+
+    fn format(input: &str) -> String {
+        let mut cst = parse_cst(input).unwrap();
+        cst.format();
+        cst.to_string()
+    }
+
+    #[test]
+    fn test_format() {
+        let input = "\n\n# top\n  name   =  \"x\"   # the name  \n[ dependencies . nom ]\nversion='7'\n\n\n\n  # below\n[[bin]]   \n\t\"path\"=\"src/main.rs\"\n\n# end\n\n";
+        assert_eq!(
+            format(input),
+            "# top\nname = \"x\" # the name\n\n[dependencies.nom]\nversion = '7'\n\n# below\n[[bin]]\n\"path\" = \"src/main.rs\"\n\n# end\n"
+        );
+    }
+
+    #[test]
+    fn test_format_keeps_quoted_keys() {
+        let input = "[ target . 'cfg(unix)' .dependencies ]\nnix = '0.26'\n\n\
+            [[ bin ]]\n'name'.\"x y\" = { 'a' . b = 1 }\n";
+        assert_eq!(
+            format(input),
+            "[target.'cfg(unix)'.dependencies]\nnix = '0.26'\n\n\
+            [[bin]]\n'name'.\"x y\" = { 'a' . b = 1 }\n"
+        );
+    }
+
+    #[test]
+    fn test_format_keeps_values_and_line_endings() {
+        let input = "list = [\n  1, # one\n  2,\n]\r\n[t]\r\nx = 0xff";
+        assert_eq!(format(input), "list = [\n  1, # one\n  2,\n]\r\n\r\n[t]\r\nx = 0xff\r\n");
+    }
+
+    #[test]
+    fn test_format_is_stable() {
+        for entry in std::fs::read_dir("assets/cargo_examples").unwrap() {
+            let input = read_to_string(entry.unwrap().path()).unwrap();
+            let formatted = format(&input);
+            assert_eq!(format(&formatted), formatted);
+            assert_eq!(
+                crate::parse(&formatted).unwrap(),
+                crate::parse(&input).unwrap()
+            );
+        }
+    }
+
+    fn format_with(input: &str, options: &FormatOptions) -> String {
+        let mut cst = parse_cst(input).unwrap();
+        cst.format_with(options);
+        let output = cst.to_string();
+        assert_eq!(crate::parse(&output).unwrap(), crate::parse(input).unwrap());
+        output
+    }
+
+    #[test]
+    fn test_format_with_defaults() {
+        let input = "a = [ 1,2 ,\n 3 ]\nb = {x=1,  y = [ 'a' ] }\nc = [\n  1, # one\n  2\n]\n";
+        assert_eq!(
+            format_with(input, &FormatOptions::default()),
+            "a = [1, 2, 3]\nb = { x = 1, y = ['a'] }\nc = [\n    1, # one\n    2,\n]\n"
+        );
+    }
+
+    #[test]
+    fn test_format_with_wrapping() {
+        let options = FormatOptions {
+            column_width: 24,
+            trailing_comma: false,
+            indent_string: "  ".to_string(),
+            ..FormatOptions::default()
+        };
+        let input = "[t]\nlist = [\"alpha\", \"beta\", [\"gamma\", \"delta\"]]\nshort = [1, 2]\n\
+            dep = { version = \"1\", features = [\"derive\", \"rc\"] }\n";
+        assert_eq!(
+            format_with(input, &options),
+            "[t]\nlist = [\n  \"alpha\",\n  \"beta\",\n  [\"gamma\", \"delta\"]\n]\n\
+            short = [1, 2]\n\
+            dep = { version = \"1\", features = [\n  \"derive\",\n  \"rc\"\n] }\n"
+        );
+    }
+
+    #[test]
+    fn test_format_with_alignment() {
+        let options = FormatOptions {
+            align_entries: true,
+            align_comments: true,
+            ..FormatOptions::default()
+        };
+        let input =
+            "name = \"x\" # name\nversion = \"0.1.0\" # version\n\nedition = \"2021\" # alone\n";
+        assert_eq!(
+            format_with(input, &options),
+            "name    = \"x\"     # name\nversion = \"0.1.0\" # version\n\n\
+            edition = \"2021\" # alone\n"
+        );
+    }
+
+    #[test]
+    fn test_format_with_indented_tables() {
+        let options = FormatOptions {
+            indent_tables: true,
+            allowed_blank_lines: 0,
+            ..FormatOptions::default()
+        };
+        let input = "[a]\nx = 1\n\n\n[a.b]\n# comment\ny = [1]\n[a.b.c]\nz = 2\n[d.e]\nw = 3\n";
+        assert_eq!(
+            format_with(input, &options),
+            "[a]\nx = 1\n\n    [a.b]\n    # comment\n    y = [1]\n\n        [a.b.c]\n\
+            \x20       z = 2\n\n[d.e]\nw = 3\n"
+        );
+    }
+
+    #[test]
+    fn test_format_with_is_stable() {
+        let options = FormatOptions {
+            indent_tables: true,
+            align_entries: true,
+            align_comments: true,
+            column_width: 40,
+            ..FormatOptions::default()
+        };
+        for entry in std::fs::read_dir("assets/cargo_examples").unwrap() {
+            let input = read_to_string(entry.unwrap().path()).unwrap();
+            let formatted = format_with(&input, &options);
+            assert_eq!(format_with(&formatted, &options), formatted);
+        }
+    }
+}
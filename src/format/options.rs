@@ -0,0 +1,149 @@
+use std::str::FromStr;
+
+use crate::document::Document;
+use crate::error::Error;
+use crate::parsers::PathSegment;
+
+/// How [`format_with`](crate::CstDocument::format_with) lays out a document.
+///
+/// The options can be read from a `toml-fmt.toml` file, with a key in kebab case for each
+/// option that is not left at its default:
+///
+/// ```toml
+/// indent-tables = true
+/// align-entries = true
+/// column-width = 80
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Indents a table one level deeper than the table it is in, if that has a header too,
+    /// like `[dependencies.nom]` below `[dependencies]`
+    pub indent_tables: bool,
+    /// One level of indentation, four spaces by default
+    pub indent_string: String,
+    /// Lines up the `=` of key value pairs that are not separated by an empty line
+    pub align_entries: bool,
+    /// Lines up the comments after the values of key value pairs that are not separated by
+    /// an empty line
+    pub align_comments: bool,
+    /// Arrays that would make a line wider than this are written one value per line.
+    /// Inline tables stay on one line, as TOML asks, but the arrays in them are wrapped.
+    pub column_width: usize,
+    /// Whether the last value of an array written one value per line is followed by a comma
+    pub trailing_comma: bool,
+    /// The most empty lines kept in a row
+    pub allowed_blank_lines: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            indent_tables: false,
+            indent_string: "    ".to_string(),
+            align_entries: false,
+            align_comments: false,
+            column_width: 100,
+            trailing_comma: true,
+            allowed_blank_lines: 1,
+        }
+    }
+}
+
+impl FormatOptions {
+    /// Reads the options from a document like a `toml-fmt.toml` file. Options that are not
+    /// in it keep their default, and keys that are not options are errors.
+    pub fn from_document(document: &Document) -> Result<Self, Error> {
+        let mut options = FormatOptions::default();
+        for (key, value) in document.root().iter() {
+            let spans = document.spans_at(&[PathSegment::Key(key.clone())]);
+            let span = spans.map(|spans| spans.value).unwrap_or_default();
+            let invalid = |message: &str| {
+                Error::invalid_value(key.clone(), message.to_string(), span)
+            };
+            let boolean = || bool::try_from(value).map_err(|e| e.at(key, span));
+            let count = || usize::try_from(value).map_err(|e| e.at(key, span));
+            match key.as_str() {
+                "indent-tables" => options.indent_tables = boolean()?,
+                "indent-string" => {
+                    let indent = String::try_from(value).map_err(|e| e.at(key, span))?;
+                    if indent.is_empty() || !indent.chars().all(|c| c == ' ' || c == '\t') {
+                        return Err(invalid("the indentation can only be spaces and tabs"));
+                    }
+                    options.indent_string = indent;
+                }
+                "align-entries" => options.align_entries = boolean()?,
+                "align-comments" => options.align_comments = boolean()?,
+                "column-width" => {
+                    options.column_width = count()?;
+                    if options.column_width == 0 {
+                        return Err(invalid("the width has to be at least 1"));
+                    }
+                }
+                "trailing-comma" => options.trailing_comma = boolean()?,
+                "allowed-blank-lines" => options.allowed_blank_lines = count()?,
+                _ => {
+                    let span = spans.and_then(|spans| spans.key).unwrap_or(span);
+                    return Err(Error::invalid_value(
+                        key.clone(),
+                        "unknown formatting option".to_string(),
+                        span,
+                    ));
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+impl FromStr for FormatOptions {
+    type Err = Error;
+
+    /// Parses the options from the contents of a `toml-fmt.toml` file
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        FormatOptions::from_document(&crate::parse(input)?)
+    }
+}
+
+#[cfg(test)]
+mod tests_options {
+    use crate::ErrorKind;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_options() {
+        let options: FormatOptions =
+            "indent-tables = true\nindent-string = \"\\t\"\ncolumn-width = 80\n".parse().unwrap();
+        assert_eq!(
+            options,
+            FormatOptions {
+                indent_tables: true,
+                indent_string: "\t".to_string(),
+                column_width: 80,
+                ..FormatOptions::default()
+            }
+        );
+        assert_eq!("".parse::<FormatOptions>().unwrap(), FormatOptions::default());
+    }
+
+    #[test]
+    fn test_invalid_options() {
+        let error = "column-width = 0".parse::<FormatOptions>().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "the width has to be at least 1 for `column-width` at line 1, column 16"
+        );
+        let error = "align = true".parse::<FormatOptions>().unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::InvalidValue { path: "align".to_string() });
+        assert_eq!(error.column(), Some(1));
+        let error = "trailing-comma = 1".parse::<FormatOptions>().unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::InvalidType {
+                expected: "bool",
+                found: "integer"
+            }
+        );
+        assert!("allowed-blank-lines = -1".parse::<FormatOptions>().is_err());
+    }
+}
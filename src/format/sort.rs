@@ -0,0 +1,90 @@
+use crate::cst::{blank_lines, CstDocument, CstKeyValue};
+use crate::parsers::{parse_path, PathSegment};
+
+use super::runs;
+
+/// Sorts a run of key value pairs by their keys. The comments above each pair move with it,
+/// and the empty lines above the run stay where they are. Returns whether anything moved.
+fn sort_run(run: &mut [CstKeyValue], newline: &str) -> bool {
+    if run.is_sorted_by(|a, b| a.key.segments() <= b.key.segments()) {
+        return false;
+    }
+    // Only the last line of the document can lack a line ending, and it may not stay last
+    for entry in run.iter_mut().filter(|entry| entry.newline.is_empty()) {
+        entry.newline = newline.to_string();
+    }
+    let blank = blank_lines(&run[0].leading).len();
+    let blank: String = run[0].leading.drain(..blank).collect();
+    run.sort_by(|a, b| a.key.segments().cmp(b.key.segments()));
+    run[0].leading.insert_str(0, &blank);
+    true
+}
+
+impl CstDocument {
+    /// Sorts the key value pairs of the tables with a header at `path`, like
+    /// `dependencies` or `bin[0]`, by their keys. Comments above a pair move with it. Pairs
+    /// that an empty line separates are sorted on their own, so groups of keys stay apart.
+    ///
+    /// Returns whether anything moved.
+    pub fn sort_keys(&mut self, path: &str) -> bool {
+        match parse_path(path) {
+            Some(path) => self.sort_keys_at(&path),
+            None => false,
+        }
+    }
+
+    pub(crate) fn sort_keys_at(&mut self, path: &[PathSegment]) -> bool {
+        let newline = self.line_ending();
+        let paths = self.section_paths();
+        let mut moved = false;
+        for (section, section_path) in self.sections.iter_mut().zip(paths) {
+            if section.header.is_some() && section_path == path {
+                for run in runs(&mut section.entries) {
+                    moved |= sort_run(run, &newline);
+                }
+            }
+        }
+        moved
+    }
+}
+
+#[cfg(test)]
+mod tests_sort {
+    use crate::parse_cst;
+
+    fn sort(input: &str, path: &str) -> (String, bool) {
+        let mut cst = parse_cst(input).unwrap();
+        let moved = cst.sort_keys(path);
+        let output = cst.to_string();
+        crate::parse(&output).unwrap();
+        (output, moved)
+    }
+
+    #[test]
+    fn test_sort_keys() {
+        let input =
+            "[t]\n# about c\nc = 3 # three\nb.y = 2\na = 1\nb.x = 1\n\n# group\nz = 0\ny = 0";
+        assert_eq!(
+            sort(input, "t"),
+            (
+                "[t]\na = 1\nb.x = 1\nb.y = 2\n# about c\nc = 3 # three\n\ny = 0\n# group\nz = 0\n"
+                    .to_string(),
+                true
+            )
+        );
+        let sorted = sort(input, "t").0;
+        assert_eq!(sort(&sorted, "t"), (sorted, false));
+    }
+
+    #[test]
+    fn test_sort_keys_of_array_of_tables() {
+        let input =
+            "[[bin]]\nname = \"a\"\npath = \"a.rs\"\n\n[[bin]]\npath = \"b.rs\"\nname = \"b\"\n";
+        assert_eq!(sort(input, "bin[0]"), (input.to_string(), false));
+        assert_eq!(
+            sort(input, "bin[1]").0,
+            input.replace("path = \"b.rs\"\nname = \"b\"", "name = \"b\"\npath = \"b.rs\"")
+        );
+        assert_eq!(sort(input, "missing"), (input.to_string(), false));
+    }
+}
@@ -0,0 +1,267 @@
+use crate::cst::{CstArray, CstInlineKeyValue, CstInlineTable, CstItem, CstValue};
+use crate::format::FormatOptions;
+use crate::parsers::trim_key;
+
+/// Splits the whitespace and comments after a value, up to the next value, into the comment
+/// on the line the value ends on and the comments on the lines below it. The comma between
+/// the values is not part of `trivia`, which is why it comes in pieces.
+fn comments<'a>(trivia: &[&'a str]) -> (Option<&'a str>, Vec<&'a str>) {
+    let mut same_line = None;
+    let mut below = vec![];
+    let mut first_line = true;
+    for part in trivia {
+        for (i, line) in part.split('\n').enumerate() {
+            first_line &= i == 0;
+            match line.trim() {
+                "" => {}
+                comment if first_line => same_line = Some(comment),
+                comment => below.push(comment),
+            }
+        }
+    }
+    (same_line, below)
+}
+
+/// A value in an array, with the comments around it
+#[derive(Debug, PartialEq)]
+struct Item<'a> {
+    /// Comments on the lines above the value
+    comments: Vec<&'a str>,
+    value: &'a CstValue,
+    /// A comment after the value, on the same line
+    comment: Option<&'a str>,
+}
+
+/// The values of an array with their comments, and the comments after the last value
+fn items(array: &CstArray) -> (Vec<Item<'_>>, Vec<&str>) {
+    let Some(first) = array.items.first() else {
+        let (same_line, below) = comments(&[&array.trailing]);
+        return (vec![], same_line.into_iter().chain(below).collect());
+    };
+    // The comments after the `[` are above the first value, wherever they are
+    let (same_line, mut above) = comments(&[&first.leading]);
+    above.splice(0..0, same_line);
+    let mut items = vec![];
+    for (i, item) in array.items.iter().enumerate() {
+        let next = match array.items.get(i + 1) {
+            Some(next) => &next.leading,
+            None => &array.trailing,
+        };
+        let (comment, below) = comments(&[&item.suffix, next]);
+        items.push(Item {
+            comments: std::mem::replace(&mut above, below),
+            value: &item.value,
+            comment,
+        });
+    }
+    (items, above)
+}
+
+/// The value written on one line, unless it has comments that need more
+fn flat(value: &CstValue) -> Option<CstValue> {
+    match value {
+        CstValue::Scalar(_) => Some(value.clone()),
+        CstValue::Array(array) => {
+            let (items, comments) = items(array);
+            if !comments.is_empty() {
+                return None;
+            }
+            let items = items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| match (item.comments.is_empty(), item.comment) {
+                    (true, None) => Some(CstItem {
+                        leading: if i == 0 { String::new() } else { " ".to_string() },
+                        value: flat(item.value)?,
+                        suffix: String::new(),
+                    }),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()?;
+            Some(CstValue::Array(CstArray {
+                items,
+                comma: false,
+                trailing: String::new(),
+            }))
+        }
+        CstValue::InlineTable(table) => {
+            let len = table.entries.len();
+            let entries = table
+                .entries
+                .iter()
+                .enumerate()
+                .map(|(i, entry)| {
+                    Some(CstInlineKeyValue {
+                        leading: " ".to_string(),
+                        key_raw: trim_key(&entry.key_raw),
+                        separator: " = ".to_string(),
+                        value: flat(&entry.value)?,
+                        suffix: if i + 1 == len { " ".to_string() } else { String::new() },
+                        ..entry.clone()
+                    })
+                })
+                .collect::<Option<Vec<_>>>()?;
+            Some(CstValue::InlineTable(CstInlineTable {
+                entries,
+                trailing: String::new(),
+            }))
+        }
+    }
+}
+
+/// The column the next character after `text` will be in, if `text` starts at `start`
+fn column(text: &str, start: usize) -> usize {
+    match text.rfind('\n') {
+        Some(i) => text[i + 1..].chars().count(),
+        None => start + text.chars().count(),
+    }
+}
+
+/// Lays out values the way [`FormatOptions`] asks for
+pub(super) struct Layout<'o> {
+    pub(super) options: &'o FormatOptions,
+    pub(super) newline: &'o str,
+}
+
+impl Layout<'_> {
+    /// Lays out `value`, which starts in column `start` of a line indented `depth` levels
+    /// deep
+    pub(super) fn write(&self, value: &CstValue, start: usize, depth: usize) -> CstValue {
+        if let Some(flat) = flat(value) {
+            let fits = start + flat.to_string().chars().count() <= self.options.column_width;
+            let unbreakable = match value {
+                CstValue::Scalar(_) => true,
+                CstValue::Array(array) => array.items.is_empty(),
+                CstValue::InlineTable(_) => false,
+            };
+            if fits || unbreakable {
+                return flat;
+            }
+        }
+        match value {
+            CstValue::Scalar(_) => value.clone(),
+            CstValue::Array(array) => {
+                let indent = self.options.indent_string.repeat(depth + 1);
+                let (items, comments) = items(array);
+                // The comment after a value comes after its comma, so it starts what follows
+                let mut leading = String::new();
+                let mut laid_out = vec![];
+                for item in items {
+                    for comment in &item.comments {
+                        self.line(&mut leading, &indent, comment);
+                    }
+                    self.line(&mut leading, &indent, "");
+                    laid_out.push(CstItem {
+                        leading: std::mem::take(&mut leading),
+                        value: self.write(item.value, indent.chars().count(), depth + 1),
+                        suffix: String::new(),
+                    });
+                    if let Some(comment) = item.comment {
+                        leading.push(' ');
+                        leading.push_str(comment);
+                    }
+                }
+                for comment in comments {
+                    self.line(&mut leading, &indent, comment);
+                }
+                self.line(&mut leading, &self.options.indent_string.repeat(depth), "");
+                CstValue::Array(CstArray {
+                    items: laid_out,
+                    comma: self.options.trailing_comma,
+                    trailing: leading,
+                })
+            }
+            CstValue::InlineTable(table) => {
+                let mut text = "{".to_string();
+                let len = table.entries.len();
+                let mut entries = vec![];
+                for (i, entry) in table.entries.iter().enumerate() {
+                    if i > 0 {
+                        text.push(',');
+                    }
+                    let key_raw = trim_key(&entry.key_raw);
+                    text.push_str(&format!(" {} = ", key_raw));
+                    let value = self.write(&entry.value, column(&text, start), depth);
+                    text.push_str(&value.to_string());
+                    entries.push(CstInlineKeyValue {
+                        leading: " ".to_string(),
+                        key_raw,
+                        separator: " = ".to_string(),
+                        value,
+                        suffix: if i + 1 == len { " ".to_string() } else { String::new() },
+                        ..entry.clone()
+                    });
+                }
+                CstValue::InlineTable(CstInlineTable {
+                    entries,
+                    trailing: String::new(),
+                })
+            }
+        }
+    }
+
+    /// Starts a new line with `text` on it
+    fn line(&self, out: &mut String, indent: &str, text: &str) {
+        out.push_str(self.newline);
+        out.push_str(indent);
+        out.push_str(text);
+    }
+}
+
+#[cfg(test)]
+mod tests_value {
+    use crate::parse_cst;
+
+    use super::*;
+
+    fn value(raw: &str) -> CstValue {
+        let cst = parse_cst(&format!("v = {}", raw)).unwrap();
+        cst.sections[0].entries[0].value_raw.clone()
+    }
+
+    #[test]
+    fn test_items() {
+        let array = value("[ # first\n  'a,b' ,  # a\n  # b\n  [1,2], { \"k=\" = 1 }, # last\n]");
+        let CstValue::Array(array) = &array else {
+            panic!("{:?} is not an array", array);
+        };
+        let (items, comments) = items(array);
+        let items: Vec<(Vec<&str>, String, Option<&str>)> = items
+            .iter()
+            .map(|item| (item.comments.clone(), item.value.to_string(), item.comment))
+            .collect();
+        assert_eq!(
+            items,
+            vec![
+                (vec!["# first"], "'a,b'".to_string(), Some("# a")),
+                (vec!["# b"], "[1,2]".to_string(), None),
+                (vec![], "{ \"k=\" = 1 }".to_string(), Some("# last")),
+            ]
+        );
+        assert!(comments.is_empty());
+        assert_eq!(flat(&CstValue::Array(array.clone())), None);
+        assert_eq!(flat(&value("[ [1,2] ,{a=1} ]")).unwrap().to_string(), "[[1, 2], { a = 1 }]");
+    }
+
+    #[test]
+    fn test_write() {
+        let options = FormatOptions {
+            column_width: 12,
+            ..FormatOptions::default()
+        };
+        let layout = Layout {
+            options: &options,
+            newline: "\r\n",
+        };
+        let write = |raw: &str, start: usize| layout.write(&value(raw), start, 1).to_string();
+        assert_eq!(write("[ 1,\n2 ]", 4), "[1, 2]");
+        assert_eq!(write("[ 1,\n2 ]", 8), "[\r\n        1,\r\n        2,\r\n    ]");
+        assert_eq!(write("[]", 20), "[]");
+        assert_eq!(write("[ # only\n]", 0), "[\r\n        # only\r\n    ]");
+        assert_eq!(write("{a=[1,2]}", 4), "{ a = [\r\n        1,\r\n        2,\r\n    ] }");
+        assert_eq!(
+            write("[1, # one\n2 # two\n]", 0),
+            "[\r\n        1, # one\r\n        2, # two\r\n    ]"
+        );
+    }
+}
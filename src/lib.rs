@@ -1 +1,280 @@
+use nom::combinator::all_consuming;
+
+pub use cst::{CstDocument, CstHeader, CstKeyValue, CstSection};
+#[cfg(feature = "serde")]
+pub use de::{from_document, from_str};
+#[cfg(feature = "serde")]
+pub use ser::to_string;
+pub use diagnostic::Diagnostic;
+pub use document::Document;
+pub use error::{Error, ErrorKind, Label, Location};
+pub use format::FormatOptions;
+pub use lint::{Finding, Lint, LintConfig, LintContext, Linter, Rule, Severity};
+pub use manifest::{
+    CargoManifest, Dependencies, Dependency, DependencyKind, DependencyStyle, DependencyTable,
+    Feature, FeatureClosure, FeatureGraph, FeatureIssue, FeatureRef, GitReference, Inheritable,
+    NewDependency, Package, PlatformTarget, Profile, Source, Spanned, Strings, Target, Workspace,
+    WorkspacePackage,
+};
+pub use parsers::{Key, KeyValue, Section, TableKind};
+pub use span::{Span, Spans};
+pub use value::{Table, TomlValue};
+
+use crate::parsers::{cargo_root, cst_document, recovering_root, SyntaxError};
+
+mod consistency;
+mod cst;
+#[cfg(feature = "serde")]
+mod de;
+mod diagnostic;
+mod document;
+mod emit;
+mod error;
+mod format;
+mod lint;
+mod manifest;
 mod parsers;
+#[cfg(feature = "serde")]
+mod ser;
+mod span;
+mod value;
+
+/// Turns the error of a parser that had to consume all of `input` into a syntax error
+fn syntax_error(input: &str, error: nom::Err<SyntaxError>) -> Error {
+    match error {
+        nom::Err::Error(e) | nom::Err::Failure(e) => {
+            let offset = input.len() - e.input.len();
+            Error::syntax(input, offset, e.expected, e.context, e.invalid)
+        }
+        nom::Err::Incomplete(_) => Error::syntax(input, input.len(), vec![], vec![], None),
+    }
+}
+
+/// Parses a whole TOML document.
+///
+/// Unlike the parsers it is built from, `parse` has to consume the entire input. Anything
+/// left over after the last section is reported as an error instead of being dropped.
+pub fn parse(input: &str) -> Result<Document, Error> {
+    match all_consuming(cargo_root::<SyntaxError>)(input) {
+        Ok((_, (root, sections))) => Document::new(input, root, sections),
+        Err(e) => Err(syntax_error(input, e)),
+    }
+}
+
+/// Parses a whole TOML document like [`parse`], but carries on after errors to report all of
+/// them at once, which is what linters and editors need.
+///
+/// A line with a syntax error is skipped, and so is a table whose header has one, up to the
+/// next header. Key value pairs and tables that contradict earlier ones are left out. The
+/// document holds everything else. The errors are sorted by where they are in `input`, and
+/// the document is complete only if there are none.
+pub fn parse_recovering(input: &str) -> (Document, Vec<Error>) {
+    let (root, sections, syntax_errors) = recovering_root(input);
+    let (document, mut errors) = Document::recovering(input, root, sections);
+    errors.extend(
+        syntax_errors
+            .into_iter()
+            .map(|e| syntax_error(input, nom::Err::Error(e))),
+    );
+    errors.sort_by_key(|error| error.offset());
+    (document, errors)
+}
+
+/// Parses a `Cargo.toml` file into a [`CargoManifest`]. Besides syntax errors and
+/// inconsistent tables, fails if a key cargo knows has a value of the wrong type.
+pub fn parse_manifest(input: &str) -> Result<CargoManifest, Error> {
+    CargoManifest::from_document(&parse(input)?)
+}
+
+/// Parses a whole TOML document into a tree that keeps its comments and formatting, for
+/// tools that edit a document and write it back out. The document is checked for
+/// consistency like [`parse`] does.
+pub fn parse_cst(input: &str) -> Result<CstDocument, Error> {
+    match all_consuming(cst_document::<SyntaxError>)(input) {
+        Ok((_, cst)) => {
+            cst.to_document()?;
+            Ok(cst)
+        }
+        Err(e) => Err(syntax_error(input, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_cargo_self() {
+        let input = read_to_string("assets/cargo_examples/self.toml").unwrap();
+        let doc = parse(&input).unwrap();
+        let headers: Vec<String> = doc.sections().iter().map(|t| t.header().to_string()).collect();
+        assert_eq!(headers, vec!["package", "dependencies"]);
+        assert_eq!(
+            doc.sections()[1].key_values()[0],
+            KeyValue(Key::from(["nom"]), TomlValue::Str("7".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_cargo_examples() {
+        for entry in std::fs::read_dir("assets/cargo_examples").unwrap() {
+            let path = entry.unwrap().path();
+            let input = read_to_string(&path).unwrap();
+            if let Err(e) = parse(&input) {
+                panic!("{}: {}", path.display(), e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_recovering_cargo_examples() {
+        for entry in std::fs::read_dir("assets/cargo_examples").unwrap() {
+            let input = read_to_string(entry.unwrap().path()).unwrap();
+            let (doc, errors) = parse_recovering(&input);
+            assert!(errors.is_empty());
+            assert_eq!(doc, parse(&input).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_parse_recovering() {
+        let input = "\
+name = \"x\"
+version = 
+edition = \"2021\"
+
+[dependencies
+nom = \"7\"
+
+[dev-dependencies]
+serde = \"1\"
+serde = \"2\"
+chrono = { version = \"0.4\"
+rand = \"0.8\"
+";
+        let (doc, errors) = parse_recovering(input);
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "expected a value in key value pair at line 2, column 11",
+                "expected `]` in table header at line 5, column 14",
+                "`dev-dependencies.serde` is defined more than once (first defined on line 9) \
+                at line 10, column 1",
+                "expected `,` or `}` in inline table at line 12, column 1",
+            ]
+        );
+        assert_eq!(doc.get("name"), Some(&TomlValue::Str("x".to_string())));
+        assert_eq!(doc.get("version"), None);
+        assert_eq!(doc.get("edition"), Some(&TomlValue::Str("2021".to_string())));
+        assert_eq!(doc.get("dependencies"), None);
+        assert_eq!(doc.get("nom"), None);
+        assert_eq!(doc.get("dev-dependencies.serde"), Some(&TomlValue::Str("1".to_string())));
+        assert_eq!(doc.get("dev-dependencies.chrono"), None);
+        assert_eq!(doc.get("dev-dependencies.rand"), Some(&TomlValue::Str("0.8".to_string())));
+    }
+
+    #[test]
+    fn test_parse_recovering_without_errors() {
+        let input = "a = 1\n[b]\nc = 2\n";
+        let (doc, errors) = parse_recovering(input);
+        assert!(errors.is_empty());
+        assert_eq!(doc, parse(input).unwrap());
+    }
+
+    #[test]
+    fn test_parse_root_table() {
+        let input = read_to_string("assets/root-table.toml").unwrap();
+        let doc = parse(&input).unwrap();
+        assert!(doc.root_section().header().is_empty());
+        assert_eq!(
+            doc.root_section().key_values(),
+            &[
+                KeyValue(Key::from(["title"]), TomlValue::Str("root".to_string())),
+                KeyValue(Key::from(["key1"]), TomlValue::Integer(1)),
+            ]
+        );
+        assert_eq!(doc.sections().len(), 1);
+        assert_eq!(doc.sections()[0].header(), &Key::from(["table-1"]));
+    }
+
+    #[test]
+    fn test_parse_root_table_only() {
+        let doc = parse("key1 = false\nkey2 = 123\n").unwrap();
+        assert_eq!(doc.root_section().key_values().len(), 2);
+        assert!(doc.sections().is_empty());
+    }
+
+    #[test]
+    fn test_parse_integers_out_of_range() {
+        let error = parse("a = 9223372036854775808\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "integer out of range in key value pair at line 1, column 5"
+        );
+        assert_eq!(error.span().unwrap().range(), 4..23);
+        let error = parse("a = 0xFFFFFFFFFFFFFFFFF\n").unwrap_err();
+        assert_eq!(error.span().unwrap().range(), 4..23);
+        assert_eq!(error.message(), "integer out of range in key value pair");
+        let doc = parse("a = -9223372036854775808\n").unwrap();
+        assert_eq!(doc.root()["a"], TomlValue::Integer(i64::MIN));
+    }
+
+    #[test]
+    fn test_parse_rejects_trailing_input() {
+        let input = "[table-1]\nkey = 1\n\n[table-2]\nkey = oops\n";
+        let error = parse(input).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::Syntax);
+        assert_eq!(error.offset(), Some(input.find("oops").unwrap()));
+        assert_eq!((error.line(), error.column()), (Some(5), Some(7)));
+        assert_eq!(
+            error.to_string(),
+            "expected a value in key value pair at line 5, column 7"
+        );
+
+        let error = parse("a = 1\n]\n").unwrap_err();
+        assert_eq!(error.message(), "expected a key or a table header");
+        assert_eq!((error.line(), error.column()), (Some(2), Some(1)));
+    }
+
+    #[test]
+    fn test_parse_unterminated_string() {
+        let error = parse("[table]\nkey = \"abc").unwrap_err();
+        assert_eq!(error.message(), "unexpected end of input");
+        assert_eq!(error.offset(), Some(18));
+    }
+
+    #[test]
+    fn test_parse_error_messages() {
+        for (input, message, line, column) in [
+            ("key 1\n", "expected `=` in key value pair", 1, 5),
+            ("key = 1 2\n", "expected end of line in key value pair", 1, 9),
+            ("a = [1, 2\n", "expected `,` or `]` in array", 2, 1),
+            ("a = [1, oops]\n", "expected a value or `]` in array", 1, 9),
+            ("a = { b = 1 c = 2 }\n", "expected `,` or `}` in inline table", 1, 13),
+            ("a = { b }\n", "expected `=` in inline table", 1, 9),
+            ("[table\nkey = 1\n", "expected `]` in table header", 1, 7),
+            ("[[bin]\n", "expected `]]` in table header", 1, 6),
+            ("[]\n", "expected a key in table header", 1, 2),
+            ("[a] b = 1\n", "expected end of line in table header", 1, 5),
+        ] {
+            let error = parse(input).unwrap_err();
+            assert_eq!(error.message(), message, "{:?}", input);
+            assert_eq!((error.line(), error.column()), (Some(line), Some(column)), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_error_context() {
+        let error = parse("[package]\nkeywords = [\"toml\", { a = }]\n").unwrap_err();
+        assert_eq!(error.expected(), &["a value"]);
+        assert_eq!(error.context(), &["inline table", "array", "key value pair"]);
+        assert_eq!((error.line(), error.column()), (Some(2), Some(27)));
+
+        let error = parse_cst("a = 1\nb = [1,\n").unwrap_err();
+        assert_eq!(error.expected(), &["a value", "`]`"]);
+        assert_eq!(error.context(), &["array"]);
+    }
+}
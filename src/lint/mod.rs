@@ -0,0 +1,466 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::cst::CstDocument;
+use crate::document::Document;
+use crate::error::{Error, Label};
+use crate::manifest::CargoManifest;
+use crate::parsers::{DisplayPath, PathSegment};
+use crate::span::Span;
+use crate::value::{Table, TomlValue};
+
+mod rules;
+
+/// How much a lint matters. A rule set to `Allow` is not checked at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl Severity {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "allow" => Some(Severity::Allow),
+            "warn" => Some(Severity::Warn),
+            "deny" => Some(Severity::Deny),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Severity::Allow => "allow",
+            Severity::Warn => "warn",
+            Severity::Deny => "deny",
+        })
+    }
+}
+
+/// Something a [`Rule`] found, pointing at where it is in the manifest
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub message: String,
+    pub span: Span,
+    /// Other places that explain the finding, like where a dependency is declared first
+    pub related: Vec<(Span, String)>,
+}
+
+impl Finding {
+    pub fn new(span: Span, message: impl Into<String>) -> Self {
+        Finding {
+            message: message.into(),
+            span,
+            related: vec![],
+        }
+    }
+
+    pub fn with_related(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.related.push((span, message.into()));
+        self
+    }
+}
+
+/// The manifest a rule checks, typed and as the document it was read from, which has the
+/// spans of every key and all the keys Cargo does not know
+#[derive(Debug, Clone, Copy)]
+pub struct LintContext<'a> {
+    pub manifest: &'a CargoManifest,
+    pub document: &'a Document,
+}
+
+/// A check of a manifest. Besides the rules [`Linter::new`] comes with, a linter can be given
+/// rules of your own with [`Linter::with_rule`].
+pub trait Rule {
+    /// The ID the rule is configured and allowed with, like `wildcard-version`
+    fn id(&self) -> &'static str;
+
+    /// What the rule checks, in a few words
+    fn description(&self) -> &'static str;
+
+    /// How much what the rule finds matters, unless the configuration says otherwise
+    fn default_severity(&self) -> Severity {
+        Severity::Warn
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<Finding>;
+}
+
+/// A finding of a rule, with the severity the rule is set to. Render it with
+/// [`Diagnostic::for_lint`](crate::Diagnostic::for_lint).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lint {
+    rule: &'static str,
+    severity: Severity,
+    message: String,
+    span: Span,
+    labels: Vec<Label>,
+    notes: Vec<String>,
+}
+
+impl Lint {
+    fn new(rule: &'static str, severity: Severity, finding: Finding) -> Self {
+        let mut labels = vec![Label::primary(finding.span, finding.message.clone())];
+        labels.extend(
+            finding
+                .related
+                .into_iter()
+                .map(|(span, message)| Label::secondary(span, message)),
+        );
+        Lint {
+            rule,
+            severity,
+            message: finding.message,
+            span: finding.span,
+            labels,
+            notes: vec![format!(
+                "`{}` is set to `{}`, `# lint: allow({})` allows it on a line",
+                rule, severity, rule
+            )],
+        }
+    }
+
+    /// The ID of the rule that found it
+    pub fn rule(&self) -> &'static str {
+        self.rule
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn labels(&self) -> &[Label] {
+        &self.labels
+    }
+
+    pub fn notes(&self) -> &[String] {
+        &self.notes
+    }
+}
+
+/// The severity of each rule that does not keep its default, read from the `[lints]` table of
+/// a file like `toml-lint.toml`. A level is either a string or a table with a `level`, like
+/// the `[lints]` of a manifest:
+///
+/// ```toml
+/// [lints]
+/// wildcard-version = "deny"
+/// unknown-key = { level = "allow" }
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LintConfig {
+    /// The ID of each rule, where it was set, and its severity
+    levels: Vec<(String, Span, Severity)>,
+}
+
+impl LintConfig {
+    /// Sets the severity of a rule
+    pub fn with_severity(mut self, rule: impl Into<String>, severity: Severity) -> Self {
+        self.levels.push((rule.into(), Span::default(), severity));
+        self
+    }
+
+    /// Reads the `[lints]` table of a document. A document without one keeps every rule at
+    /// its default.
+    pub fn from_document(document: &Document) -> Result<Self, Error> {
+        let mut config = LintConfig::default();
+        let lints = [PathSegment::Key("lints".to_string())];
+        let span = |path: &[PathSegment]| {
+            document.spans_at(path).map(|spans| spans.value).unwrap_or_default()
+        };
+        for (key, value) in document.root().iter() {
+            if key != "lints" {
+                let path = [PathSegment::Key(key.clone())];
+                let key_span = document.spans_at(&path).and_then(|spans| spans.key);
+                return Err(Error::invalid_value(
+                    key.clone(),
+                    "only `[lints]` can be configured".to_string(),
+                    key_span.unwrap_or_else(|| span(&path)),
+                ));
+            }
+            let table = <&Table>::try_from(value)
+                .map_err(|e| e.at("lints", span(&lints)))?;
+            for (rule, level) in table.iter() {
+                let mut path = lints.to_vec();
+                path.push(PathSegment::Key(rule.clone()));
+                let name = match level {
+                    TomlValue::Table(table) => {
+                        path.push(PathSegment::Key("level".to_string()));
+                        table.get("level")
+                    }
+                    level => Some(level),
+                };
+                let severity = name
+                    .and_then(TomlValue::as_str)
+                    .and_then(Severity::from_name)
+                    .ok_or_else(|| {
+                        Error::invalid_value(
+                            DisplayPath(&path).to_string(),
+                            "the level has to be `allow`, `warn` or `deny`".to_string(),
+                            span(&path),
+                        )
+                    })?;
+                let key_span = document.spans_at(&path[..2]).and_then(|spans| spans.key);
+                config
+                    .levels
+                    .push((rule.clone(), key_span.unwrap_or_default(), severity));
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl FromStr for LintConfig {
+    type Err = Error;
+
+    /// Parses the configuration from the contents of a file like `toml-lint.toml`
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        LintConfig::from_document(&crate::parse(input)?)
+    }
+}
+
+/// Checks manifests with a set of [`Rule`]s
+pub struct Linter {
+    rules: Vec<Box<dyn Rule>>,
+    config: LintConfig,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Linter::new()
+    }
+}
+
+impl Linter {
+    /// A linter with the rules this crate comes with, each at its default severity
+    pub fn new() -> Self {
+        Linter {
+            rules: rules::all(),
+            config: LintConfig::default(),
+        }
+    }
+
+    /// Adds a rule of your own
+    pub fn with_rule(mut self, rule: impl Rule + 'static) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// Sets the severities of the rules. Fails for an ID no rule has, so add the rules of
+    /// your own first.
+    pub fn with_config(mut self, config: LintConfig) -> Result<Self, Error> {
+        for (id, span, _) in &config.levels {
+            if !self.rules.iter().any(|rule| rule.id() == id) {
+                let path = [PathSegment::Key("lints".to_string()), PathSegment::Key(id.clone())];
+                return Err(Error::invalid_value(
+                    DisplayPath(&path).to_string(),
+                    "unknown lint rule".to_string(),
+                    *span,
+                ));
+            }
+        }
+        self.config = config;
+        Ok(self)
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = &dyn Rule> {
+        self.rules.iter().map(|rule| rule.as_ref())
+    }
+
+    /// The severity a rule is set to, by the configuration or by default
+    pub fn severity(&self, rule: &dyn Rule) -> Severity {
+        self.config
+            .levels
+            .iter()
+            .rev()
+            .find(|(id, _, _)| id == rule.id())
+            .map_or_else(|| rule.default_severity(), |(_, _, severity)| *severity)
+    }
+
+    /// Checks a manifest with every rule that is not set to `allow`, and returns what they
+    /// found in the order it is in the manifest. What a `# lint: allow(rule-id)` comment
+    /// allows is left out. The comment works on its own line, for the line below it, at the
+    /// end of a line, and above or after a header, for the whole table.
+    ///
+    /// Fails if `source` is not a valid manifest.
+    pub fn lint(&self, source: &str) -> Result<Vec<Lint>, Error> {
+        let document = crate::parse(source)?;
+        let manifest = CargoManifest::from_document(&document)?;
+        let context = LintContext {
+            manifest: &manifest,
+            document: &document,
+        };
+        let allowed = allowed_rules(&crate::parse_cst(source)?);
+        let mut lints = vec![];
+        for rule in &self.rules {
+            let severity = self.severity(rule.as_ref());
+            if severity == Severity::Allow {
+                continue;
+            }
+            for finding in rule.check(&context) {
+                let line = finding.span.line();
+                if !allowed.get(line).is_some_and(|rules| rules.iter().any(|id| id == rule.id())) {
+                    lints.push(Lint::new(rule.id(), severity, finding));
+                }
+            }
+        }
+        lints.sort_by_key(|lint| lint.span.start());
+        Ok(lints)
+    }
+}
+
+/// The IDs in a `# lint: allow(a, b)` comment
+fn allow_comment(comment: &str) -> Vec<String> {
+    let comment = comment.trim_start_matches('#').trim();
+    let ids = comment
+        .strip_prefix("lint:")
+        .map(str::trim_start)
+        .and_then(|rest| rest.strip_prefix("allow("))
+        .and_then(|rest| rest.trim_end().strip_suffix(')'));
+    ids.map(|ids| ids.split(',').map(|id| id.trim().to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// The IDs in the `# lint: allow(...)` comments on lines of their own in `leading`
+fn allowed_above(leading: &str) -> Vec<String> {
+    leading.lines().flat_map(|line| allow_comment(line.trim())).collect()
+}
+
+/// The rules `# lint: allow(...)` comments allow on each line, counting from 1. Only the
+/// comments on lines of their own and at the end of a line count, not the ones in strings
+/// or between the values of an array.
+fn allowed_rules(cst: &CstDocument) -> Vec<Vec<String>> {
+    let mut allowed = vec![vec![]];
+    // Adds the lines of `text`, on which `ids` are allowed
+    let add = |allowed: &mut Vec<Vec<String>>, text: &str, ids: &[String]| {
+        let lines = text.split_inclusive('\n').count();
+        allowed.extend(std::iter::repeat_n(ids.to_vec(), lines));
+    };
+    for section in &cst.sections {
+        // Allowed for the whole table
+        let mut table = vec![];
+        if let Some(header) = &section.header {
+            add(&mut allowed, &header.leading, &[]);
+            table = allowed_above(&header.leading);
+            table.extend(allow_comment(header.suffix.trim()));
+            let line = format!("{}{}{}", header.raw, header.suffix, header.newline);
+            add(&mut allowed, &line, &table);
+        }
+        for entry in &section.entries {
+            add(&mut allowed, &entry.leading, &table);
+            let mut here = allowed_above(&entry.leading);
+            here.extend(allow_comment(entry.suffix.trim()));
+            here.extend(table.iter().cloned());
+            add(&mut allowed, &entry.to_string()[entry.leading.len()..], &here);
+        }
+    }
+    allowed
+}
+
+#[cfg(test)]
+mod tests_lint {
+    use super::*;
+
+    #[test]
+    fn test_allow_comment() {
+        assert_eq!(allow_comment("# lint: allow(a, b-c)"), vec!["a", "b-c"]);
+        assert_eq!(allow_comment("#lint:allow(a)"), vec!["a"]);
+        assert!(allow_comment("# allow(a)").is_empty());
+    }
+
+    #[test]
+    fn test_allowed_rules() {
+        let source = "\
+# lint: allow(a)
+x = 1 # lint: allow(b)
+y = 2
+# lint: allow(c)
+[t]
+z = 3
+[u] # lint: allow(d)
+w = 4
+";
+        let allowed = allowed_rules(&crate::parse_cst(source).unwrap());
+        assert_eq!(allowed[2], vec!["a", "b"]);
+        assert!(allowed[3].is_empty());
+        assert_eq!(allowed[5], vec!["c"]);
+        assert_eq!(allowed[6], vec!["c"]);
+        assert_eq!(allowed[8], vec!["d"]);
+    }
+
+    #[test]
+    fn test_allowed_rules_ignore_values() {
+        let source = "\
+x = \"\"\"
+# lint: allow(a)
+y = 1 # lint: allow(b)
+\"\"\"
+z = [
+    # lint: allow(c)
+    1,
+]
+w = 2";
+        let allowed = allowed_rules(&crate::parse_cst(source).unwrap());
+        assert_eq!(allowed.len(), 10);
+        assert!(allowed.iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    fn test_allow_comments() {
+        let source = "\
+[package] # lint: allow(missing-license)
+name = \"x\"
+repository = \"https://example.com/x\"
+
+[dependencies]
+a = \"*\" # lint: allow(wildcard-version)
+# lint: allow(wildcard-version, git-without-rev)
+b = { version = \"*\", git = \"https://example.com/b\" }
+c = \"*\"
+";
+        let lints = Linter::new().lint(source).unwrap();
+        let found: Vec<_> = lints.iter().map(|lint| (lint.rule(), lint.span().line())).collect();
+        assert_eq!(found, vec![("wildcard-version", 9)]);
+
+        let config = LintConfig::default().with_severity("wildcard-version", Severity::Allow);
+        let linter = Linter::new().with_config(config).unwrap();
+        assert!(linter.lint(source).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_config() {
+        let config: LintConfig =
+            "[lints]\nwildcard-version = \"deny\"\nunknown-key = { level = \"allow\" }\n"
+                .parse()
+                .unwrap();
+        let linter = Linter::new().with_config(config).unwrap();
+        let severity = |id: &str| {
+            let rule = linter.rules().find(|rule| rule.id() == id).unwrap();
+            linter.severity(rule)
+        };
+        assert_eq!(severity("wildcard-version"), Severity::Deny);
+        assert_eq!(severity("unknown-key"), Severity::Allow);
+        assert_eq!(severity("git-without-rev"), Severity::Warn);
+
+        let error = "[lints]\nwildcard = \"deny\"".parse::<LintConfig>().unwrap();
+        let error = Linter::new().with_config(error).err().unwrap();
+        assert_eq!(error.to_string(), "unknown lint rule for `lints.wildcard` at line 2, column 1");
+        let error = "[lints]\na = \"loud\"".parse::<LintConfig>().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "the level has to be `allow`, `warn` or `deny` for `lints.a` at line 2, column 5"
+        );
+        assert!("lint = 1".parse::<LintConfig>().is_err());
+    }
+}
@@ -0,0 +1,630 @@
+use crate::lint::{Finding, LintContext, Rule};
+use crate::manifest::{CargoManifest, Dependencies, Dependency, GitReference, Source};
+use crate::parsers::{DisplayPath, PathSegment};
+use crate::span::Span;
+use crate::value::{Table, TomlValue};
+
+/// The rules every [`Linter`](crate::Linter) starts with
+pub(super) fn all() -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(WildcardVersion),
+        Box::new(GitWithoutRev),
+        Box::new(MissingLicense),
+        Box::new(MissingRepository),
+        Box::new(DuplicateDependency),
+        Box::new(DefaultFeaturesWithoutFeatures),
+        Box::new(UnknownKey),
+    ]
+}
+
+/// Every dependency table of the manifest with its path, like `target.'cfg(unix)'.dependencies`
+fn dependency_tables(manifest: &CargoManifest) -> Vec<(String, &Dependencies)> {
+    let path = |segments: &[&str]| {
+        let segments: Vec<_> = segments.iter().map(|s| PathSegment::Key(s.to_string())).collect();
+        DisplayPath(&segments).to_string()
+    };
+    let mut tables = vec![
+        (path(&["dependencies"]), &manifest.dependencies),
+        (path(&["dev-dependencies"]), &manifest.dev_dependencies),
+        (path(&["build-dependencies"]), &manifest.build_dependencies),
+    ];
+    for target in &manifest.target {
+        let cfg = target.cfg.as_str();
+        tables.extend([
+            (path(&["target", cfg, "dependencies"]), &target.dependencies),
+            (path(&["target", cfg, "dev-dependencies"]), &target.dev_dependencies),
+            (path(&["target", cfg, "build-dependencies"]), &target.build_dependencies),
+        ]);
+    }
+    if let Some(workspace) = &manifest.workspace {
+        tables.push((path(&["workspace", "dependencies"]), &workspace.dependencies));
+    }
+    tables
+}
+
+fn dependencies<'a>(context: &LintContext<'a>) -> impl Iterator<Item = &'a Dependency> {
+    dependency_tables(context.manifest)
+        .into_iter()
+        .flat_map(|(_, dependencies)| dependencies)
+}
+
+/// Where the key of a dependency is, or the whole dependency if it has none
+fn key_span(dependency: &Dependency) -> Span {
+    dependency.spans.header.or(dependency.spans.key).unwrap_or(dependency.spans.value)
+}
+
+struct WildcardVersion;
+
+impl Rule for WildcardVersion {
+    fn id(&self) -> &'static str {
+        "wildcard-version"
+    }
+
+    fn description(&self) -> &'static str {
+        "a dependency accepts any version, `*`"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<Finding> {
+        dependencies(context)
+            .filter_map(|dependency| {
+                let version = dependency.version.as_ref()?;
+                (version.trim() == "*").then(|| {
+                    let message = format!("`{}` accepts any version", dependency.name.as_str());
+                    Finding::new(version.span(), message)
+                })
+            })
+            .collect()
+    }
+}
+
+struct GitWithoutRev;
+
+impl Rule for GitWithoutRev {
+    fn id(&self) -> &'static str {
+        "git-without-rev"
+    }
+
+    fn description(&self) -> &'static str {
+        "a git dependency is not pinned to a commit with `rev`"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<Finding> {
+        dependencies(context)
+            .filter_map(|dependency| match &dependency.source {
+                Source::Git { url, reference } => {
+                    (!matches!(reference, Some(GitReference::Rev(_)))).then(|| {
+                        let message =
+                            format!("`{}` is not pinned with `rev`", dependency.name.as_str());
+                        Finding::new(url.span(), message)
+                    })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Where the `[package]` table is, for findings about keys it lacks
+fn package_span(context: &LintContext) -> Span {
+    let spans = context.document.spans_at(&[PathSegment::Key("package".to_string())]);
+    spans.map(|spans| spans.header.or(spans.key).unwrap_or(spans.value)).unwrap_or_default()
+}
+
+struct MissingLicense;
+
+impl Rule for MissingLicense {
+    fn id(&self) -> &'static str {
+        "missing-license"
+    }
+
+    fn description(&self) -> &'static str {
+        "`[package]` has neither `license` nor `license-file`"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<Finding> {
+        match &context.manifest.package {
+            Some(package) if package.license.is_none() && package.license_file.is_none() => {
+                let message = "`[package]` has neither `license` nor `license-file`";
+                vec![Finding::new(package_span(context), message)]
+            }
+            _ => vec![],
+        }
+    }
+}
+
+struct MissingRepository;
+
+impl Rule for MissingRepository {
+    fn id(&self) -> &'static str {
+        "missing-repository"
+    }
+
+    fn description(&self) -> &'static str {
+        "`[package]` has no `repository`"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<Finding> {
+        match &context.manifest.package {
+            Some(package) if package.repository.is_none() => {
+                vec![Finding::new(package_span(context), "`[package]` has no `repository`")]
+            }
+            _ => vec![],
+        }
+    }
+}
+
+struct DuplicateDependency;
+
+impl Rule for DuplicateDependency {
+    fn id(&self) -> &'static str {
+        "duplicate-dependency"
+    }
+
+    fn description(&self) -> &'static str {
+        "a package is a dependency in more than one table"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<Finding> {
+        let mut seen: Vec<(&str, &str, &Dependency)> = vec![];
+        let mut findings = vec![];
+        // The dependencies of the workspace are only there to be inherited
+        let tables = dependency_tables(context.manifest)
+            .into_iter()
+            .filter(|(table, _)| table != "workspace.dependencies")
+            .collect::<Vec<_>>();
+        for (table, dependencies) in &tables {
+            for dependency in dependencies.iter() {
+                let name = dependency.package_name();
+                match seen.iter().find(|(seen, _, _)| *seen == name) {
+                    Some((_, first_table, first)) => {
+                        let message = format!(
+                            "`{}` is already a dependency in `[{}]`",
+                            name, first_table
+                        );
+                        findings.push(
+                            Finding::new(key_span(dependency), message)
+                                .with_related(key_span(first), "first declared here"),
+                        );
+                    }
+                    None => seen.push((name, table, dependency)),
+                }
+            }
+        }
+        findings
+    }
+}
+
+struct DefaultFeaturesWithoutFeatures;
+
+impl Rule for DefaultFeaturesWithoutFeatures {
+    fn id(&self) -> &'static str {
+        "default-features-without-features"
+    }
+
+    fn description(&self) -> &'static str {
+        "a dependency turns off its default features without turning on any"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<Finding> {
+        dependencies(context)
+            .filter_map(|dependency| {
+                let default_features = dependency.default_features.as_ref()?;
+                let features = dependency.features.as_ref().map_or(0, |features| features.len());
+                (!default_features.value && features == 0).then(|| {
+                    let message = format!(
+                        "`{}` turns off its default features without turning on any",
+                        dependency.name.as_str()
+                    );
+                    let spans = default_features.spans;
+                    Finding::new(spans.key.unwrap_or(spans.value), message)
+                })
+            })
+            .collect()
+    }
+}
+
+/// The keys cargo reads in a table
+enum Schema {
+    /// Anything, like the `[package.metadata]` of other tools
+    Any,
+    /// These keys and what each of them holds
+    Keys(&'static [(&'static str, Schema)]),
+    /// Keys of any name, like the names of dependencies, that all hold the same
+    Map(&'static Schema),
+}
+
+use Schema::{Any, Keys, Map};
+
+const DEPENDENCY: Schema = Keys(&[
+    ("version", Any),
+    ("registry", Any),
+    ("registry-index", Any),
+    ("path", Any),
+    ("base", Any),
+    ("git", Any),
+    ("branch", Any),
+    ("tag", Any),
+    ("rev", Any),
+    ("features", Any),
+    ("optional", Any),
+    ("default-features", Any),
+    ("default_features", Any),
+    ("package", Any),
+    ("public", Any),
+    ("workspace", Any),
+    ("artifact", Any),
+    ("lib", Any),
+    ("target", Any),
+]);
+
+const DEPENDENCIES: Schema = Map(&DEPENDENCY);
+
+const TARGET: Schema = Keys(&[
+    ("name", Any),
+    ("path", Any),
+    ("test", Any),
+    ("doctest", Any),
+    ("bench", Any),
+    ("doc", Any),
+    ("doc-scrape-examples", Any),
+    ("harness", Any),
+    ("edition", Any),
+    ("crate-type", Any),
+    ("crate_type", Any),
+    ("proc-macro", Any),
+    ("proc_macro", Any),
+    ("plugin", Any),
+    ("required-features", Any),
+]);
+
+const PACKAGE: Schema = Keys(&[
+    ("name", Any),
+    ("version", Any),
+    ("authors", Any),
+    ("edition", Any),
+    ("rust-version", Any),
+    ("description", Any),
+    ("documentation", Any),
+    ("readme", Any),
+    ("homepage", Any),
+    ("repository", Any),
+    ("license", Any),
+    ("license-file", Any),
+    ("keywords", Any),
+    ("categories", Any),
+    ("workspace", Any),
+    ("build", Any),
+    ("links", Any),
+    ("exclude", Any),
+    ("include", Any),
+    ("publish", Any),
+    ("metadata", Any),
+    ("default-run", Any),
+    ("autolib", Any),
+    ("autobins", Any),
+    ("autoexamples", Any),
+    ("autotests", Any),
+    ("autobenches", Any),
+    ("resolver", Any),
+    ("forced-target", Any),
+    ("im-a-teapot", Any),
+]);
+
+const PLATFORM: Schema = Keys(&[
+    ("dependencies", DEPENDENCIES),
+    ("dev-dependencies", DEPENDENCIES),
+    ("dev_dependencies", DEPENDENCIES),
+    ("build-dependencies", DEPENDENCIES),
+    ("build_dependencies", DEPENDENCIES),
+]);
+
+const PROFILE_OVERRIDE: Schema = Keys(&[
+    ("opt-level", Any),
+    ("debug", Any),
+    ("split-debuginfo", Any),
+    ("strip", Any),
+    ("debug-assertions", Any),
+    ("overflow-checks", Any),
+    ("incremental", Any),
+    ("codegen-units", Any),
+]);
+
+const PROFILE: Schema = Keys(&[
+    ("inherits", Any),
+    ("opt-level", Any),
+    ("debug", Any),
+    ("split-debuginfo", Any),
+    ("strip", Any),
+    ("debug-assertions", Any),
+    ("overflow-checks", Any),
+    ("lto", Any),
+    ("panic", Any),
+    ("incremental", Any),
+    ("codegen-units", Any),
+    ("rpath", Any),
+    ("trim-paths", Any),
+    ("package", Map(&PROFILE_OVERRIDE)),
+    ("build-override", PROFILE_OVERRIDE),
+]);
+
+const WORKSPACE_PACKAGE: Schema = Keys(&[
+    ("version", Any),
+    ("authors", Any),
+    ("edition", Any),
+    ("rust-version", Any),
+    ("description", Any),
+    ("documentation", Any),
+    ("readme", Any),
+    ("homepage", Any),
+    ("repository", Any),
+    ("license", Any),
+    ("license-file", Any),
+    ("keywords", Any),
+    ("categories", Any),
+    ("exclude", Any),
+    ("include", Any),
+    ("publish", Any),
+]);
+
+const WORKSPACE: Schema = Keys(&[
+    ("members", Any),
+    ("default-members", Any),
+    ("exclude", Any),
+    ("resolver", Any),
+    ("package", WORKSPACE_PACKAGE),
+    ("dependencies", DEPENDENCIES),
+    ("lints", Any),
+    ("metadata", Any),
+]);
+
+const ROOT: Schema = Keys(&[
+    ("cargo-features", Any),
+    ("package", PACKAGE),
+    ("project", PACKAGE),
+    ("lib", TARGET),
+    ("bin", TARGET),
+    ("example", TARGET),
+    ("test", TARGET),
+    ("bench", TARGET),
+    ("dependencies", DEPENDENCIES),
+    ("dev-dependencies", DEPENDENCIES),
+    ("dev_dependencies", DEPENDENCIES),
+    ("build-dependencies", DEPENDENCIES),
+    ("build_dependencies", DEPENDENCIES),
+    ("target", Map(&PLATFORM)),
+    ("badges", Any),
+    ("features", Any),
+    ("lints", Any),
+    ("patch", Map(&DEPENDENCIES)),
+    ("replace", Any),
+    ("profile", Map(&PROFILE)),
+    ("workspace", WORKSPACE),
+]);
+
+struct UnknownKey;
+
+impl UnknownKey {
+    /// Checks the tables in `value`, which is at `path`
+    fn value(
+        context: &LintContext,
+        value: &TomlValue,
+        schema: &Schema,
+        path: &mut Vec<PathSegment>,
+        findings: &mut Vec<Finding>,
+    ) {
+        match value {
+            TomlValue::Table(table) => Self::table(context, table, schema, path, findings),
+            // Arrays of tables, like `[[bin]]`
+            TomlValue::Array(values) => {
+                for (i, value) in values.iter().enumerate() {
+                    path.push(PathSegment::Index(i));
+                    Self::value(context, value, schema, path, findings);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn table(
+        context: &LintContext,
+        table: &Table,
+        schema: &Schema,
+        path: &mut Vec<PathSegment>,
+        findings: &mut Vec<Finding>,
+    ) {
+        for (key, value) in table.iter() {
+            let schema = match schema {
+                Any => return,
+                Keys(keys) => match keys.iter().find(|(name, _)| name == key) {
+                    Some((_, schema)) => schema,
+                    None => {
+                        path.push(PathSegment::Key(key.clone()));
+                        let spans = context.document.spans_at(path);
+                        let span = spans.map(|s| s.key.unwrap_or(s.value)).unwrap_or_default();
+                        path.pop();
+                        // The table as its header names it, `[[bin]]` for the tables of
+                        // an array
+                        let keys: Vec<PathSegment> = path
+                            .iter()
+                            .filter(|segment| matches!(segment, PathSegment::Key(_)))
+                            .cloned()
+                            .collect();
+                        let message = match path.last() {
+                            None => format!("unknown key `{}`", key),
+                            Some(PathSegment::Index(_)) => {
+                                format!("unknown key `{}` in `[[{}]]`", key, DisplayPath(&keys))
+                            }
+                            Some(PathSegment::Key(_)) => {
+                                format!("unknown key `{}` in `[{}]`", key, DisplayPath(&keys))
+                            }
+                        };
+                        findings.push(Finding::new(span, message));
+                        continue;
+                    }
+                },
+                Map(schema) => schema,
+            };
+            path.push(PathSegment::Key(key.clone()));
+            Self::value(context, value, schema, path, findings);
+            path.pop();
+        }
+    }
+}
+
+impl Rule for UnknownKey {
+    fn id(&self) -> &'static str {
+        "unknown-key"
+    }
+
+    fn description(&self) -> &'static str {
+        "a key cargo does not read, which is often a typo"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<Finding> {
+        let mut findings = vec![];
+        Self::table(context, context.document.root(), &ROOT, &mut vec![], &mut findings);
+        findings
+    }
+}
+
+#[cfg(test)]
+mod tests_rules {
+    use crate::lint::{Linter, Severity};
+
+    /// The rule, line, column and message of every lint
+    fn lint(source: &str) -> Vec<(&'static str, String)> {
+        Linter::new()
+            .lint(source)
+            .unwrap()
+            .iter()
+            .map(|lint| (lint.rule(), format!("{} {}", lint.span(), lint.message())))
+            .collect()
+    }
+
+    const PACKAGE: &str = "\
+[package]
+name = \"x\"
+license = \"MIT\"
+repository = \"https://example.com/x\"
+";
+
+    #[test]
+    fn test_examples() {
+        let source = std::fs::read_to_string("assets/cargo_examples/cargo-expand.toml").unwrap();
+        assert_eq!(
+            lint(&source),
+            vec![(
+                "default-features-without-features",
+                "18:28 `quote` turns off its default features without turning on any".to_string()
+            )]
+        );
+        let source = std::fs::read_to_string("assets/cargo_examples/pyo3.toml").unwrap();
+        assert!(lint(&source).is_empty());
+    }
+
+    #[test]
+    fn test_package() {
+        assert_eq!(
+            lint("[package]\nname = \"x\"\nlicense-file = \"LICENSE\"\n"),
+            vec![("missing-repository", "1:1 `[package]` has no `repository`".to_string())]
+        );
+        assert_eq!(
+            lint("[package]\nname = \"x\"\nrepository.workspace = true\n"),
+            vec![(
+                "missing-license",
+                "1:1 `[package]` has neither `license` nor `license-file`".to_string()
+            )]
+        );
+        // A virtual workspace has no package to describe
+        assert!(lint("[workspace]\nmembers = [\"a\"]\n").is_empty());
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let source = format!(
+            "{}\
+[dependencies]
+a = \"*\"
+b = {{ git = \"https://example.com/b\", branch = \"main\" }}
+c = {{ git = \"https://example.com/c\", rev = \"0c1d\" }}
+d = {{ version = \"1\", default-features = false }}
+e = {{ version = \"1\", default-features = false, features = [\"std\"] }}
+
+[target.'cfg(unix)'.dev-dependencies]
+x = {{ package = \"a\", version = \"1\" }}
+
+[workspace.dependencies]
+a = \"1\"
+f = {{ version = \"*\" }}
+",
+            PACKAGE
+        );
+        assert_eq!(
+            lint(&source),
+            vec![
+                ("wildcard-version", "6:5 `a` accepts any version".to_string()),
+                ("git-without-rev", "7:13 `b` is not pinned with `rev`".to_string()),
+                (
+                    "default-features-without-features",
+                    "9:22 `d` turns off its default features without turning on any".to_string()
+                ),
+                (
+                    "duplicate-dependency",
+                    "13:1 `a` is already a dependency in `[dependencies]`".to_string()
+                ),
+                ("wildcard-version", "17:17 `f` accepts any version".to_string()),
+            ]
+        );
+        let linter = Linter::new();
+        let lints = linter.lint(&source).unwrap();
+        let duplicate = lints.iter().find(|lint| lint.rule() == "duplicate-dependency").unwrap();
+        assert_eq!(duplicate.labels()[1].span().line(), 6);
+        assert_eq!(duplicate.severity(), Severity::Warn);
+    }
+
+    #[test]
+    fn test_unknown_key() {
+        let source = format!(
+            "{}\
+edition = \"2021\"
+licence = \"MIT\"
+
+[package.metadata.docs]
+anything = true
+
+[dependencies]
+a = {{ version = \"1\", feature = [\"x\"] }}
+
+[[bin]]
+name = \"x\"
+paht = \"main.rs\"
+
+[profile.release.package.a]
+opt-level = 3
+lto = true
+
+[dependancies]
+
+[[bin]]
+name = \"y\"
+tset = false
+",
+            PACKAGE
+        );
+        assert_eq!(
+            lint(&source),
+            vec![
+                ("unknown-key", "6:1 unknown key `licence` in `[package]`".to_string()),
+                ("unknown-key", "12:22 unknown key `feature` in `[dependencies.a]`".to_string()),
+                ("unknown-key", "16:1 unknown key `paht` in `[[bin]]`".to_string()),
+                (
+                    "unknown-key",
+                    "20:1 unknown key `lto` in `[profile.release.package.a]`".to_string()
+                ),
+                ("unknown-key", "22:2 unknown key `dependancies`".to_string()),
+                ("unknown-key", "26:1 unknown key `tset` in `[[bin]]`".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,346 @@
+use crate::error::Error;
+use crate::manifest::{key, Reader, Spanned, Strings};
+use crate::parsers::{DisplayPath, PathSegment};
+use crate::span::{Span, Spans};
+use crate::value::TomlValue;
+
+/// Which of the ways to write a dependency the manifest uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DependencyStyle {
+    /// Only a version requirement, `serde = "1"`
+    Version,
+    /// An inline table, `serde = { version = "1" }`. Dotted keys, `serde.version = "1"`,
+    /// count as one too.
+    InlineTable,
+    /// A table of its own, `[dependencies.serde]`
+    Table,
+}
+
+/// Which commit of a git repository to use. Without one, the default branch is used.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GitReference {
+    Branch(Spanned<String>),
+    Tag(Spanned<String>),
+    Rev(Spanned<String>),
+}
+
+/// Where a dependency comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    /// crates.io, or the registry named by `registry`
+    Registry(Option<Spanned<String>>),
+    Git {
+        url: Spanned<String>,
+        reference: Option<GitReference>,
+    },
+    /// A directory, relative to the manifest
+    Path(Spanned<String>),
+    /// `workspace = true`, which takes the rest of the dependency from the
+    /// `[workspace.dependencies]` of the workspace root. Points at the `workspace` key.
+    Workspace(Span),
+}
+
+/// A dependency, the same whichever way it is written in the manifest
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dependency {
+    /// The name the package uses for the dependency, which is the key it is defined by
+    pub name: Spanned<String>,
+    /// The name of the dependency on its registry, if the package renames it
+    pub package: Option<Spanned<String>>,
+    /// The version requirement. Dependencies from git or a path do not need one, but can
+    /// have one to be published.
+    pub version: Option<Spanned<String>>,
+    pub source: Source,
+    pub features: Option<Strings>,
+    pub default_features: Option<Spanned<bool>>,
+    pub optional: Option<Spanned<bool>>,
+    pub style: DependencyStyle,
+    /// The whole definition of the dependency
+    pub spans: Spans,
+}
+
+impl Dependency {
+    /// The name of the dependency on its registry, which is its name unless it is renamed
+    pub fn package_name(&self) -> &str {
+        match &self.package {
+            Some(package) => package,
+            None => &self.name,
+        }
+    }
+
+    pub fn is_optional(&self) -> bool {
+        self.optional.as_ref().is_some_and(|optional| optional.value)
+    }
+
+    pub fn uses_default_features(&self) -> bool {
+        self.default_features
+            .as_ref()
+            .is_none_or(|default_features| default_features.value)
+    }
+
+    /// Whether the dependency is taken from the workspace, `workspace = true`
+    pub fn is_inherited(&self) -> bool {
+        matches!(self.source, Source::Workspace(_))
+    }
+}
+
+impl Reader<'_> {
+    /// Reads a dependency from either a version requirement or a table
+    pub(super) fn dependency(
+        &self,
+        name: Spanned<String>,
+        path: &[PathSegment],
+    ) -> Result<Dependency, Error> {
+        let spans = self.spans(path);
+        match self.document.root().lookup(path) {
+            Some(TomlValue::Str(_)) => Ok(Dependency {
+                name,
+                package: None,
+                version: self.string(path)?,
+                source: Source::Registry(None),
+                features: None,
+                default_features: None,
+                optional: None,
+                style: DependencyStyle::Version,
+                spans,
+            }),
+            Some(TomlValue::Table(_)) => {
+                let style = match spans.header {
+                    Some(_) => DependencyStyle::Table,
+                    None => DependencyStyle::InlineTable,
+                };
+                // Older manifests spell it with an underscore, which cargo still reads
+                let default_features = match self.boolean(&key(path, "default-features"))? {
+                    Some(default_features) => Some(default_features),
+                    None => self.boolean(&key(path, "default_features"))?,
+                };
+                Ok(Dependency {
+                    name,
+                    package: self.string(&key(path, "package"))?,
+                    version: self.string(&key(path, "version"))?,
+                    source: self.source(path)?,
+                    features: self.strings(&key(path, "features"))?,
+                    default_features,
+                    optional: self.boolean(&key(path, "optional"))?,
+                    style,
+                    spans,
+                })
+            }
+            Some(value) => Err(value
+                .invalid_type("a version requirement or a table")
+                .at(&DisplayPath(path).to_string(), spans.value)),
+            None => unreachable!("dependencies are read from the keys of their table"),
+        }
+    }
+
+    fn invalid(&self, path: &[PathSegment], message: &str) -> Error {
+        Error::invalid_value(
+            DisplayPath(path).to_string(),
+            message.to_string(),
+            self.spans(path).value,
+        )
+    }
+
+    /// Reads the keys of a dependency table that say where it comes from, and checks that
+    /// they do not contradict each other
+    fn source(&self, path: &[PathSegment]) -> Result<Source, Error> {
+        let git = self.string(&key(path, "git"))?;
+        let local = self.string(&key(path, "path"))?;
+        let registry = self.string(&key(path, "registry"))?;
+
+        if let Some(workspace) = self.boolean(&key(path, "workspace"))? {
+            if !workspace.value {
+                return Err(self.invalid(&key(path, "workspace"), "`workspace` can only be `true`"));
+            }
+            for name in ["version", "git", "path", "registry"] {
+                if self.document.root().lookup(&key(path, name)).is_some() {
+                    return Err(self.invalid(
+                        &key(path, name),
+                        &format!("a dependency taken from the workspace cannot set `{}`", name),
+                    ));
+                }
+            }
+            return Ok(Source::Workspace(workspace.span()));
+        }
+
+        let mut reference = None;
+        for name in ["branch", "tag", "rev"] {
+            let value = match self.string(&key(path, name))? {
+                Some(value) => value,
+                None => continue,
+            };
+            if git.is_none() {
+                return Err(self.invalid(
+                    &key(path, name),
+                    &format!("`{}` can only be used with `git`", name),
+                ));
+            }
+            if reference.is_some() {
+                return Err(self.invalid(
+                    &key(path, name),
+                    "only one of `branch`, `tag` or `rev` can be used",
+                ));
+            }
+            reference = Some(match name {
+                "branch" => GitReference::Branch(value),
+                "tag" => GitReference::Tag(value),
+                _ => GitReference::Rev(value),
+            });
+        }
+
+        match (git, local) {
+            (Some(_), Some(_)) => Err(self.invalid(
+                &key(path, "path"),
+                "a dependency cannot come from both `git` and `path`",
+            )),
+            (Some(url), None) => Ok(Source::Git { url, reference }),
+            (None, Some(local)) => Ok(Source::Path(local)),
+            (None, None) => Ok(Source::Registry(registry)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_dependency {
+    use std::fs::read_to_string;
+
+    use crate::{parse_manifest, ErrorKind};
+
+    use super::*;
+
+    fn dependencies(input: &str) -> Vec<Dependency> {
+        parse_manifest(input).unwrap().dependencies
+    }
+
+    fn find<'d>(dependencies: &'d [Dependency], name: &str) -> &'d Dependency {
+        dependencies
+            .iter()
+            .find(|dependency| dependency.name.as_str() == name)
+            .unwrap()
+    }
+
+    fn version(dependency: &Dependency) -> Option<&str> {
+        dependency.version.as_ref().map(|version| version.as_str())
+    }
+
+    fn features(dependency: &Dependency) -> Vec<&str> {
+        match &dependency.features {
+            Some(features) => features.iter().map(|feature| feature.as_str()).collect(),
+            None => vec![],
+        }
+    }
+
+    #[test]
+    fn test_styles() {
+        let input = read_to_string("assets/cargo_examples/cargo-expand.toml").unwrap();
+        let dependencies = parse_manifest(&input).unwrap().dependencies;
+
+        let atty = find(&dependencies, "atty");
+        assert_eq!(atty.style, DependencyStyle::Version);
+        assert_eq!(version(atty), Some("0.2"));
+        assert_eq!(atty.source, Source::Registry(None));
+        assert!(atty.uses_default_features());
+
+        let serde = find(&dependencies, "serde");
+        assert_eq!(serde.style, DependencyStyle::InlineTable);
+        assert_eq!(version(serde), Some("1.0"));
+        assert_eq!(features(serde), vec!["derive"]);
+
+        let bat = find(&dependencies, "bat");
+        assert_eq!(bat.style, DependencyStyle::Table);
+        assert_eq!(version(bat), Some("0.18"));
+        assert_eq!(features(bat), vec!["paging", "regex-fancy"]);
+        assert!(!bat.uses_default_features());
+        assert_eq!(bat.spans.header.unwrap().line(), 27);
+    }
+
+    #[test]
+    fn test_sources() {
+        let input = read_to_string("assets/cargo_examples/pyo3.toml").unwrap();
+        let manifest = parse_manifest(&input).unwrap();
+
+        let macros = find(&manifest.dependencies, "pyo3-macros");
+        assert_eq!(version(macros), Some("=0.15.1"));
+        assert!(macros.is_optional());
+        match &macros.source {
+            Source::Path(path) => assert_eq!(path.as_str(), "pyo3-macros"),
+            source => panic!("expected a path, got {:?}", source),
+        }
+
+        let input = r#"
+[dependencies]
+nom = { git = "https://github.com/Geal/nom", branch = "main" }
+json = { package = "serde_json", version = "1", registry = "internal" }
+log = { workspace = true, features = ["std"] }
+old = { version = "1", default_features = false }
+"#;
+        let dependencies = dependencies(input);
+        match &find(&dependencies, "nom").source {
+            Source::Git {
+                url,
+                reference: Some(GitReference::Branch(branch)),
+            } => {
+                assert_eq!(url.as_str(), "https://github.com/Geal/nom");
+                assert_eq!(branch.as_str(), "main");
+            }
+            source => panic!("expected a git branch, got {:?}", source),
+        }
+
+        let json = find(&dependencies, "json");
+        assert_eq!(json.package_name(), "serde_json");
+        match &json.source {
+            Source::Registry(Some(registry)) => assert_eq!(registry.as_str(), "internal"),
+            source => panic!("expected a registry, got {:?}", source),
+        }
+
+        let log = find(&dependencies, "log");
+        assert!(log.is_inherited());
+        assert_eq!(log.package_name(), "log");
+        assert_eq!(features(log), vec!["std"]);
+
+        assert!(!find(&dependencies, "old").uses_default_features());
+    }
+
+    fn error(input: &str) -> (ErrorKind, String) {
+        let error = parse_manifest(input).unwrap_err();
+        (error.kind().clone(), error.to_string())
+    }
+
+    #[test]
+    fn test_invalid_dependencies() {
+        let invalid = |path: &str| ErrorKind::InvalidValue {
+            path: path.to_string(),
+        };
+        assert_eq!(
+            error("[dependencies]\nnom = { git = \"x\", path = \"y\" }\n"),
+            (
+                invalid("dependencies.nom.path"),
+                "a dependency cannot come from both `git` and `path` for \
+                 `dependencies.nom.path` at line 2, column 27"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            error("[dependencies]\nnom = { version = \"7\", tag = \"v7\" }\n").1,
+            "`tag` can only be used with `git` for `dependencies.nom.tag` at line 2, column 30"
+        );
+        assert_eq!(
+            error("[dependencies.nom]\ngit = \"x\"\nbranch = \"a\"\nrev = \"b\"\n").1,
+            "only one of `branch`, `tag` or `rev` can be used for `dependencies.nom.rev` \
+             at line 4, column 7"
+        );
+        assert_eq!(
+            error("[dependencies]\nnom = { workspace = true, version = \"7\" }\n").0,
+            invalid("dependencies.nom.version")
+        );
+        assert_eq!(
+            error("[dependencies]\nnom = { workspace = false }\n").0,
+            invalid("dependencies.nom.workspace")
+        );
+        assert_eq!(
+            error("[dependencies]\nnom = 7\n").1,
+            "expected a version requirement or a table, found integer for `dependencies.nom` \
+             at line 2, column 7"
+        );
+    }
+}
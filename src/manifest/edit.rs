@@ -0,0 +1,425 @@
+use crate::cst::{key_path, CstDocument, CstKeyValue};
+use crate::parsers::{Key, PathSegment};
+use crate::value::{Table, TomlValue};
+
+/// Which kind of dependencies a table holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DependencyKind {
+    #[default]
+    Normal,
+    Development,
+    Build,
+}
+
+impl DependencyKind {
+    /// The key of the table, like `dev-dependencies`
+    pub fn key(&self) -> &'static str {
+        match self {
+            DependencyKind::Normal => "dependencies",
+            DependencyKind::Development => "dev-dependencies",
+            DependencyKind::Build => "build-dependencies",
+        }
+    }
+}
+
+/// A table of dependencies, `[dependencies]` or one for a platform, like
+/// `[target.'cfg(unix)'.dependencies]`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DependencyTable {
+    pub kind: DependencyKind,
+    /// A target triple or a `cfg(...)` expression
+    pub target: Option<String>,
+}
+
+impl DependencyTable {
+    pub fn new(kind: DependencyKind) -> Self {
+        DependencyTable { kind, target: None }
+    }
+
+    pub fn for_target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    fn path(&self, name: &str) -> Vec<PathSegment> {
+        let mut path = vec![];
+        if let Some(target) = &self.target {
+            path.push(PathSegment::Key("target".to_string()));
+            path.push(PathSegment::Key(target.clone()));
+        }
+        path.push(PathSegment::Key(self.kind.key().to_string()));
+        path.push(PathSegment::Key(name.to_string()));
+        path
+    }
+}
+
+/// A dependency to add to a manifest. Only the fields that are set are written, as a
+/// version requirement, `nom = "7"`, if that is all there is, and as an inline table
+/// otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct NewDependency {
+    pub name: String,
+    pub version: Option<String>,
+    pub git: Option<String>,
+    pub branch: Option<String>,
+    pub tag: Option<String>,
+    pub rev: Option<String>,
+    pub path: Option<String>,
+    pub registry: Option<String>,
+    /// The name of the dependency on its registry, to rename it
+    pub package: Option<String>,
+    /// Takes the dependency from the workspace, `workspace = true`
+    pub workspace: bool,
+    pub default_features: Option<bool>,
+    pub features: Vec<String>,
+    pub optional: bool,
+}
+
+impl NewDependency {
+    pub fn new(name: impl Into<String>) -> Self {
+        NewDependency {
+            name: name.into(),
+            ..NewDependency::default()
+        }
+    }
+
+    /// Each key a dependency table can have, in the order they are written, with its value
+    /// if it is set
+    fn fields(&self) -> Vec<(&'static str, Option<TomlValue>)> {
+        let string = |value: &Option<String>| value.clone().map(TomlValue::Str);
+        let features = (!self.features.is_empty()).then(|| {
+            TomlValue::Array(self.features.iter().cloned().map(TomlValue::Str).collect())
+        });
+        vec![
+            ("version", string(&self.version)),
+            ("git", string(&self.git)),
+            ("branch", string(&self.branch)),
+            ("tag", string(&self.tag)),
+            ("rev", string(&self.rev)),
+            ("path", string(&self.path)),
+            ("registry", string(&self.registry)),
+            ("package", string(&self.package)),
+            ("workspace", self.workspace.then_some(TomlValue::Boolean(true))),
+            ("default-features", self.default_features.map(TomlValue::Boolean)),
+            // The old spelling, which a new value replaces
+            ("default_features", None),
+            ("features", features),
+            ("optional", self.optional.then_some(TomlValue::Boolean(true))),
+        ]
+    }
+
+    fn to_value(&self) -> TomlValue {
+        let mut table = Table::new();
+        for (key, value) in self.fields() {
+            if let Some(value) = value {
+                table.insert(key, value);
+            }
+        }
+        match &self.version {
+            Some(version) if table.len() == 1 => TomlValue::Str(version.clone()),
+            _ => TomlValue::Table(table),
+        }
+    }
+}
+
+impl CstDocument {
+    /// Whether anything at `path` is defined by a table of its own or by dotted keys
+    fn is_table_style(&self, path: &[PathSegment]) -> bool {
+        let paths = self.section_paths();
+        paths.iter().any(|section_path| section_path.starts_with(path))
+            || self.sections.iter().zip(&paths).any(|(section, section_path)| {
+                section.entries.iter().any(|entry| {
+                    let mut full = section_path.clone();
+                    full.extend(key_path(&entry.key));
+                    full.len() > path.len() && full.starts_with(path)
+                })
+            })
+    }
+
+    /// Whether a key value pair defines the value at `path` or a table it is in
+    fn defines(&self, path: &[PathSegment]) -> bool {
+        self.sections
+            .iter()
+            .zip(self.section_paths())
+            .any(|(section, section_path)| {
+                section.entries.iter().any(|entry| {
+                    let mut full = section_path.clone();
+                    full.extend(key_path(&entry.key));
+                    path.starts_with(&full)
+                })
+            })
+    }
+
+    /// Adds a dependency to a table, keeping the order of the table if its dependencies are
+    /// sorted by name, and adding the table if there is none. A dependency that is already
+    /// there is replaced where it is. One defined as a table of its own,
+    /// `[dependencies.nom]`, stays one, with the keys that are not set removed.
+    ///
+    /// Returns `false` if the table cannot hold the dependency, like when `dependencies`
+    /// is a string. The document is left as it was then.
+    pub fn add_dependency(&mut self, table: &DependencyTable, dependency: &NewDependency) -> bool {
+        let path = table.path(&dependency.name);
+        if self.is_table_style(&path) {
+            // The keys are set one by one on a copy, which only replaces the document once all
+            // of them are set and none of them contradicts a key that was already there
+            let mut edited = self.clone();
+            for (key, value) in dependency.fields() {
+                let mut field = path.clone();
+                field.push(PathSegment::Key(key.to_string()));
+                let done = match value {
+                    Some(value) => edited.set_at(&field, value),
+                    None => {
+                        edited.remove_at(&field);
+                        true
+                    }
+                };
+                if !done {
+                    return false;
+                }
+            }
+            if edited.to_document().is_err() {
+                return false;
+            }
+            *self = edited;
+            return true;
+        }
+        if !self.defines(&path) && self.insert_sorted(&path, dependency) {
+            return true;
+        }
+        self.set_at(&path, dependency.to_value())
+    }
+
+    /// Inserts a new dependency into the table it belongs to, before the first one whose name
+    /// comes after it if the table is sorted. Returns `false` if the table has no header of
+    /// its own, or the dependency belongs at its end, which `set_at` takes care of.
+    fn insert_sorted(&mut self, path: &[PathSegment], dependency: &NewDependency) -> bool {
+        let table = &path[..path.len() - 1];
+        let Some(index) = self
+            .section_paths()
+            .iter()
+            .position(|section_path| section_path == table)
+        else {
+            return false;
+        };
+        let newline = self.line_ending();
+        let section = &mut self.sections[index];
+        let names: Vec<&str> = section
+            .entries
+            .iter()
+            .map(|entry| entry.key.segments()[0].as_str())
+            .collect();
+        if !names.windows(2).all(|pair| pair[0] <= pair[1]) {
+            return false;
+        }
+        let Some(position) = names.iter().position(|name| *name > dependency.name.as_str()) else {
+            return false;
+        };
+        let indent = section.entries[position].indent.clone();
+        let key = Key::new(vec![dependency.name.clone()]);
+        let entry = CstKeyValue::new(indent, key, dependency.to_value(), newline);
+        section.entries.insert(position, entry);
+        true
+    }
+
+    /// Removes a dependency from a table, whichever way it is defined. Returns `false` if
+    /// it is not there.
+    pub fn remove_dependency(&mut self, table: &DependencyTable, name: &str) -> bool {
+        self.remove_at(&table.path(name))
+    }
+
+    /// Changes the version requirement of a dependency, keeping the rest of its definition.
+    /// Returns `false` if it is not there, or takes its version from the workspace.
+    pub fn upgrade_dependency(
+        &mut self,
+        table: &DependencyTable,
+        name: &str,
+        version: &str,
+    ) -> bool {
+        let path = table.path(name);
+        let Ok(document) = self.to_document() else {
+            return false;
+        };
+        let version = TomlValue::Str(version.to_string());
+        match document.root().lookup(&path) {
+            Some(TomlValue::Str(_)) => self.set_at(&path, version),
+            Some(TomlValue::Table(dependency)) if !dependency.contains_key("workspace") => {
+                let mut field = path;
+                field.push(PathSegment::Key("version".to_string()));
+                self.set_at(&field, version)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_edit {
+    use crate::parse_cst;
+
+    use super::*;
+
+    const INPUT: &str = r#"[package]
+name = "x"
+
+[dependencies]
+# parsing
+nom = "7" # the parser
+serde = { version = "1", features = ["derive"] }
+
+[dependencies.tokio]
+version = "1"
+features = ["full"] # everything
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+"#;
+
+    fn edit(edit: impl FnOnce(&mut CstDocument) -> bool) -> String {
+        let mut cst = parse_cst(INPUT).unwrap();
+        assert!(edit(&mut cst));
+        let output = cst.to_string();
+        crate::parse_manifest(&output).unwrap();
+        output
+    }
+
+    fn dependency(name: &str, version: &str) -> NewDependency {
+        NewDependency {
+            version: Some(version.to_string()),
+            ..NewDependency::new(name)
+        }
+    }
+
+    #[test]
+    fn test_add_sorted() {
+        let normal = DependencyTable::default();
+        assert_eq!(
+            edit(|cst| cst.add_dependency(&normal, &dependency("regex", "1"))),
+            INPUT.replace("serde = {", "regex = \"1\"\nserde = {")
+        );
+        assert_eq!(
+            edit(|cst| cst.add_dependency(&normal, &dependency("anyhow", "1"))),
+            INPUT.replace("# parsing\n", "anyhow = \"1\"\n# parsing\n")
+        );
+        assert_eq!(
+            edit(|cst| cst.add_dependency(&normal, &dependency("toml", "0.5"))),
+            INPUT.replace("[\"derive\"] }\n", "[\"derive\"] }\ntoml = \"0.5\"\n")
+        );
+        let unix = DependencyTable::default().for_target("cfg(unix)");
+        let nix = NewDependency {
+            features: vec!["fs".to_string()],
+            default_features: Some(false),
+            ..dependency("nix", "0.26")
+        };
+        assert_eq!(
+            edit(|cst| cst.add_dependency(&unix, &nix)),
+            format!(
+                "{}nix = {{ version = \"0.26\", default-features = false, features = [\"fs\"] }}\n",
+                INPUT
+            )
+        );
+    }
+
+    #[test]
+    fn test_add_new_table() {
+        let dev = DependencyTable::new(DependencyKind::Development);
+        let local = NewDependency {
+            path: Some("../local".to_string()),
+            ..NewDependency::new("local")
+        };
+        assert_eq!(
+            edit(|cst| cst.add_dependency(&dev, &local)),
+            format!("{}\n[dev-dependencies]\nlocal = {{ path = \"../local\" }}\n", INPUT)
+        );
+        let windows = DependencyTable::new(DependencyKind::Build).for_target("cfg(windows)");
+        assert_eq!(
+            edit(|cst| cst.add_dependency(&windows, &dependency("cc", "1"))),
+            format!("{}\n[target.'cfg(windows)'.build-dependencies]\ncc = \"1\"\n", INPUT)
+        );
+    }
+
+    #[test]
+    fn test_replace_existing() {
+        let normal = DependencyTable::default();
+        let nom = NewDependency {
+            optional: true,
+            ..dependency("nom", "8")
+        };
+        assert_eq!(
+            edit(|cst| cst.add_dependency(&normal, &nom)),
+            INPUT.replace("nom = \"7\"", "nom = { version = \"8\", optional = true }")
+        );
+        assert_eq!(
+            edit(|cst| cst.add_dependency(&normal, &dependency("tokio", "2"))),
+            INPUT.replace(
+                "version = \"1\"\nfeatures = [\"full\"] # everything\n",
+                "version = \"2\"\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_add_fails_without_changes() {
+        // `version` can be set, but `features` is a table of dotted keys, not an array
+        let input = "[dependencies.nom]\nversion = \"6\"\nfeatures.std = true\n";
+        let mut cst = parse_cst(input).unwrap();
+        let nom = NewDependency {
+            version: Some("8".to_string()),
+            features: vec!["std".to_string()],
+            ..NewDependency::new("nom")
+        };
+        assert!(!cst.add_dependency(&DependencyTable::default(), &nom));
+        assert_eq!(cst.to_string(), input);
+    }
+
+    #[test]
+    fn test_remove() {
+        let normal = DependencyTable::default();
+        assert_eq!(
+            edit(|cst| cst.remove_dependency(&normal, "nom")),
+            INPUT.replace("# parsing\nnom = \"7\" # the parser\n", "")
+        );
+        assert_eq!(
+            edit(|cst| cst.remove_dependency(&normal, "tokio")),
+            INPUT.replace(
+                "\n[dependencies.tokio]\nversion = \"1\"\nfeatures = [\"full\"] # everything\n",
+                ""
+            )
+        );
+        let unix = DependencyTable::default().for_target("cfg(unix)");
+        assert_eq!(
+            edit(|cst| cst.remove_dependency(&unix, "libc")),
+            INPUT.replace("libc = \"0.2\"\n", "")
+        );
+        let mut cst = parse_cst(INPUT).unwrap();
+        assert!(!cst.remove_dependency(&normal, "libc"));
+        assert_eq!(cst.to_string(), INPUT);
+    }
+
+    #[test]
+    fn test_remove_keeps_blank_lines() {
+        let input = "[dependencies]\na = \"1\"\n\n# group\nb = \"1\"\nc = \"1\"\n";
+        let mut cst = parse_cst(input).unwrap();
+        assert!(cst.remove_dependency(&DependencyTable::default(), "b"));
+        assert_eq!(cst.to_string(), "[dependencies]\na = \"1\"\n\nc = \"1\"\n");
+    }
+
+    #[test]
+    fn test_upgrade() {
+        let normal = DependencyTable::default();
+        assert_eq!(
+            edit(|cst| cst.upgrade_dependency(&normal, "nom", "8")),
+            INPUT.replace("nom = \"7\"", "nom = \"8\"")
+        );
+        assert_eq!(
+            edit(|cst| cst.upgrade_dependency(&normal, "serde", "1.0.200")),
+            INPUT.replace("{ version = \"1\",", "{ version = \"1.0.200\",")
+        );
+        assert_eq!(
+            edit(|cst| cst.upgrade_dependency(&normal, "tokio", "1.38")),
+            INPUT.replace("version = \"1\"\nfeatures", "version = \"1.38\"\nfeatures")
+        );
+        let mut cst = parse_cst("[dependencies]\nlog = { workspace = true }\n").unwrap();
+        assert!(!cst.upgrade_dependency(&normal, "log", "1"));
+        assert!(!cst.upgrade_dependency(&normal, "missing", "1"));
+    }
+}
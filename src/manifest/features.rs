@@ -0,0 +1,554 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+
+use crate::manifest::{CargoManifest, Dependency, Feature, Spanned};
+use crate::span::Span;
+
+/// What an entry of a feature turns on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeatureRef {
+    /// Another feature, or an optional dependency through the feature cargo defines for it,
+    /// `"std"`
+    Feature(String),
+    /// An optional dependency, `"dep:serde"`
+    Dependency(String),
+    /// A feature of a dependency, `"serde/derive"`, which turns the dependency on too if it is
+    /// optional. A weak one, `"serde?/derive"`, only applies once something else turns the
+    /// dependency on.
+    DependencyFeature {
+        dependency: String,
+        feature: String,
+        weak: bool,
+    },
+}
+
+impl FeatureRef {
+    pub fn parse(entry: &str) -> FeatureRef {
+        if let Some(dependency) = entry.strip_prefix("dep:") {
+            return FeatureRef::Dependency(dependency.to_string());
+        }
+        match entry.split_once('/') {
+            Some((dependency, feature)) => {
+                let (dependency, weak) = match dependency.strip_suffix('?') {
+                    Some(dependency) => (dependency, true),
+                    None => (dependency, false),
+                };
+                FeatureRef::DependencyFeature {
+                    dependency: dependency.to_string(),
+                    feature: feature.to_string(),
+                    weak,
+                }
+            }
+            None => FeatureRef::Feature(entry.to_string()),
+        }
+    }
+}
+
+impl Feature {
+    /// The entries of the feature, parsed, along with the strings they were parsed from
+    pub fn references(&self) -> impl Iterator<Item = (FeatureRef, &Spanned<String>)> {
+        self.enables
+            .iter()
+            .map(|entry| (FeatureRef::parse(entry), entry))
+    }
+}
+
+/// Everything that turning on a feature turns on
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeatureClosure {
+    /// The features, including the one that was turned on
+    pub features: BTreeSet<String>,
+    /// The optional dependencies
+    pub dependencies: BTreeSet<String>,
+    /// The features of dependencies, by dependency. Weak ones are only included if their
+    /// dependency is turned on.
+    pub dependency_features: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// A mistake in the `[features]` table, or something in it that is likely one
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeatureIssue {
+    /// A `feature` refers to something that is neither a feature nor a dependency
+    Undefined {
+        feature: Spanned<String>,
+        reference: Spanned<String>,
+    },
+    /// A `feature` uses `dep:name` or `name?/feature` for a dependency that is not optional
+    NotOptional {
+        feature: Spanned<String>,
+        reference: Spanned<String>,
+    },
+    /// Features that turn each other on, in the order they do
+    Cycle { features: Vec<Spanned<String>> },
+    /// An optional dependency that no feature turns on, so it can only be turned on by the
+    /// feature cargo defines for it
+    UnusedOptional { dependency: Spanned<String> },
+}
+
+impl FeatureIssue {
+    /// Where the issue is in the manifest
+    pub fn span(&self) -> Span {
+        match self {
+            FeatureIssue::Undefined { reference, .. }
+            | FeatureIssue::NotOptional { reference, .. } => reference.span(),
+            FeatureIssue::Cycle { features } => features[0].spans.key.unwrap_or(features[0].span()),
+            FeatureIssue::UnusedOptional { dependency } => {
+                dependency.spans.key.unwrap_or(dependency.span())
+            }
+        }
+    }
+}
+
+impl Display for FeatureIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FeatureIssue::Undefined { feature, reference } => write!(
+                f,
+                "feature `{}` turns on `{}`, which is neither a feature nor a dependency",
+                feature.as_str(),
+                reference.as_str()
+            ),
+            FeatureIssue::NotOptional { feature, reference } => write!(
+                f,
+                "feature `{}` uses `{}`, but the dependency is not optional",
+                feature.as_str(),
+                reference.as_str()
+            ),
+            FeatureIssue::Cycle { features } => {
+                f.write_str("features turn each other on: ")?;
+                for feature in features {
+                    write!(f, "`{}` -> ", feature.as_str())?;
+                }
+                write!(f, "`{}`", features[0].as_str())
+            }
+            FeatureIssue::UnusedOptional { dependency } => write!(
+                f,
+                "optional dependency `{}` is not turned on by any feature",
+                dependency.as_str()
+            ),
+        }
+    }
+}
+
+/// The features of a manifest and what they turn on. Dependencies are the ones of
+/// `[dependencies]`, `[build-dependencies]` and of the `[target.<cfg>]` tables, as
+/// dev-dependencies cannot be optional.
+pub struct FeatureGraph<'m> {
+    features: &'m [Feature],
+    /// The dependencies by name, with whether any definition of them is optional
+    dependencies: BTreeMap<&'m str, (&'m Dependency, bool)>,
+    /// The optional dependencies that have a feature of their own, the ones no feature
+    /// refers to with `dep:name`
+    implicit: BTreeSet<&'m str>,
+}
+
+impl<'m> FeatureGraph<'m> {
+    pub fn new(manifest: &'m CargoManifest) -> Self {
+        let mut dependencies: BTreeMap<&str, (&Dependency, bool)> = BTreeMap::new();
+        let targets = manifest
+            .target
+            .iter()
+            .flat_map(|target| target.dependencies.iter().chain(&target.build_dependencies));
+        for dependency in manifest
+            .dependencies
+            .iter()
+            .chain(&manifest.build_dependencies)
+            .chain(targets)
+        {
+            dependencies
+                .entry(dependency.name.as_str())
+                .or_insert((dependency, false))
+                .1 |= dependency.is_optional();
+        }
+
+        let mut explicit = BTreeSet::new();
+        for feature in &manifest.features {
+            for (reference, _) in feature.references() {
+                if let FeatureRef::Dependency(name) = reference {
+                    explicit.insert(name);
+                }
+            }
+        }
+        let implicit = dependencies
+            .iter()
+            .filter(|(name, (_, optional))| *optional && !explicit.contains(**name))
+            .map(|(name, _)| *name)
+            .collect();
+
+        FeatureGraph {
+            features: &manifest.features,
+            dependencies,
+            implicit,
+        }
+    }
+
+    fn feature(&self, name: &str) -> Option<&'m Feature> {
+        self.features.iter().find(|feature| feature.name.as_str() == name)
+    }
+
+    fn is_optional(&self, name: &str) -> bool {
+        self.dependencies.get(name).is_some_and(|(_, optional)| *optional)
+    }
+
+    /// Everything turning on `feature` turns on, or `None` if there is no such feature. The
+    /// feature cargo defines for an optional dependency counts as one.
+    pub fn closure(&self, feature: &str) -> Option<FeatureClosure> {
+        if self.feature(feature).is_none() && !self.implicit.contains(feature) {
+            return None;
+        }
+        let mut closure = FeatureClosure::default();
+        let mut weak = vec![];
+        let mut pending = vec![feature.to_string()];
+        while let Some(name) = pending.pop() {
+            let feature = self.feature(&name);
+            // Leaves out what is not defined, which `issues` reports
+            if feature.is_none() && !self.implicit.contains(name.as_str()) {
+                continue;
+            }
+            if !closure.features.insert(name.clone()) {
+                continue;
+            }
+            let feature = match feature {
+                Some(feature) => feature,
+                None => {
+                    // The feature of an optional dependency
+                    closure.dependencies.insert(name);
+                    continue;
+                }
+            };
+            for (reference, _) in feature.references() {
+                match reference {
+                    FeatureRef::Feature(name) => pending.push(name),
+                    FeatureRef::Dependency(name) => {
+                        closure.dependencies.insert(name);
+                    }
+                    FeatureRef::DependencyFeature {
+                        dependency,
+                        feature,
+                        weak: true,
+                    } => weak.push((dependency, feature)),
+                    FeatureRef::DependencyFeature {
+                        dependency,
+                        feature,
+                        weak: false,
+                    } => {
+                        if self.is_optional(&dependency) {
+                            closure.dependencies.insert(dependency.clone());
+                        }
+                        if self.enables_feature_of(&dependency) {
+                            pending.push(dependency.clone());
+                        }
+                        closure
+                            .dependency_features
+                            .entry(dependency)
+                            .or_default()
+                            .insert(feature);
+                    }
+                }
+            }
+        }
+        for (dependency, feature) in weak {
+            if !self.is_optional(&dependency) || closure.dependencies.contains(&dependency) {
+                closure
+                    .dependency_features
+                    .entry(dependency)
+                    .or_default()
+                    .insert(feature);
+            }
+        }
+        Some(closure)
+    }
+
+    /// Finds references to features or dependencies that do not exist and features that turn
+    /// each other on, in the order of the features, and then the optional dependencies no
+    /// feature turns on, by name
+    pub fn issues(&self) -> Vec<FeatureIssue> {
+        let mut issues = vec![];
+        let mut used = BTreeSet::new();
+        for feature in self.features {
+            for (reference, entry) in feature.references() {
+                let issue = |undefined: bool| {
+                    let (feature, reference) = (feature.name.clone(), entry.clone());
+                    if undefined {
+                        FeatureIssue::Undefined { feature, reference }
+                    } else {
+                        FeatureIssue::NotOptional { feature, reference }
+                    }
+                };
+                let dependency = match &reference {
+                    FeatureRef::Feature(name) => {
+                        if self.feature(name).is_none() && !self.implicit.contains(name.as_str()) {
+                            issues.push(issue(true));
+                        }
+                        used.insert(name.clone());
+                        continue;
+                    }
+                    FeatureRef::Dependency(name) => name,
+                    FeatureRef::DependencyFeature { dependency, .. } => dependency,
+                };
+                let must_be_optional = match &reference {
+                    FeatureRef::DependencyFeature { weak, .. } => *weak,
+                    _ => true,
+                };
+                if !self.dependencies.contains_key(dependency.as_str()) {
+                    issues.push(issue(true));
+                } else if must_be_optional && !self.is_optional(dependency) {
+                    issues.push(issue(false));
+                }
+                if !matches!(reference, FeatureRef::DependencyFeature { weak: true, .. }) {
+                    used.insert(dependency.clone());
+                }
+            }
+        }
+        self.cycles(&mut issues);
+        for (name, (dependency, optional)) in &self.dependencies {
+            if *optional && !used.contains(*name) {
+                issues.push(FeatureIssue::UnusedOptional {
+                    dependency: dependency.name.clone(),
+                });
+            }
+        }
+        issues
+    }
+
+    /// Whether `dependency/feature` turns on the feature named after the dependency too. cargo
+    /// only does that for an optional dependency no feature refers to with `dep:name`.
+    fn enables_feature_of(&self, dependency: &str) -> bool {
+        self.implicit.contains(dependency)
+    }
+
+    /// The features a feature turns on, leaving out dependencies
+    fn edges(&self, feature: &Feature) -> Vec<usize> {
+        feature
+            .references()
+            .filter_map(|(reference, _)| match reference {
+                FeatureRef::Feature(name) => Some(name),
+                FeatureRef::DependencyFeature {
+                    dependency,
+                    weak: false,
+                    ..
+                } if self.enables_feature_of(&dependency) => Some(dependency),
+                _ => None,
+            })
+            .filter_map(|name| {
+                self.features
+                    .iter()
+                    .position(|feature| feature.name.as_str() == name)
+            })
+            .collect()
+    }
+
+    /// Reports each cycle once, found by a depth first search that keeps the features it is
+    /// in on a stack
+    fn cycles(&self, issues: &mut Vec<FeatureIssue>) {
+        #[derive(Clone, Copy, PartialEq)]
+        enum State {
+            New,
+            OnStack,
+            Done,
+        }
+
+        fn visit(
+            graph: &FeatureGraph<'_>,
+            i: usize,
+            states: &mut [State],
+            stack: &mut Vec<usize>,
+            issues: &mut Vec<FeatureIssue>,
+        ) {
+            states[i] = State::OnStack;
+            stack.push(i);
+            for next in graph.edges(&graph.features[i]) {
+                match states[next] {
+                    State::New => visit(graph, next, states, stack, issues),
+                    State::OnStack => {
+                        let start = stack.iter().position(|&j| j == next).unwrap();
+                        issues.push(FeatureIssue::Cycle {
+                            features: stack[start..]
+                                .iter()
+                                .map(|&j| graph.features[j].name.clone())
+                                .collect(),
+                        });
+                    }
+                    State::Done => {}
+                }
+            }
+            stack.pop();
+            states[i] = State::Done;
+        }
+
+        let mut states = vec![State::New; self.features.len()];
+        for i in 0..self.features.len() {
+            if states[i] == State::New {
+                visit(self, i, &mut states, &mut vec![], issues);
+            }
+        }
+    }
+}
+
+impl CargoManifest {
+    /// The features of the manifest, to find out what they turn on
+    pub fn feature_graph(&self) -> FeatureGraph<'_> {
+        FeatureGraph::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests_features {
+    use std::fs::read_to_string;
+
+    use crate::parse_manifest;
+
+    use super::*;
+
+    fn set(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_references() {
+        assert_eq!(FeatureRef::parse("std"), FeatureRef::Feature("std".to_string()));
+        assert_eq!(FeatureRef::parse("dep:serde"), FeatureRef::Dependency("serde".to_string()));
+        assert_eq!(
+            FeatureRef::parse("serde?/derive"),
+            FeatureRef::DependencyFeature {
+                dependency: "serde".to_string(),
+                feature: "derive".to_string(),
+                weak: true
+            }
+        );
+    }
+
+    #[test]
+    fn test_cargo_examples() {
+        let input = read_to_string("assets/cargo_examples/pyo3.toml").unwrap();
+        let manifest = parse_manifest(&input).unwrap();
+        let graph = manifest.feature_graph();
+        let closure = graph.closure("default").unwrap();
+        assert_eq!(
+            closure.features,
+            set(&["default", "indoc", "macros", "paste", "pyo3-macros", "unindent"])
+        );
+        assert_eq!(
+            closure.dependencies,
+            set(&["indoc", "paste", "pyo3-macros", "unindent"])
+        );
+        let closure = graph.closure("abi3-py37").unwrap();
+        assert_eq!(
+            closure.dependency_features["pyo3-build-config"],
+            set(&["abi3", "abi3-py37", "abi3-py38", "abi3-py39", "abi3-py310"])
+        );
+        assert!(closure.dependencies.is_empty());
+        assert_eq!(graph.closure("serde").unwrap().dependencies, set(&["serde"]));
+        assert_eq!(graph.closure("cfg-if"), None);
+
+        let unused: Vec<String> = graph
+            .issues()
+            .iter()
+            .map(|issue| issue.to_string())
+            .collect();
+        assert_eq!(unused.len(), 7);
+        assert_eq!(
+            unused[0],
+            "optional dependency `anyhow` is not turned on by any feature"
+        );
+
+        let input = read_to_string("assets/cargo_examples/nom-locate.toml").unwrap();
+        let manifest = parse_manifest(&input).unwrap();
+        let graph = manifest.feature_graph();
+        let closure = graph.closure("default").unwrap();
+        assert_eq!(closure.features, set(&["alloc", "default", "std"]));
+        assert_eq!(closure.dependency_features["nom"], set(&["alloc", "std"]));
+        assert!(graph.issues().is_empty());
+    }
+
+    #[test]
+    fn test_dependency_syntax() {
+        let input = r#"
+[dependencies]
+serde = { version = "1", optional = true }
+rgb = { version = "0.8", optional = true }
+log = "0.4"
+
+[features]
+serde = ["dep:serde", "rgb?/serde"]
+color = ["dep:rgb"]
+all = ["serde", "color", "log/std"]
+"#;
+        let manifest = parse_manifest(input).unwrap();
+        let graph = manifest.feature_graph();
+
+        let serde = graph.closure("serde").unwrap();
+        assert_eq!(serde.dependencies, set(&["serde"]));
+        assert!(serde.dependency_features.is_empty());
+
+        let all = graph.closure("all").unwrap();
+        assert_eq!(all.features, set(&["all", "color", "serde"]));
+        assert_eq!(all.dependencies, set(&["rgb", "serde"]));
+        assert_eq!(all.dependency_features["rgb"], set(&["serde"]));
+        assert_eq!(all.dependency_features["log"], set(&["std"]));
+
+        // `dep:rgb` takes away the feature cargo would define for it
+        assert_eq!(graph.closure("rgb"), None);
+        assert!(graph.issues().is_empty());
+    }
+
+    #[test]
+    fn test_issues() {
+        let input = r#"
+[dependencies]
+log = "0.4"
+serde = { version = "1", optional = true }
+unused = { version = "1", optional = true }
+
+[features]
+a = ["b", "missing"]
+b = ["c", "dep:log"]
+c = ["a", "serde/derive", "other/std", "log?/std"]
+"#;
+        let manifest = parse_manifest(input).unwrap();
+        let graph = manifest.feature_graph();
+        let issues: Vec<String> = graph.issues().iter().map(|issue| issue.to_string()).collect();
+        assert_eq!(
+            issues,
+            vec![
+                "feature `a` turns on `missing`, which is neither a feature nor a dependency",
+                "feature `b` uses `dep:log`, but the dependency is not optional",
+                "feature `c` turns on `other/std`, which is neither a feature nor a dependency",
+                "feature `c` uses `log?/std`, but the dependency is not optional",
+                "features turn each other on: `a` -> `b` -> `c` -> `a`",
+                "optional dependency `unused` is not turned on by any feature",
+            ]
+        );
+        assert_eq!(graph.issues()[0].span().line(), 8);
+        assert_eq!(graph.issues()[0].span().column(), 11);
+
+        let closure = graph.closure("b").unwrap();
+        assert_eq!(closure.features, set(&["a", "b", "c", "serde"]));
+        assert_eq!(closure.dependencies, set(&["log", "serde"]));
+        assert_eq!(closure.dependency_features["log"], set(&["std"]));
+    }
+
+    #[test]
+    fn test_dependency_features_without_a_feature_of_their_own() {
+        let input = r#"
+[dependencies]
+serde = { version = "1", optional = true }
+log = "0.4"
+
+[features]
+serde = ["dep:serde", "serde/std"]
+log = ["log/std"]
+"#;
+        let manifest = parse_manifest(input).unwrap();
+        let graph = manifest.feature_graph();
+        assert!(graph.issues().is_empty());
+
+        let serde = graph.closure("serde").unwrap();
+        assert_eq!(serde.features, set(&["serde"]));
+        assert_eq!(serde.dependencies, set(&["serde"]));
+        assert_eq!(serde.dependency_features["serde"], set(&["std"]));
+        let log = graph.closure("log").unwrap();
+        assert_eq!(log.features, set(&["log"]));
+        assert_eq!(log.dependency_features["log"], set(&["std"]));
+    }
+}
@@ -0,0 +1,388 @@
+use crate::error::Error;
+use crate::manifest::{
+    key, CargoManifest, Dependencies, Dependency, Reader, Source, Spanned, Strings,
+};
+use crate::parsers::{DisplayPath, PathSegment};
+use crate::span::{Span, Spans};
+use crate::value::TomlValue;
+
+/// A key of `[package]` a member of a workspace can take from the workspace root instead of
+/// defining it, `version.workspace = true`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inheritable<T> {
+    Value(T),
+    /// Taken from `[workspace.package]`. Points at the `workspace` key.
+    Workspace(Span),
+}
+
+impl<T> Inheritable<T> {
+    /// The value, unless it is still to be taken from the workspace
+    pub fn value(&self) -> Option<&T> {
+        match self {
+            Inheritable::Value(value) => Some(value),
+            Inheritable::Workspace(_) => None,
+        }
+    }
+
+    pub fn is_inherited(&self) -> bool {
+        matches!(self, Inheritable::Workspace(_))
+    }
+}
+
+/// The `[workspace.package]` table, with the keys of `[package]` members can inherit
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspacePackage {
+    pub version: Option<Spanned<String>>,
+    pub authors: Option<Strings>,
+    pub edition: Option<Spanned<String>>,
+    pub rust_version: Option<Spanned<String>>,
+    pub description: Option<Spanned<String>>,
+    pub documentation: Option<Spanned<String>>,
+    pub readme: Option<Spanned<TomlValue>>,
+    pub homepage: Option<Spanned<String>>,
+    pub repository: Option<Spanned<String>>,
+    pub license: Option<Spanned<String>>,
+    pub license_file: Option<Spanned<String>>,
+    pub keywords: Option<Strings>,
+    pub categories: Option<Strings>,
+    pub exclude: Option<Strings>,
+    pub include: Option<Strings>,
+    pub publish: Option<Spanned<TomlValue>>,
+    pub spans: Spans,
+}
+
+impl Reader<'_> {
+    /// Reads the value at `path` with `read`, unless it is `{ workspace = true }`
+    pub(super) fn inheritable<T>(
+        &self,
+        path: &[PathSegment],
+        read: fn(&Self, &[PathSegment]) -> Result<Option<T>, Error>,
+    ) -> Result<Option<Inheritable<T>>, Error> {
+        if let Some(TomlValue::Table(_)) = self.document.root().lookup(path) {
+            let workspace = key(path, "workspace");
+            match self.boolean(&workspace)? {
+                Some(inherited) if inherited.value => {
+                    return Ok(Some(Inheritable::Workspace(inherited.span())))
+                }
+                Some(inherited) => {
+                    return Err(Error::invalid_value(
+                        DisplayPath(&workspace).to_string(),
+                        "`workspace` can only be `true`".to_string(),
+                        inherited.span(),
+                    ))
+                }
+                // Reading it fails and tells which type the value should have
+                None => {}
+            }
+        }
+        Ok(read(self, path)?.map(Inheritable::Value))
+    }
+
+    pub(super) fn workspace_package(
+        &self,
+        path: &[PathSegment],
+    ) -> Result<WorkspacePackage, Error> {
+        Ok(WorkspacePackage {
+            version: self.string(&key(path, "version"))?,
+            authors: self.strings(&key(path, "authors"))?,
+            edition: self.string(&key(path, "edition"))?,
+            rust_version: self.string(&key(path, "rust-version"))?,
+            description: self.string(&key(path, "description"))?,
+            documentation: self.string(&key(path, "documentation"))?,
+            readme: self.value(&key(path, "readme"))?,
+            homepage: self.string(&key(path, "homepage"))?,
+            repository: self.string(&key(path, "repository"))?,
+            license: self.string(&key(path, "license"))?,
+            license_file: self.string(&key(path, "license-file"))?,
+            keywords: self.strings(&key(path, "keywords"))?,
+            categories: self.strings(&key(path, "categories"))?,
+            exclude: self.strings(&key(path, "exclude"))?,
+            include: self.strings(&key(path, "include"))?,
+            publish: self.value(&key(path, "publish"))?,
+            spans: self.spans(path),
+        })
+    }
+}
+
+/// Replaces `field` with the value of `[workspace.package]` if it is inherited
+fn inherit<T: Clone>(
+    field: &mut Option<Inheritable<T>>,
+    inherited: Option<&T>,
+    name: &str,
+) -> Result<(), Error> {
+    if let Some(Inheritable::Workspace(span)) = field {
+        let value = inherited.ok_or_else(|| {
+            let path = [
+                PathSegment::Key("workspace".to_string()),
+                PathSegment::Key("package".to_string()),
+                PathSegment::Key(name.to_string()),
+            ];
+            Error::missing_inherited_key(DisplayPath(&path).to_string(), *span)
+        })?;
+        *field = Some(Inheritable::Value(value.clone()));
+    }
+    Ok(())
+}
+
+/// Merges the dependencies that are `workspace = true` with the ones of
+/// `[workspace.dependencies]` they are named after, the way cargo does
+fn inherit_dependencies(
+    dependencies: &mut Dependencies,
+    workspace: &[Dependency],
+) -> Result<(), Error> {
+    for dependency in dependencies {
+        let span = match dependency.source {
+            Source::Workspace(span) => span,
+            _ => continue,
+        };
+        let inherited = workspace
+            .iter()
+            .find(|inherited| inherited.name.value == dependency.name.value)
+            .ok_or_else(|| {
+                let path = [
+                    PathSegment::Key("workspace".to_string()),
+                    PathSegment::Key("dependencies".to_string()),
+                    PathSegment::Key(dependency.name.value.clone()),
+                ];
+                Error::missing_inherited_key(DisplayPath(&path).to_string(), span)
+            })?;
+
+        // The member can only add features to the ones of the workspace
+        let mut features = inherited.features.clone();
+        if let Some(added) = &dependency.features {
+            match &mut features {
+                Some(features) => {
+                    let added: Vec<_> = added
+                        .iter()
+                        .filter(|feature| !features.iter().any(|f| f.value == feature.value))
+                        .cloned()
+                        .collect();
+                    features.value.extend(added);
+                }
+                None => features = Some(added.clone()),
+            }
+        }
+        // Default features that are on for the workspace cannot be turned off by a member,
+        // but the ones that are off can be turned on
+        let default_features = match (&dependency.default_features, &inherited.default_features) {
+            (Some(member), Some(workspace)) if member.value && !workspace.value => {
+                Some(member.clone())
+            }
+            _ => inherited.default_features.clone(),
+        };
+
+        *dependency = Dependency {
+            name: dependency.name.clone(),
+            package: inherited.package.clone(),
+            version: inherited.version.clone(),
+            source: inherited.source.clone(),
+            features,
+            default_features,
+            optional: dependency.optional.clone(),
+            style: dependency.style,
+            spans: dependency.spans,
+        };
+    }
+    Ok(())
+}
+
+impl CargoManifest {
+    /// Takes the keys of `[package]` and the dependencies a member of a workspace inherits,
+    /// the ones that are `workspace = true`, from the manifest at the `root` of the workspace.
+    /// Features of inherited dependencies are added to the ones of the workspace, and
+    /// `optional` is taken from the member.
+    ///
+    /// The values taken from the root keep their spans, which point into the root manifest.
+    /// Paths like `readme` or `license-file` are left relative to the root. Fails if the root
+    /// does not define a key that is inherited.
+    pub fn resolve(&self, root: &CargoManifest) -> Result<CargoManifest, Error> {
+        let workspace = root.workspace.as_ref();
+        let mut manifest = self.clone();
+
+        if let Some(package) = &mut manifest.package {
+            let inherited = workspace.and_then(|workspace| workspace.package.as_ref());
+            macro_rules! inherit {
+                ($($field:ident: $name:literal),*) => {
+                    $(inherit(
+                        &mut package.$field,
+                        inherited.and_then(|inherited| inherited.$field.as_ref()),
+                        $name,
+                    )?;)*
+                };
+            }
+            inherit!(
+                version: "version",
+                authors: "authors",
+                edition: "edition",
+                rust_version: "rust-version",
+                description: "description",
+                documentation: "documentation",
+                readme: "readme",
+                homepage: "homepage",
+                repository: "repository",
+                license: "license",
+                license_file: "license-file",
+                keywords: "keywords",
+                categories: "categories",
+                exclude: "exclude",
+                include: "include",
+                publish: "publish"
+            );
+        }
+
+        let inherited: &[Dependency] = match workspace {
+            Some(workspace) => &workspace.dependencies,
+            None => &[],
+        };
+        inherit_dependencies(&mut manifest.dependencies, inherited)?;
+        inherit_dependencies(&mut manifest.dev_dependencies, inherited)?;
+        inherit_dependencies(&mut manifest.build_dependencies, inherited)?;
+        for target in &mut manifest.target {
+            inherit_dependencies(&mut target.dependencies, inherited)?;
+            inherit_dependencies(&mut target.dev_dependencies, inherited)?;
+            inherit_dependencies(&mut target.build_dependencies, inherited)?;
+        }
+        Ok(manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests_inherit {
+    use crate::{parse_manifest, ErrorKind};
+
+    use super::*;
+
+    const ROOT: &str = r#"
+[workspace]
+members = ["member"]
+
+[workspace.package]
+version = "1.2.0"
+edition = "2021"
+authors = ["Ferris"]
+
+[workspace.dependencies]
+serde = { version = "1", features = ["derive"], default-features = false }
+json = { package = "serde_json", version = "1" }
+nom = "7"
+"#;
+
+    fn resolve(member: &str) -> Result<CargoManifest, Error> {
+        parse_manifest(member)
+            .unwrap()
+            .resolve(&parse_manifest(ROOT).unwrap())
+    }
+
+    #[test]
+    fn test_inherited_package() {
+        let member = r#"
+[package]
+name = "member"
+version.workspace = true
+edition = { workspace = true }
+authors.workspace = true
+description = "a member"
+"#;
+        let manifest = parse_manifest(member).unwrap();
+        let package = manifest.package.as_ref().unwrap();
+        assert!(package.version.as_ref().unwrap().is_inherited());
+        assert_eq!(package.version.as_ref().unwrap().value(), None);
+
+        let package = resolve(member).unwrap().package.unwrap();
+        let version = package.version.unwrap();
+        assert_eq!(version.value().unwrap().as_str(), "1.2.0");
+        assert_eq!(version.value().unwrap().span().line(), 6);
+        assert_eq!(package.edition.unwrap().value().unwrap().as_str(), "2021");
+        assert_eq!(
+            package.authors.unwrap().value().unwrap()[0].as_str(),
+            "Ferris"
+        );
+        assert_eq!(package.description.unwrap().value().unwrap().as_str(), "a member");
+    }
+
+    #[test]
+    fn test_inherited_dependencies() {
+        let member = r#"
+[dependencies]
+serde = { workspace = true, features = ["rc", "derive"], optional = true }
+json = { workspace = true, default-features = false }
+regex = "1"
+
+[target.'cfg(unix)'.dev-dependencies]
+nom.workspace = true
+"#;
+        let manifest = resolve(member).unwrap();
+        let serde = &manifest.dependencies[0];
+        assert_eq!(serde.version.as_ref().unwrap().as_str(), "1");
+        assert_eq!(serde.source, Source::Registry(None));
+        let features: Vec<&str> = serde
+            .features
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|feature| feature.as_str())
+            .collect();
+        assert_eq!(features, vec!["derive", "rc"]);
+        assert!(serde.is_optional());
+        assert!(!serde.uses_default_features());
+
+        let json = &manifest.dependencies[1];
+        assert_eq!(json.package_name(), "serde_json");
+        assert!(json.uses_default_features());
+        assert!(!json.is_optional());
+
+        assert_eq!(manifest.dependencies[2].version.as_ref().unwrap().as_str(), "1");
+        let nom = &manifest.target[0].dev_dependencies[0];
+        assert_eq!(nom.version.as_ref().unwrap().as_str(), "7");
+        assert!(!nom.is_inherited());
+    }
+
+    #[test]
+    fn test_default_features_turned_on() {
+        let member = "[dependencies]\nserde = { workspace = true, default-features = true }\n";
+        let serde = &resolve(member).unwrap().dependencies[0];
+        assert!(serde.uses_default_features());
+    }
+
+    #[test]
+    fn test_missing_inherited_keys() {
+        let error = resolve("[package]\nname = \"member\"\nlicense.workspace = true\n")
+            .unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::MissingKey {
+                path: "workspace.package.license".to_string()
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "missing key `workspace.package.license` in the workspace root at line 3, column 21"
+        );
+
+        let error = resolve("[dependencies]\nlog = { workspace = true }\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "missing key `workspace.dependencies.log` in the workspace root at line 2, column 21"
+        );
+
+        let member = parse_manifest("[package]\nname = \"x\"\nversion.workspace = true\n").unwrap();
+        assert!(member.resolve(&member).is_err());
+    }
+
+    #[test]
+    fn test_invalid_inheritance() {
+        let error = parse_manifest("[package]\nname = \"x\"\nversion.workspace = false\n")
+            .unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::InvalidValue {
+                path: "package.version.workspace".to_string()
+            }
+        );
+        let error = parse_manifest("[package]\nname = \"x\"\nversion.other = true\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected string, found table for `package.version` at line 3, column 1"
+        );
+    }
+}
@@ -0,0 +1,636 @@
+use std::ops::Deref;
+
+pub use dependency::{Dependency, DependencyStyle, GitReference, Source};
+pub use edit::{DependencyKind, DependencyTable, NewDependency};
+pub use features::{FeatureClosure, FeatureGraph, FeatureIssue, FeatureRef};
+pub use inherit::{Inheritable, WorkspacePackage};
+
+use crate::document::Document;
+use crate::error::Error;
+use crate::parsers::{DisplayPath, PathSegment};
+use crate::span::{Span, Spans};
+use crate::value::{Table, TomlValue};
+
+mod dependency;
+mod edit;
+mod features;
+mod inherit;
+mod sort;
+
+/// A value read from a manifest, along with where it was defined, so tools built on the
+/// manifest can point at it when they report a problem
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub spans: Spans,
+}
+
+impl<T> Spanned<T> {
+    /// The span of the value, as opposed to the one of its key
+    pub fn span(&self) -> Span {
+        self.spans.value
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+/// Several strings, like the authors or the keywords of a package
+pub type Strings = Spanned<Vec<Spanned<String>>>;
+
+/// The dependencies of a table like `[dependencies]`, in the order they were defined
+pub type Dependencies = Vec<Dependency>;
+
+/// A `Cargo.toml` file. Every field keeps the spans it was defined at.
+///
+/// Only what cargo itself reads is typed. Values whose shape varies a lot, like the
+/// `[package.metadata]` of other tools, are kept as [`TomlValue`]s.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CargoManifest {
+    pub package: Option<Package>,
+    pub lib: Option<Target>,
+    pub bin: Vec<Target>,
+    pub example: Vec<Target>,
+    pub test: Vec<Target>,
+    pub bench: Vec<Target>,
+    pub dependencies: Dependencies,
+    pub dev_dependencies: Dependencies,
+    pub build_dependencies: Dependencies,
+    pub features: Vec<Feature>,
+    pub profile: Vec<Profile>,
+    pub workspace: Option<Workspace>,
+    /// The dependencies each `[patch.<registry>]` table overrides
+    pub patch: Vec<(Spanned<String>, Dependencies)>,
+    /// The `[target.<cfg>]` tables
+    pub target: Vec<PlatformTarget>,
+}
+
+/// The `[package]` table. The keys a member of a workspace can inherit are
+/// [`Inheritable`], see [`CargoManifest::resolve`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Package {
+    pub name: Spanned<String>,
+    pub version: Option<Inheritable<Spanned<String>>>,
+    pub authors: Option<Inheritable<Strings>>,
+    pub edition: Option<Inheritable<Spanned<String>>>,
+    pub rust_version: Option<Inheritable<Spanned<String>>>,
+    pub description: Option<Inheritable<Spanned<String>>>,
+    pub documentation: Option<Inheritable<Spanned<String>>>,
+    pub readme: Option<Inheritable<Spanned<TomlValue>>>,
+    pub homepage: Option<Inheritable<Spanned<String>>>,
+    pub repository: Option<Inheritable<Spanned<String>>>,
+    pub license: Option<Inheritable<Spanned<String>>>,
+    pub license_file: Option<Inheritable<Spanned<String>>>,
+    pub keywords: Option<Inheritable<Strings>>,
+    pub categories: Option<Inheritable<Strings>>,
+    pub workspace: Option<Spanned<String>>,
+    pub build: Option<Spanned<TomlValue>>,
+    pub links: Option<Spanned<String>>,
+    pub exclude: Option<Inheritable<Strings>>,
+    pub include: Option<Inheritable<Strings>>,
+    /// Either a boolean or the registries the package may be published to
+    pub publish: Option<Inheritable<Spanned<TomlValue>>>,
+    pub default_run: Option<Spanned<String>>,
+    pub autobins: Option<Spanned<bool>>,
+    pub autoexamples: Option<Spanned<bool>>,
+    pub autotests: Option<Spanned<bool>>,
+    pub autobenches: Option<Spanned<bool>>,
+    pub resolver: Option<Spanned<String>>,
+    pub metadata: Option<Spanned<TomlValue>>,
+}
+
+/// A `[lib]` table, or an element of `[[bin]]`, `[[example]]`, `[[test]]` or `[[bench]]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    pub name: Option<Spanned<String>>,
+    pub path: Option<Spanned<String>>,
+    pub test: Option<Spanned<bool>>,
+    pub doctest: Option<Spanned<bool>>,
+    pub bench: Option<Spanned<bool>>,
+    pub doc: Option<Spanned<bool>>,
+    pub harness: Option<Spanned<bool>>,
+    pub edition: Option<Spanned<String>>,
+    pub crate_type: Option<Strings>,
+    pub required_features: Option<Strings>,
+    /// Where the whole table was defined
+    pub spans: Spans,
+}
+
+/// A feature and the features and dependencies it enables, `macros = ["indoc", "paste"]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Feature {
+    pub name: Spanned<String>,
+    pub enables: Strings,
+}
+
+/// A `[profile.<name>]` table
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub name: Spanned<String>,
+    pub inherits: Option<Spanned<String>>,
+    /// An integer, or `"s"` or `"z"` to optimize for size
+    pub opt_level: Option<Spanned<TomlValue>>,
+    /// A boolean, an integer or a name like `"line-tables-only"`
+    pub debug: Option<Spanned<TomlValue>>,
+    pub debug_assertions: Option<Spanned<bool>>,
+    pub overflow_checks: Option<Spanned<bool>>,
+    /// A boolean or `"thin"`, `"fat"` or `"off"`
+    pub lto: Option<Spanned<TomlValue>>,
+    pub panic: Option<Spanned<String>>,
+    pub incremental: Option<Spanned<bool>>,
+    pub codegen_units: Option<Spanned<u32>>,
+    pub strip: Option<Spanned<TomlValue>>,
+}
+
+/// The `[workspace]` table
+#[derive(Debug, Clone, PartialEq)]
+pub struct Workspace {
+    pub members: Option<Strings>,
+    pub default_members: Option<Strings>,
+    pub exclude: Option<Strings>,
+    pub resolver: Option<Spanned<String>>,
+    /// The keys of `[package]` that members can inherit
+    pub package: Option<WorkspacePackage>,
+    /// The dependencies that members can inherit
+    pub dependencies: Dependencies,
+    pub metadata: Option<Spanned<TomlValue>>,
+    pub spans: Spans,
+}
+
+/// A `[target.<cfg>]` table, with dependencies that are only used on some platforms
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlatformTarget {
+    /// A target triple or a `cfg(...)` expression
+    pub cfg: Spanned<String>,
+    pub dependencies: Dependencies,
+    pub dev_dependencies: Dependencies,
+    pub build_dependencies: Dependencies,
+}
+
+fn key(path: &[PathSegment], key: &str) -> Vec<PathSegment> {
+    let mut path = path.to_vec();
+    path.push(PathSegment::Key(key.to_string()));
+    path
+}
+
+fn index(path: &[PathSegment], index: usize) -> Vec<PathSegment> {
+    let mut path = path.to_vec();
+    path.push(PathSegment::Index(index));
+    path
+}
+
+/// The keys of a table along with the paths of their values
+type Entries = Vec<(Spanned<String>, Vec<PathSegment>)>;
+
+/// Reads typed values out of a document by their paths, and points the errors at them
+struct Reader<'a> {
+    document: &'a Document,
+}
+
+impl<'a> Reader<'a> {
+    fn spans(&self, path: &[PathSegment]) -> Spans {
+        self.document.spans_at(path).copied().unwrap_or(Spans {
+            key: None,
+            value: Span::default(),
+            header: None,
+        })
+    }
+
+    /// Converts the value at `path`, if there is one
+    fn convert<T>(
+        &self,
+        path: &[PathSegment],
+        convert: impl FnOnce(&'a TomlValue) -> Result<T, Error>,
+    ) -> Result<Option<Spanned<T>>, Error> {
+        let value = match self.document.root().lookup(path) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let spans = self.spans(path);
+        match convert(value) {
+            Ok(value) => Ok(Some(Spanned { value, spans })),
+            Err(e) => Err(e.at(&DisplayPath(path).to_string(), spans.value)),
+        }
+    }
+
+    fn string(&self, path: &[PathSegment]) -> Result<Option<Spanned<String>>, Error> {
+        self.convert(path, String::try_from)
+    }
+
+    fn required_string(
+        &self,
+        table: &[PathSegment],
+        name: &str,
+    ) -> Result<Spanned<String>, Error> {
+        let path = key(table, name);
+        self.string(&path)?.ok_or_else(|| {
+            Error::missing_key(DisplayPath(&path).to_string(), self.spans(table).value)
+        })
+    }
+
+    fn boolean(&self, path: &[PathSegment]) -> Result<Option<Spanned<bool>>, Error> {
+        self.convert(path, bool::try_from)
+    }
+
+    fn value(&self, path: &[PathSegment]) -> Result<Option<Spanned<TomlValue>>, Error> {
+        self.convert(path, |value| Ok(value.clone()))
+    }
+
+    fn strings(&self, path: &[PathSegment]) -> Result<Option<Strings>, Error> {
+        let array = match self.convert(path, <&[TomlValue]>::try_from)? {
+            Some(array) => array,
+            None => return Ok(None),
+        };
+        let mut strings = Vec::with_capacity(array.len());
+        for i in 0..array.len() {
+            strings.extend(self.string(&index(path, i))?);
+        }
+        Ok(Some(Spanned {
+            value: strings,
+            spans: array.spans,
+        }))
+    }
+
+    /// The keys of the table at `path`, with the paths of their values
+    fn entries(
+        &self,
+        path: &[PathSegment],
+    ) -> Result<Entries, Error> {
+        let table = match self.convert(path, <&Table>::try_from)? {
+            Some(table) => table,
+            None => return Ok(vec![]),
+        };
+        Ok(table
+            .keys()
+            .map(|name| {
+                let path = key(path, name);
+                let spans = self.spans(&path);
+                (
+                    Spanned {
+                        value: name.clone(),
+                        spans,
+                    },
+                    path,
+                )
+            })
+            .collect())
+    }
+
+    /// Reads the table at `path` with `read`, if there is one
+    fn table<T>(
+        &self,
+        path: &[PathSegment],
+        read: fn(&Self, &[PathSegment]) -> Result<T, Error>,
+    ) -> Result<Option<T>, Error> {
+        match self.convert(path, <&Table>::try_from)? {
+            Some(_) => read(self, path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads each table of the array of tables at `path` with `read`
+    fn tables<T>(
+        &self,
+        path: &[PathSegment],
+        read: fn(&Self, &[PathSegment]) -> Result<T, Error>,
+    ) -> Result<Vec<T>, Error> {
+        let len = match self.convert(path, <&[TomlValue]>::try_from)? {
+            Some(array) => array.len(),
+            None => return Ok(vec![]),
+        };
+        (0..len)
+            .map(|i| {
+                let path = index(path, i);
+                self.convert(&path, <&Table>::try_from)?;
+                read(self, &path)
+            })
+            .collect()
+    }
+
+    fn dependencies(&self, path: &[PathSegment]) -> Result<Dependencies, Error> {
+        self.entries(path)?
+            .into_iter()
+            .map(|(name, path)| self.dependency(name, &path))
+            .collect()
+    }
+
+    fn package(&self, path: &[PathSegment]) -> Result<Package, Error> {
+        Ok(Package {
+            name: self.required_string(path, "name")?,
+            version: self.inheritable(&key(path, "version"), Reader::string)?,
+            authors: self.inheritable(&key(path, "authors"), Reader::strings)?,
+            edition: self.inheritable(&key(path, "edition"), Reader::string)?,
+            rust_version: self.inheritable(&key(path, "rust-version"), Reader::string)?,
+            description: self.inheritable(&key(path, "description"), Reader::string)?,
+            documentation: self.inheritable(&key(path, "documentation"), Reader::string)?,
+            readme: self.inheritable(&key(path, "readme"), Reader::value)?,
+            homepage: self.inheritable(&key(path, "homepage"), Reader::string)?,
+            repository: self.inheritable(&key(path, "repository"), Reader::string)?,
+            license: self.inheritable(&key(path, "license"), Reader::string)?,
+            license_file: self.inheritable(&key(path, "license-file"), Reader::string)?,
+            keywords: self.inheritable(&key(path, "keywords"), Reader::strings)?,
+            categories: self.inheritable(&key(path, "categories"), Reader::strings)?,
+            workspace: self.string(&key(path, "workspace"))?,
+            build: self.value(&key(path, "build"))?,
+            links: self.string(&key(path, "links"))?,
+            exclude: self.inheritable(&key(path, "exclude"), Reader::strings)?,
+            include: self.inheritable(&key(path, "include"), Reader::strings)?,
+            publish: self.inheritable(&key(path, "publish"), Reader::value)?,
+            default_run: self.string(&key(path, "default-run"))?,
+            autobins: self.boolean(&key(path, "autobins"))?,
+            autoexamples: self.boolean(&key(path, "autoexamples"))?,
+            autotests: self.boolean(&key(path, "autotests"))?,
+            autobenches: self.boolean(&key(path, "autobenches"))?,
+            resolver: self.string(&key(path, "resolver"))?,
+            metadata: self.value(&key(path, "metadata"))?,
+        })
+    }
+
+    fn target(&self, path: &[PathSegment]) -> Result<Target, Error> {
+        Ok(Target {
+            name: self.string(&key(path, "name"))?,
+            path: self.string(&key(path, "path"))?,
+            test: self.boolean(&key(path, "test"))?,
+            doctest: self.boolean(&key(path, "doctest"))?,
+            bench: self.boolean(&key(path, "bench"))?,
+            doc: self.boolean(&key(path, "doc"))?,
+            harness: self.boolean(&key(path, "harness"))?,
+            edition: self.string(&key(path, "edition"))?,
+            crate_type: self.strings(&key(path, "crate-type"))?,
+            required_features: self.strings(&key(path, "required-features"))?,
+            spans: self.spans(path),
+        })
+    }
+
+    fn features(&self, path: &[PathSegment]) -> Result<Vec<Feature>, Error> {
+        self.entries(path)?
+            .into_iter()
+            .map(|(name, path)| {
+                let enables = self.strings(&path)?.expect("the entry exists");
+                Ok(Feature { name, enables })
+            })
+            .collect()
+    }
+
+    fn profiles(&self, path: &[PathSegment]) -> Result<Vec<Profile>, Error> {
+        self.entries(path)?
+            .into_iter()
+            .map(|(name, path)| {
+                self.convert(&path, <&Table>::try_from)?;
+                Ok(Profile {
+                    name,
+                    inherits: self.string(&key(&path, "inherits"))?,
+                    opt_level: self.value(&key(&path, "opt-level"))?,
+                    debug: self.value(&key(&path, "debug"))?,
+                    debug_assertions: self.boolean(&key(&path, "debug-assertions"))?,
+                    overflow_checks: self.boolean(&key(&path, "overflow-checks"))?,
+                    lto: self.value(&key(&path, "lto"))?,
+                    panic: self.string(&key(&path, "panic"))?,
+                    incremental: self.boolean(&key(&path, "incremental"))?,
+                    codegen_units: self.convert(&key(&path, "codegen-units"), u32::try_from)?,
+                    strip: self.value(&key(&path, "strip"))?,
+                })
+            })
+            .collect()
+    }
+
+    fn workspace(&self, path: &[PathSegment]) -> Result<Workspace, Error> {
+        Ok(Workspace {
+            members: self.strings(&key(path, "members"))?,
+            default_members: self.strings(&key(path, "default-members"))?,
+            exclude: self.strings(&key(path, "exclude"))?,
+            resolver: self.string(&key(path, "resolver"))?,
+            package: self.table(&key(path, "package"), Reader::workspace_package)?,
+            dependencies: self.dependencies(&key(path, "dependencies"))?,
+            metadata: self.value(&key(path, "metadata"))?,
+            spans: self.spans(path),
+        })
+    }
+
+    fn patches(&self, path: &[PathSegment]) -> Result<Vec<(Spanned<String>, Dependencies)>, Error> {
+        self.entries(path)?
+            .into_iter()
+            .map(|(registry, path)| Ok((registry, self.dependencies(&path)?)))
+            .collect()
+    }
+
+    fn platform_targets(&self, path: &[PathSegment]) -> Result<Vec<PlatformTarget>, Error> {
+        self.entries(path)?
+            .into_iter()
+            .map(|(cfg, path)| {
+                Ok(PlatformTarget {
+                    cfg,
+                    dependencies: self.dependencies(&key(&path, "dependencies"))?,
+                    dev_dependencies: self.dependencies(&key(&path, "dev-dependencies"))?,
+                    build_dependencies: self.dependencies(&key(&path, "build-dependencies"))?,
+                })
+            })
+            .collect()
+    }
+}
+
+impl CargoManifest {
+    /// Reads a manifest out of a parsed document. Fails if a key cargo knows has a value of
+    /// the wrong type, or if the package has no name. Keys cargo does not know are ignored.
+    pub fn from_document(document: &Document) -> Result<Self, Error> {
+        let reader = Reader { document };
+        let root: &[PathSegment] = &[];
+        Ok(CargoManifest {
+            package: reader.table(&key(root, "package"), Reader::package)?,
+            lib: reader.table(&key(root, "lib"), Reader::target)?,
+            bin: reader.tables(&key(root, "bin"), Reader::target)?,
+            example: reader.tables(&key(root, "example"), Reader::target)?,
+            test: reader.tables(&key(root, "test"), Reader::target)?,
+            bench: reader.tables(&key(root, "bench"), Reader::target)?,
+            dependencies: reader.dependencies(&key(root, "dependencies"))?,
+            dev_dependencies: reader.dependencies(&key(root, "dev-dependencies"))?,
+            build_dependencies: reader.dependencies(&key(root, "build-dependencies"))?,
+            features: reader.features(&key(root, "features"))?,
+            profile: reader.profiles(&key(root, "profile"))?,
+            workspace: reader.table(&key(root, "workspace"), Reader::workspace)?,
+            patch: reader.patches(&key(root, "patch"))?,
+            target: reader.platform_targets(&key(root, "target"))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests_manifest {
+    use std::fs::read_to_string;
+
+    use crate::{parse_manifest, ErrorKind};
+
+    use super::*;
+
+    fn manifest(name: &str) -> CargoManifest {
+        let input = read_to_string(format!("assets/cargo_examples/{}", name)).unwrap();
+        parse_manifest(&input).unwrap()
+    }
+
+    fn names(strings: &Strings) -> Vec<&str> {
+        strings.iter().map(|s| s.as_str()).collect()
+    }
+
+    #[test]
+    fn test_cargo_examples() {
+        for entry in std::fs::read_dir("assets/cargo_examples").unwrap() {
+            let path = entry.unwrap().path();
+            let input = read_to_string(&path).unwrap();
+            if let Err(e) = parse_manifest(&input) {
+                panic!("{}: {}", path.display(), e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_package() {
+        let pyo3 = manifest("pyo3.toml");
+        let package = pyo3.package.unwrap();
+        assert_eq!(package.name.as_str(), "pyo3");
+        assert_eq!(package.name.spans.key.unwrap().line(), 2);
+        assert_eq!(package.name.span().column(), 8);
+        assert_eq!(package.edition.unwrap().value().unwrap().as_str(), "2018");
+        assert_eq!(
+            names(package.keywords.as_ref().unwrap().value().unwrap()),
+            vec!["pyo3", "python", "cpython", "ffi"]
+        );
+        assert_eq!(package.links.unwrap().as_str(), "python");
+        assert_eq!(
+            package.metadata.unwrap()["docs"]["rs"]["no-default-features"],
+            TomlValue::Boolean(true)
+        );
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let pyo3 = manifest("pyo3.toml");
+        let names: Vec<&str> = pyo3.dependencies.iter().map(|dependency| dependency.name.as_str()).collect();
+        assert_eq!(&names[..4], &["cfg-if", "libc", "parking_lot", "pyo3-macros"]);
+        let cfg_if = &pyo3.dependencies[0];
+        assert_eq!(cfg_if.name.spans.key.unwrap().line(), 18);
+        assert_eq!(cfg_if.version.as_ref().unwrap().as_str(), "1.0");
+        assert_eq!(pyo3.dev_dependencies.len(), 7);
+        assert_eq!(pyo3.build_dependencies[0].name.as_str(), "pyo3-build-config");
+
+        let expand = manifest("cargo-expand.toml");
+        let bat = expand
+            .dependencies
+            .iter()
+            .find(|dependency| dependency.name.as_str() == "bat")
+            .unwrap();
+        assert_eq!(bat.version.as_ref().unwrap().as_str(), "0.18");
+        assert_eq!(bat.name.spans.header.unwrap().line(), 27);
+    }
+
+    #[test]
+    fn test_features_and_targets() {
+        let pyo3 = manifest("pyo3.toml");
+        let macros = pyo3
+            .features
+            .iter()
+            .find(|feature| feature.name.as_str() == "macros")
+            .unwrap();
+        assert_eq!(
+            names(&macros.enables),
+            vec!["pyo3-macros", "indoc", "paste", "unindent"]
+        );
+        assert_eq!(macros.enables[1].span().line(), 59);
+        assert_eq!(pyo3.bench.len(), 9);
+        assert_eq!(pyo3.bench[0].name.as_ref().unwrap().as_str(), "bench_call");
+        assert!(!pyo3.bench[0].harness.as_ref().unwrap().value);
+        assert_eq!(pyo3.bench[1].spans.header.unwrap().line(), 89);
+        assert_eq!(names(pyo3.workspace.unwrap().members.as_ref().unwrap()).len(), 7);
+    }
+
+    #[test]
+    fn test_sections() {
+        let input = r#"
+[package]
+name = "x"
+autotests = false
+
+[lib]
+crate-type = ["cdylib"]
+
+[profile.release]
+opt-level = "z"
+lto = true
+codegen-units = 1
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+
+[patch.crates-io]
+nom = { git = "https://github.com/Geal/nom" }
+
+[workspace]
+resolver = "2"
+
+[workspace.dependencies]
+serde = "1"
+"#;
+        let manifest = parse_manifest(input).unwrap();
+        assert!(!manifest.package.unwrap().autotests.unwrap().value);
+        assert_eq!(names(manifest.lib.unwrap().crate_type.as_ref().unwrap()), vec!["cdylib"]);
+        let release = &manifest.profile[0];
+        assert_eq!(release.name.as_str(), "release");
+        assert_eq!(release.opt_level.as_ref().unwrap().value, TomlValue::Str("z".to_string()));
+        assert_eq!(release.codegen_units.as_ref().unwrap().value, 1);
+        assert_eq!(manifest.target[0].cfg.as_str(), "cfg(unix)");
+        assert_eq!(manifest.target[0].dependencies[0].name.as_str(), "libc");
+        assert_eq!(manifest.patch[0].0.as_str(), "crates-io");
+        assert_eq!(manifest.patch[0].1[0].name.as_str(), "nom");
+        let workspace = manifest.workspace.unwrap();
+        assert_eq!(workspace.resolver.unwrap().as_str(), "2");
+        assert_eq!(workspace.dependencies[0].name.as_str(), "serde");
+    }
+
+    #[test]
+    fn test_invalid_types() {
+        let error = parse_manifest("[package]\nname = \"x\"\nedition = 2018\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected string, found integer for `package.edition` at line 3, column 11"
+        );
+        assert_eq!(error.labels()[0].message(), "expected string, found integer");
+
+        let error = parse_manifest("[package]\nname = \"x\"\nkeywords = [\"a\", 1]\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected string, found integer for `package.keywords[1]` at line 3, column 18"
+        );
+
+        let error = parse_manifest("bin = [1]\n").unwrap_err();
+        assert_eq!(error.message(), "expected table, found integer for `bin[0]`");
+
+        let error = parse_manifest("[profile.dev]\ncodegen-units = -1\n").unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::OutOfRange {
+                expected: "u32",
+                value: -1
+            }
+        );
+    }
+
+    #[test]
+    fn test_missing_name() {
+        let error = parse_manifest("[package]\nversion = \"1.0.0\"\n").unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::MissingKey {
+                path: "package.name".to_string()
+            }
+        );
+        assert_eq!(error.to_string(), "missing key `package.name` at line 1, column 1");
+    }
+}
@@ -0,0 +1,238 @@
+use crate::cst::{blank_lines, CstDocument, CstSection};
+use crate::manifest::DependencyKind;
+use crate::parsers::PathSegment;
+
+/// The tables of a manifest in the order the Cargo reference lists them
+const TABLE_ORDER: &[&str] = &[
+    "package",
+    "lib",
+    "bin",
+    "example",
+    "test",
+    "bench",
+    "dependencies",
+    "dev-dependencies",
+    "build-dependencies",
+    "target",
+    "badges",
+    "features",
+    "lints",
+    "patch",
+    "replace",
+    "profile",
+    "workspace",
+];
+
+/// Whether a table holds dependencies, like `dependencies`,
+/// `target.'cfg(unix)'.dev-dependencies` or `workspace.dependencies`
+fn is_dependency_table(path: &[PathSegment]) -> bool {
+    let is = |segment: &PathSegment, names: &[&str]| {
+        matches!(segment, PathSegment::Key(key) if names.contains(&key.as_str()))
+    };
+    let kinds = [DependencyKind::Normal, DependencyKind::Development, DependencyKind::Build]
+        .map(|kind| kind.key());
+    match path {
+        [kind] => is(kind, &kinds),
+        [target, _, kind] => is(target, &["target"]) && is(kind, &kinds),
+        [workspace, dependencies] => {
+            is(workspace, &["workspace"]) && is(dependencies, &["dependencies"])
+        }
+        _ => false,
+    }
+}
+
+/// Where a table goes in [`TABLE_ORDER`], with the tables Cargo does not know last
+fn rank(section: &CstSection) -> usize {
+    let first = section.header.as_ref().and_then(|header| header.key.segments().first());
+    first
+        .and_then(|first| TABLE_ORDER.iter().position(|table| table == first))
+        .unwrap_or(TABLE_ORDER.len())
+}
+
+impl CstDocument {
+    /// Sorts the dependencies in each dependency table with a header, like
+    /// `[dependencies]`, `[target.'cfg(unix)'.dev-dependencies]` or
+    /// `[workspace.dependencies]`, the way [`sort_keys`](CstDocument::sort_keys) does.
+    ///
+    /// Returns whether anything moved.
+    pub fn sort_dependencies(&mut self) -> bool {
+        let mut moved = false;
+        for path in self.section_paths() {
+            if is_dependency_table(&path) {
+                moved |= self.sort_keys_at(&path);
+            }
+        }
+        moved
+    }
+
+    /// Puts the tables in the order the Cargo reference lists them in: `[package]` first,
+    /// then `[lib]`, the targets, the dependencies and so on, with the tables Cargo does not
+    /// know last. Tables that go in the same place, like `[dependencies]` and
+    /// `[dependencies.nom]` or the tables of `[[bin]]`, keep their order. The comments above
+    /// a header move with it, and the empty lines stay where they are. If the file starts
+    /// with a header, the comments above it up to the first empty line are about the file
+    /// and stay at the top.
+    ///
+    /// Returns whether anything moved.
+    pub fn sort_tables(&mut self) -> bool {
+        // The root section comes first, and cannot move as it has no header
+        if self.sections[1..].is_sorted_by_key(rank) {
+            return false;
+        }
+        self.end_last_line();
+        let newline = self.line_ending();
+        let mut top = String::new();
+        if self.sections[0].entries.is_empty() {
+            let first = self.sections.get_mut(1).and_then(|section| section.header.as_mut());
+            if let Some(header) = first {
+                let start = blank_lines(&header.leading).len();
+                let mut end = start;
+                for line in header.leading[start..].split_inclusive('\n') {
+                    end += line.len();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                }
+                top = header.leading.drain(start..end).collect();
+            }
+        }
+        let blanks: Vec<String> = self.sections[1..]
+            .iter_mut()
+            .filter_map(|section| section.header.as_mut())
+            .map(|header| {
+                let blank = blank_lines(&header.leading).len();
+                header.leading.drain(..blank).collect()
+            })
+            .collect();
+        self.sections[1..].sort_by_key(rank);
+        for (section, blank) in self.sections[1..].iter_mut().zip(blanks) {
+            if let Some(header) = &mut section.header {
+                header.leading.insert_str(0, &blank);
+            }
+        }
+        let first = self.sections.get_mut(1).and_then(|section| section.header.as_mut());
+        if let Some(header) = first {
+            let start = blank_lines(&header.leading).len();
+            // The comments of the header that is first now are kept apart from the file's
+            let ends_blank = top.lines().last().is_some_and(|line| line.trim().is_empty());
+            if !top.is_empty() && !ends_blank && header.leading.len() > start {
+                top.push_str(&newline);
+            }
+            header.leading.insert_str(start, &top);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests_sort {
+    use std::fs::read_to_string;
+
+    use crate::parse_cst;
+
+    const INPUT: &str = r#"# a manifest
+[dependencies]
+serde = "1"
+# parsing
+nom = "7"
+
+[dev-dependencies]
+b = "1"
+a = "1"
+
+# the package
+[package]
+name = "x"
+
+[target.'cfg(unix)'.dependencies]
+nix = "0.26"
+libc = "0.2"
+
+[dependencies.tokio]
+version = "1"
+
+[lib]
+path = "src/lib.rs""#;
+
+    #[test]
+    fn test_sort_dependencies() {
+        let mut cst = parse_cst(INPUT).unwrap();
+        assert!(cst.sort_dependencies());
+        assert_eq!(
+            cst.to_string(),
+            INPUT
+                .replace(
+                    "serde = \"1\"\n# parsing\nnom = \"7\"",
+                    "# parsing\nnom = \"7\"\nserde = \"1\""
+                )
+                .replace("b = \"1\"\na = \"1\"", "a = \"1\"\nb = \"1\"")
+                .replace("nix = \"0.26\"\nlibc = \"0.2\"", "libc = \"0.2\"\nnix = \"0.26\"")
+        );
+        assert!(!cst.sort_dependencies());
+    }
+
+    #[test]
+    fn test_sort_tables() {
+        let mut cst = parse_cst(INPUT).unwrap();
+        assert!(cst.sort_tables());
+        assert_eq!(
+            cst.to_string(),
+            r#"# a manifest
+
+# the package
+[package]
+name = "x"
+
+[lib]
+path = "src/lib.rs"
+
+[dependencies]
+serde = "1"
+# parsing
+nom = "7"
+
+[dependencies.tokio]
+version = "1"
+
+[dev-dependencies]
+b = "1"
+a = "1"
+
+[target.'cfg(unix)'.dependencies]
+nix = "0.26"
+libc = "0.2"
+"#
+        );
+        assert!(!cst.sort_tables());
+        assert_eq!(cst.to_document().unwrap().root(), crate::parse(INPUT).unwrap().root());
+
+        let input = "# file\n\n# parsing\n[dependencies]\nnom = \"7\"\n\n[package]\nname = \"x\"\n";
+        let mut cst = parse_cst(input).unwrap();
+        assert!(cst.sort_tables());
+        assert_eq!(
+            cst.to_string(),
+            "# file\n\n[package]\nname = \"x\"\n\n# parsing\n[dependencies]\nnom = \"7\"\n"
+        );
+    }
+
+    #[test]
+    fn test_sorting_keeps_the_manifests() {
+        for entry in std::fs::read_dir("assets/cargo_examples").unwrap() {
+            let input = read_to_string(entry.unwrap().path()).unwrap();
+            let mut cst = parse_cst(&input).unwrap();
+            cst.sort_tables();
+            cst.sort_dependencies();
+            let sorted = cst.to_string();
+            let names = |input: &str| {
+                let manifest = crate::parse_manifest(input).unwrap();
+                let dependencies = manifest.dependencies.into_iter();
+                let mut names: Vec<String> =
+                    dependencies.map(|dependency| dependency.name.value).collect();
+                names.sort();
+                names
+            };
+            assert_eq!(names(&sorted), names(&input));
+            assert!(!cst.sort_tables() && !cst.sort_dependencies());
+        }
+    }
+}
@@ -1,129 +1,100 @@
-use std::fmt::{Display, Formatter};
-
 use nom::branch::alt;
-use nom::bytes::complete::tag;
-use nom::combinator::{map, value};
-use nom::error::{FromExternalError, ParseError};
+use nom::bytes::complete::take_while1;
+use nom::character::complete::char;
+use nom::combinator::{cut, map, recognize};
+use nom::error::{context, FromExternalError, ParseError};
 use nom::IResult;
-use nom::multi::separated_list1;
-use nom::sequence::{delimited, pair, separated_pair, tuple};
-
-use crate::parsers::{toml_value, TomlValue};
-use crate::parsers::key_value::key;
-use crate::parsers::whitespace::whitespace;
-
-// ToDo: Should key be a concrete type?
-// ToDo: Should arrays be a subset of key value pairs?
-// ToDo: Pretty printing
-// ToDo: Add multiline array definition support
-/// Stores any data that a TOML array should can store, including other arrays
-/// So, each item in an array is either a single value, such as a integer, or another array.
-/// `Array` stores both types. For single values, the `value` optional field holds a
-/// `TomlValue` and sets the `children` to `None`, while an array item sets `value` to `None`
-///  and stores array data in the `children` optional field.
-#[derive(Debug, PartialEq)]
-pub(crate) struct Array {
-    pub(crate) value: Option<TomlValue>,
-    pub(crate) children: Option<Vec<Array>>,
-}
+use nom::multi::{many0_count, separated_list1};
+use nom::sequence::{delimited, pair, preceded, terminated};
 
-impl Display for Array {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let mut output = String::new();
-        match &self.value {
-            Some(tv) => output.push_str(&format!("\t{}", &tv.to_string())),
-            None => {}
-        }
-        match &self.children {
-            Some(vec) => {
-                output.push_str(&format!("Array:\n"));
-                output.push_str("\t[\n");
-                for a in vec {
-                    output.push_str(&format!("\t{},\n", &a.to_string()));
-                }
-                output.push_str("\t]\n");
-            }
-            None => {}
-        }
-        f.write_str(&output)
-    }
-}
+use crate::parsers::{expect, toml_value, ExpectError, TomlValue};
+use crate::parsers::comment::comment;
+use crate::span::{spanned, NestedSpans, ValueSpan};
 
-/// Parses array items that are not arrays themselves
-fn array_toml_value<
-    'a,
-    E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
->(
+/// Arrays can span multiple lines, so whitespace, newlines and comments are all allowed
+/// between the brackets, values and commas
+pub(crate) fn array_space<'a, E: ParseError<&'a str>>(
     input: &'a str,
-) -> IResult<&'a str, Array, E> {
-    map(toml_value, |toml_val| Array {
-        value: Some(toml_val),
-        children: None,
-    })(input)
+) -> IResult<&'a str, &'a str, E> {
+    recognize(many0_count(alt((
+        take_while1(|c| " \t\r\n".contains(c)),
+        comment,
+    ))))(input)
 }
 
-// ToDo: A general way to handle whitespace
-// ToDo: Test single entry or empty arrays, with extraneous commas
 /// A recursive parser to parses the right side of a TOML array definition such as "name = [1,2,3]"
-fn array_value<'a, E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>>(
+/// Each array item is either another array or a single TOML value, and `toml_value` handles both.
+/// A trailing comma after the last value is allowed. Everything after the opening bracket
+/// has to be part of the array.
+fn array_value<
+    'a,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+>(
     input: &'a str,
-) -> IResult<&'a str, Array, E> {
-    map(
-        delimited(
-            pair(tag("["), whitespace),
-            separated_list1(
-                delimited(whitespace, tag(","), whitespace),
-                /// Each array item is either another array or a single TOML value, so test for both
-                alt((array_value, array_toml_value)),
+) -> IResult<&'a str, (Vec<TomlValue>, Vec<ValueSpan>), E> {
+    let close = pair(
+        array_space,
+        alt((
+            preceded(
+                pair(char(','), array_space),
+                expect(&["a value", "`]`"], char(']')),
             ),
-            pair(whitespace, tag("]")),
+            expect(&["`,`", "`]`"], char(']')),
+        )),
+    );
+    context(
+        "array",
+        map(
+            preceded(
+                pair(char('['), array_space),
+                cut(alt((
+                    map(char(']'), |_| vec![]),
+                    terminated(
+                        separated_list1(
+                            delimited(array_space, char(','), array_space),
+                            toml_value,
+                        ),
+                        close,
+                    ),
+                ))),
+            ),
+            |values| values.into_iter().unzip(),
         ),
-        |array_values| Array {
-            value: None,
-            children: Some(array_values),
-        },
     )(input)
 }
 
 /// Parses a TOML array definition such as "name = [1,2,3]"
 pub(crate) fn array<
     'a,
-    E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 >(
     input: &'a str,
-) -> IResult<&'a str, TomlValue, E> {
-    map(array_value, |v| TomlValue::Array(Box::new(v)))(input)
+) -> IResult<&'a str, (TomlValue, ValueSpan), E> {
+    map(spanned(array_value), |((values, spans), span)| {
+        let nested = NestedSpans::Array(spans);
+        (TomlValue::Array(values), ValueSpan { span, nested })
+    })(input)
 }
 
 #[cfg(test)]
 mod tests_array {
     use nom::error::ErrorKind;
 
+    use crate::parsers::without_spans;
+
     use super::*;
 
     #[test]
     fn test_no_whitespace_array_value() {
         assert_eq!(
-            array_value::<(&str, ErrorKind)>("[1,2,3]"),
+            array_value::<(&str, ErrorKind)>("[1,2,3]").map(without_spans),
             Ok((
                 "",
-                Array {
-                    value: None,
-                    children: Some(vec![
-                        Array {
-                            value: Some(TomlValue::Integer(1)),
-                            children: None,
-                        },
-                        Array {
-                            value: Some(TomlValue::Integer(2)),
-                            children: None,
-                        },
-                        Array {
-                            value: Some(TomlValue::Integer(3)),
-                            children: None,
-                        },
-                    ],)
-                }
+                vec![
+                    TomlValue::Integer(1),
+                    TomlValue::Integer(2),
+                    TomlValue::Integer(3),
+                ]
             ))
         );
     }
@@ -131,26 +102,14 @@ mod tests_array {
     #[test]
     fn test_whitespaced_integer_array() {
         assert_eq!(
-            array_value::<(&str, ErrorKind)>("[ 1, 2, 3 ]"),
+            array_value::<(&str, ErrorKind)>("[ 1, 2, 3 ]").map(without_spans),
             Ok((
                 "",
-                Array {
-                    value: None,
-                    children: Some(vec![
-                        Array {
-                            value: Some(TomlValue::Integer(1)),
-                            children: None,
-                        },
-                        Array {
-                            value: Some(TomlValue::Integer(2)),
-                            children: None,
-                        },
-                        Array {
-                            value: Some(TomlValue::Integer(3)),
-                            children: None,
-                        },
-                    ],)
-                }
+                vec![
+                    TomlValue::Integer(1),
+                    TomlValue::Integer(2),
+                    TomlValue::Integer(3),
+                ]
             ))
         );
     }
@@ -158,26 +117,14 @@ mod tests_array {
     #[test]
     fn test_integer_array() {
         assert_eq!(
-            array::<(&str, ErrorKind)>("[ 1, 2, 3 ]"),
+            array::<(&str, ErrorKind)>("[ 1, 2, 3 ]").map(without_spans),
             Ok((
                 "",
-                TomlValue::Array(Box::new(Array {
-                    value: None,
-                    children: Some(vec![
-                        Array {
-                            value: Some(TomlValue::Integer(1)),
-                            children: None,
-                        },
-                        Array {
-                            value: Some(TomlValue::Integer(2)),
-                            children: None,
-                        },
-                        Array {
-                            value: Some(TomlValue::Integer(3)),
-                            children: None,
-                        },
-                    ]),
-                }))
+                TomlValue::Array(vec![
+                    TomlValue::Integer(1),
+                    TomlValue::Integer(2),
+                    TomlValue::Integer(3),
+                ])
             ))
         );
     }
@@ -186,26 +133,14 @@ mod tests_array {
     fn test_float_array() {
         // Test with an extra comma at the end
         assert_eq!(
-            array::<(&str, ErrorKind)>("[ 0.1, 0.2, 0.5]"),
+            array::<(&str, ErrorKind)>("[ 0.1, 0.2, 0.5]").map(without_spans),
             Ok((
                 "",
-                TomlValue::Array(Box::new(Array {
-                    value: None,
-                    children: Some(vec![
-                        Array {
-                            value: Some(TomlValue::Float(0.1)),
-                            children: None,
-                        },
-                        Array {
-                            value: Some(TomlValue::Float(0.2)),
-                            children: None,
-                        },
-                        Array {
-                            value: Some(TomlValue::Float(0.5)),
-                            children: None,
-                        },
-                    ]),
-                }))
+                TomlValue::Array(vec![
+                    TomlValue::Float(0.1),
+                    TomlValue::Float(0.2),
+                    TomlValue::Float(0.5),
+                ])
             ))
         );
     }
@@ -214,38 +149,17 @@ mod tests_array {
     fn test_mixed_integer_float_array() {
         // Test with an extra comma at the end
         assert_eq!(
-            array::<(&str, ErrorKind)>("[ 0.1, 0.2, 0.5, 1, 2, 5 ]"),
+            array::<(&str, ErrorKind)>("[ 0.1, 0.2, 0.5, 1, 2, 5 ]").map(without_spans),
             Ok((
                 "",
-                TomlValue::Array(Box::new(Array {
-                    value: None,
-                    children: Some(vec![
-                        Array {
-                            value: Some(TomlValue::Float(0.1)),
-                            children: None,
-                        },
-                        Array {
-                            value: Some(TomlValue::Float(0.2)),
-                            children: None,
-                        },
-                        Array {
-                            value: Some(TomlValue::Float(0.5)),
-                            children: None,
-                        },
-                        Array {
-                            value: Some(TomlValue::Integer(1)),
-                            children: None,
-                        },
-                        Array {
-                            value: Some(TomlValue::Integer(2)),
-                            children: None,
-                        },
-                        Array {
-                            value: Some(TomlValue::Integer(5)),
-                            children: None,
-                        },
-                    ]),
-                }))
+                TomlValue::Array(vec![
+                    TomlValue::Float(0.1),
+                    TomlValue::Float(0.2),
+                    TomlValue::Float(0.5),
+                    TomlValue::Integer(1),
+                    TomlValue::Integer(2),
+                    TomlValue::Integer(5),
+                ])
             ))
         );
     }
@@ -253,44 +167,17 @@ mod tests_array {
     #[test]
     fn test_nested_integer_array() {
         assert_eq!(
-            array::<(&str, ErrorKind)>("[ [ 1, 2 ], [3, 4, 5] ]"),
+            array::<(&str, ErrorKind)>("[ [ 1, 2 ], [3, 4, 5] ]").map(without_spans),
             Ok((
                 "",
-                TomlValue::Array(Box::new(Array {
-                    value: None,
-                    children: Some(vec![
-                        Array {
-                            value: None,
-                            children: Some(vec![
-                                Array {
-                                    value: Some(TomlValue::Integer(1)),
-                                    children: None,
-                                },
-                                Array {
-                                    value: Some(TomlValue::Integer(2)),
-                                    children: None,
-                                },
-                            ]),
-                        },
-                        Array {
-                            value: None,
-                            children: Some(vec![
-                                Array {
-                                    value: Some(TomlValue::Integer(3)),
-                                    children: None,
-                                },
-                                Array {
-                                    value: Some(TomlValue::Integer(4)),
-                                    children: None,
-                                },
-                                Array {
-                                    value: Some(TomlValue::Integer(5)),
-                                    children: None,
-                                },
-                            ]),
-                        },
+                TomlValue::Array(vec![
+                    TomlValue::Array(vec![TomlValue::Integer(1), TomlValue::Integer(2)]),
+                    TomlValue::Array(vec![
+                        TomlValue::Integer(3),
+                        TomlValue::Integer(4),
+                        TomlValue::Integer(5),
                     ]),
-                }))
+                ])
             ))
         );
     }
@@ -298,26 +185,14 @@ mod tests_array {
     #[test]
     fn test_no_whitespace_string_array() {
         assert_eq!(
-            array_value::<(&str, ErrorKind)>(r#"["red","yellow","green"]"#),
+            array_value::<(&str, ErrorKind)>(r#"["red","yellow","green"]"#).map(without_spans),
             Ok((
                 "",
-                Array {
-                    value: None,
-                    children: Some(vec![
-                        Array {
-                            value: Some(TomlValue::Str("red".to_string())),
-                            children: None,
-                        },
-                        Array {
-                            value: Some(TomlValue::Str("yellow".to_string())),
-                            children: None,
-                        },
-                        Array {
-                            value: Some(TomlValue::Str("green".to_string())),
-                            children: None,
-                        },
-                    ], ),
-                }
+                vec![
+                    TomlValue::Str("red".to_string()),
+                    TomlValue::Str("yellow".to_string()),
+                    TomlValue::Str("green".to_string()),
+                ]
             ))
         );
     }
@@ -325,26 +200,14 @@ mod tests_array {
     #[test]
     fn test_basic_string_array() {
         assert_eq!(
-            array::<(&str, ErrorKind)>(r#"[ "red", "yellow", "green" ]"#),
+            array::<(&str, ErrorKind)>(r#"[ "red", "yellow", "green" ]"#).map(without_spans),
             Ok((
                 "",
-                TomlValue::Array(Box::new(Array {
-                    value: None,
-                    children: Some(vec![
-                        Array {
-                            value: Some(TomlValue::Str("red".to_string())),
-                            children: None,
-                        },
-                        Array {
-                            value: Some(TomlValue::Str("yellow".to_string())),
-                            children: None,
-                        },
-                        Array {
-                            value: Some(TomlValue::Str("green".to_string())),
-                            children: None,
-                        },
-                    ]),
-                }, ))
+                TomlValue::Array(vec![
+                    TomlValue::Str("red".to_string()),
+                    TomlValue::Str("yellow".to_string()),
+                    TomlValue::Str("green".to_string()),
+                ])
             ))
         );
     }
@@ -362,6 +225,44 @@ mod tests_array {
         println!("{:?}", array::<(&str, ErrorKind)>(r#"["derive"]"#));
     }
 
+    #[test]
+    fn test_empty_array() {
+        assert_eq!(
+            array::<(&str, ErrorKind)>("[]").map(without_spans),
+            Ok(("", TomlValue::Array(vec![])))
+        );
+        assert_eq!(
+            array::<(&str, ErrorKind)>("[ ]").map(without_spans),
+            Ok(("", TomlValue::Array(vec![])))
+        );
+    }
+
+    #[test]
+    fn test_trailing_comma_array() {
+        assert_eq!(
+            array::<(&str, ErrorKind)>("[ 1, 2, ]").map(without_spans),
+            Ok((
+                "",
+                TomlValue::Array(vec![TomlValue::Integer(1), TomlValue::Integer(2)])
+            ))
+        );
+    }
+
+    #[test]
+    fn test_multiline_array() {
+        let input = "[\n    \"doc/calculator\", # the first one\n    # a full line comment\n    \"lalrpop\",\n]";
+        assert_eq!(
+            array::<(&str, ErrorKind)>(input).map(without_spans),
+            Ok((
+                "",
+                TomlValue::Array(vec![
+                    TomlValue::Str("doc/calculator".to_string()),
+                    TomlValue::Str("lalrpop".to_string()),
+                ])
+            ))
+        );
+    }
+
     // #[test]
     // fn test_different_string_types_array() {
     //     println!("{:?}", array::<(&str, ErrorKind)>(r#"string_array = [ "all", 'strings', """are the same""", '''type''' ]"#));
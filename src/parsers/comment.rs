@@ -1,7 +1,11 @@
+use nom::branch::alt;
 use nom::character::complete::{char, line_ending, not_line_ending};
+use nom::combinator::{eof, opt, recognize};
 use nom::error::ParseError;
 use nom::IResult;
-use nom::sequence::{preceded, terminated};
+use nom::sequence::{pair, preceded, terminated, tuple};
+
+use crate::parsers::whitespace::whitespace;
 
 /// A hash symbol marks the rest of the line as a comment, except when inside a string.
 /// ```Rust
@@ -12,12 +16,20 @@ use nom::sequence::{preceded, terminated};
 ///
 /// Control characters other than tab (U+0000 to U+0008, U+000A to U+001F, U+007F) are not permitted
 /// in comments.
-/// ToDo: Test inline comments
+/// The line ending after the comment is consumed too, unless the comment is on the last line.
 /// ToDo: Test for control characters
-/// ToDo: Should the newline be consumed?
-/// ToDo: What if the comment is in the last line of the file?
 pub(crate) fn comment<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
-    preceded(char('#'), terminated(not_line_ending, line_ending))(input)
+    preceded(char('#'), terminated(not_line_ending, alt((line_ending, eof))))(input)
+}
+
+/// The end of a line that holds a key value pair or a table header: optional whitespace and
+/// a comment, followed by a line ending or the end of the input
+pub(crate) fn line_end<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    recognize(tuple((
+        whitespace,
+        opt(pair(char('#'), not_line_ending)),
+        alt((line_ending, eof)),
+    )))(input)
 }
 
 #[cfg(test)]
@@ -32,5 +44,20 @@ mod tests_comment {
             comment::<(&str, ErrorKind)>("# This is a full-line comment\n"),
             Ok(("", " This is a full-line comment"))
         );
+        assert_eq!(
+            comment::<(&str, ErrorKind)>("# A comment on the last line"),
+            Ok(("", " A comment on the last line"))
+        );
+    }
+
+    #[test]
+    fn test_line_end() {
+        assert_eq!(
+            line_end::<(&str, ErrorKind)>("  # This is a comment at the end of a line\nkey"),
+            Ok(("key", "  # This is a comment at the end of a line\n"))
+        );
+        assert_eq!(line_end::<(&str, ErrorKind)>("\r\nkey"), Ok(("key", "\r\n")));
+        assert_eq!(line_end::<(&str, ErrorKind)>("  "), Ok(("", "  ")));
+        assert!(line_end::<(&str, ErrorKind)>(" key").is_err());
     }
 }
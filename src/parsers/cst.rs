@@ -0,0 +1,408 @@
+use nom::branch::alt;
+use nom::character::complete::{char, line_ending, not_line_ending};
+use nom::combinator::{consumed, cut, eof, map, map_opt, opt, recognize};
+use nom::error::{context, FromExternalError, ParseError};
+use nom::IResult;
+use nom::multi::{many0, many0_count};
+use nom::sequence::{pair, preceded, terminated, tuple};
+
+use crate::cst::{
+    CstArray, CstDocument, CstHeader, CstInlineKeyValue, CstInlineTable, CstItem, CstKeyValue,
+    CstSection, CstValue,
+};
+use crate::parsers::array::array_space;
+use crate::parsers::key_value::dotted_key;
+use crate::parsers::table::table_header_key;
+use crate::parsers::{expect, toml_value, ExpectError, TomlValue};
+use crate::parsers::whitespace::{sp, whitespace};
+use crate::value::Table;
+
+// The parsers in this module mirror the ones that build a `Document`, but hand back every
+// piece of the input they consume, so that nothing is lost when the tree is written back out.
+
+/// An empty line, or a line that only holds a comment, including its line ending
+fn trivia_line<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    recognize(tuple((
+        whitespace,
+        opt(pair(char('#'), not_line_ending)),
+        line_ending,
+    )))(input)
+}
+
+/// The empty lines and comments in front of a key value pair or a header
+fn trivia<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    recognize(many0_count(trivia_line))(input)
+}
+
+/// Whitespace and an optional comment at the end of a line, without the line ending
+fn suffix<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    recognize(pair(whitespace, opt(pair(char('#'), not_line_ending))))(input)
+}
+
+fn newline<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    alt((line_ending, eof))(input)
+}
+
+/// A value, split up into its values and what is written between them if it is an array or
+/// an inline table. The grammar and the errors are the ones of `toml_value`.
+fn cst_value<
+    'a,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+>(
+    input: &'a str,
+) -> IResult<&'a str, (CstValue, TomlValue), E> {
+    expect(
+        &["a value"],
+        alt((
+            cst_array,
+            cst_inline_table,
+            map(consumed(toml_value), |(raw, (value, _))| {
+                (CstValue::Scalar(raw.to_string()), value)
+            }),
+        )),
+    )(input)
+}
+
+/// Mirrors `array_value`
+fn cst_array<
+    'a,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+>(
+    input: &'a str,
+) -> IResult<&'a str, (CstValue, TomlValue), E> {
+    // The whitespace and comments after the last value, and after its comma if there is one
+    let close = pair(
+        array_space,
+        alt((
+            map(
+                terminated(
+                    preceded(char(','), array_space),
+                    expect(&["a value", "`]`"], char(']')),
+                ),
+                Some,
+            ),
+            map(expect(&["`,`", "`]`"], char(']')), |_| None),
+        )),
+    );
+    let items = tuple((
+        cst_value,
+        many0(tuple((array_space, char(','), array_space, cst_value))),
+        close,
+    ));
+    context(
+        "array",
+        map(
+            preceded(
+                char('['),
+                pair(
+                    array_space,
+                    cut(alt((
+                        map(char(']'), |_| None),
+                        map(items, Some),
+                    ))),
+                ),
+            ),
+            |(leading, items)| {
+                let mut array = CstArray {
+                    items: vec![],
+                    comma: false,
+                    trailing: String::new(),
+                };
+                let mut values = vec![];
+                let Some(((first, first_value), rest, (suffix, comma))) = items else {
+                    array.trailing = leading.to_string();
+                    return (CstValue::Array(array), TomlValue::Array(values));
+                };
+                let mut item = CstItem {
+                    leading: leading.to_string(),
+                    value: first,
+                    suffix: String::new(),
+                };
+                values.push(first_value);
+                for (suffix, _, leading, (value, toml)) in rest {
+                    item.suffix = suffix.to_string();
+                    array.items.push(item);
+                    item = CstItem {
+                        leading: leading.to_string(),
+                        value,
+                        suffix: String::new(),
+                    };
+                    values.push(toml);
+                }
+                item.suffix = suffix.to_string();
+                array.items.push(item);
+                array.comma = comma.is_some();
+                array.trailing = comma.unwrap_or_default().to_string();
+                (CstValue::Array(array), TomlValue::Array(values))
+            },
+        ),
+    )(input)
+}
+
+/// Mirrors `inline_key_val_pair`
+fn cst_inline_key_value<
+    'a,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+>(
+    input: &'a str,
+) -> IResult<&'a str, (CstInlineKeyValue, TomlValue), E> {
+    map(
+        pair(
+            tuple((whitespace, consumed(dotted_key), whitespace)),
+            cut(pair(
+                recognize(pair(expect(&["`=`"], char('=')), whitespace)),
+                cst_value,
+            )),
+        ),
+        |((leading, (key_raw, key), space), (separator, (value, toml)))| {
+            let entry = CstInlineKeyValue {
+                leading: leading.to_string(),
+                key_raw: key_raw.to_string(),
+                key,
+                separator: format!("{}{}", space, separator),
+                value,
+                suffix: String::new(),
+            };
+            (entry, toml)
+        },
+    )(input)
+}
+
+/// Mirrors `inline_table_value`, which fails on a key that is defined twice
+fn cst_inline_table<
+    'a,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+>(
+    input: &'a str,
+) -> IResult<&'a str, (CstValue, TomlValue), E> {
+    let entries = tuple((
+        expect(&["a key"], cst_inline_key_value),
+        many0(tuple((sp, char(','), sp, expect(&["a key"], cst_inline_key_value)))),
+        terminated(sp, expect(&["`,`", "`}`"], char('}'))),
+    ));
+    context(
+        "inline table",
+        map_opt(
+            preceded(
+                char('{'),
+                pair(sp, cut(alt((map(char('}'), |_| None), map(entries, Some))))),
+            ),
+            |(leading, entries)| {
+                let mut inline = CstInlineTable {
+                    entries: vec![],
+                    trailing: String::new(),
+                };
+                let mut table = Table::new();
+                let Some((first, rest, suffix)) = entries else {
+                    inline.trailing = leading.to_string();
+                    return Some((CstValue::InlineTable(inline), TomlValue::Table(table)));
+                };
+                let (mut entry, value) = first;
+                entry.leading.insert_str(0, leading);
+                let mut values = vec![value];
+                for (suffix, _, leading, (next, value)) in rest {
+                    entry.suffix = suffix.to_string();
+                    inline.entries.push(entry);
+                    entry = next;
+                    entry.leading.insert_str(0, leading);
+                    values.push(value);
+                }
+                entry.suffix = suffix.to_string();
+                inline.entries.push(entry);
+                for (entry, value) in inline.entries.iter().zip(values) {
+                    if !table.insert_dotted(entry.key.segments(), value) {
+                        return None;
+                    }
+                }
+                Some((CstValue::InlineTable(inline), TomlValue::Table(table)))
+            },
+        ),
+    )(input)
+}
+
+fn cst_key_value<
+    'a,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+>(
+    input: &'a str,
+) -> IResult<&'a str, CstKeyValue, E> {
+    map(
+        tuple((
+            trivia,
+            whitespace,
+            consumed(dotted_key),
+            cut(tuple((
+                recognize(tuple((whitespace, expect(&["`=`"], char('=')), whitespace))),
+                cst_value,
+                suffix,
+                expect(&["end of line"], newline),
+            ))),
+        )),
+        |(leading, indent, (key_raw, key), (separator, (value_raw, value), suffix, newline))| {
+            CstKeyValue {
+                leading: leading.to_string(),
+                indent: indent.to_string(),
+                key_raw: key_raw.to_string(),
+                key,
+                separator: separator.to_string(),
+                value_raw,
+                value,
+                suffix: suffix.to_string(),
+                newline: newline.to_string(),
+            }
+        },
+    )(input)
+}
+
+fn cst_header<
+    'a,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+>(
+    input: &'a str,
+) -> IResult<&'a str, CstHeader, E> {
+    map(
+        tuple((
+            trivia,
+            consumed(preceded(whitespace, table_header_key)),
+            suffix,
+            cut(expect(&["end of line"], newline)),
+        )),
+        |(leading, (raw, ((key, kind), _)), suffix, newline)| CstHeader {
+            leading: leading.to_string(),
+            raw: raw.to_string(),
+            key,
+            kind,
+            suffix: suffix.to_string(),
+            newline: newline.to_string(),
+        },
+    )(input)
+}
+
+fn cst_section<
+    'a,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+>(
+    input: &'a str,
+) -> IResult<&'a str, CstSection, E> {
+    map(pair(cst_header, many0(cst_key_value)), |(header, entries)| {
+        CstSection {
+            header: Some(header),
+            entries,
+        }
+    })(input)
+}
+
+/// Parses a document into its root section, the sections that follow it and whatever
+/// empty lines and comments are left at the end
+pub(crate) fn cst_document<
+    'a,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+>(
+    input: &'a str,
+) -> IResult<&'a str, CstDocument, E> {
+    map(
+        tuple((
+            many0(cst_key_value),
+            many0(cst_section),
+            recognize(pair(trivia, suffix)),
+        )),
+        |(root, sections, trailing)| {
+            let mut all = vec![CstSection {
+                header: None,
+                entries: root,
+            }];
+            all.extend(sections);
+            CstDocument {
+                sections: all,
+                trailing: trailing.to_string(),
+            }
+        },
+    )(input)
+}
+
+#[cfg(test)]
+mod tests_cst {
+    use nom::error::ErrorKind;
+
+    use crate::parsers::{Key, TableKind, TomlValue};
+
+    use super::*;
+
+    #[test]
+    fn test_cst_key_value() {
+        let (rest, key_val) =
+            cst_key_value::<(&str, ErrorKind)>("\n# about a\n  a . b\t=  0x10 # hex\nc = 1")
+                .unwrap();
+        assert_eq!(rest, "c = 1");
+        assert_eq!(key_val.leading, "\n# about a\n");
+        assert_eq!(key_val.indent, "  ");
+        assert_eq!(key_val.key_raw, "a . b");
+        assert_eq!(key_val.key, Key::from(["a", "b"]));
+        assert_eq!(key_val.separator, "\t=  ");
+        assert_eq!(key_val.value_raw, CstValue::Scalar("0x10".to_string()));
+        assert_eq!(key_val.value, TomlValue::Integer(16));
+        assert_eq!(key_val.suffix, " # hex");
+        assert_eq!(key_val.newline, "\n");
+    }
+
+    #[test]
+    fn test_cst_array() {
+        let (rest, (value, toml)) =
+            cst_value::<(&str, ErrorKind)>("[ # first\n  1 ,# one\n  [] , # last\n] x").unwrap();
+        assert_eq!(rest, " x");
+        let array = TomlValue::Array(vec![TomlValue::Integer(1), TomlValue::Array(vec![])]);
+        assert_eq!(toml, array);
+        let CstValue::Array(array) = &value else {
+            panic!("{:?} is not an array", value);
+        };
+        let pieces: Vec<(&str, &str)> = array
+            .items
+            .iter()
+            .map(|item| (item.leading.as_str(), item.suffix.as_str()))
+            .collect();
+        assert_eq!(pieces, vec![(" # first\n  ", " "), ("# one\n  ", " ")]);
+        assert_eq!(array.items[1].value.to_string(), "[]");
+        assert!(array.comma);
+        assert_eq!(array.trailing, " # last\n");
+        assert_eq!(value.to_string(), "[ # first\n  1 ,# one\n  [] , # last\n]");
+    }
+
+    #[test]
+    fn test_cst_inline_table() {
+        let (_, (value, toml)) =
+            cst_value::<(&str, ErrorKind)>("{a.b=1 ,  c = { } }").unwrap();
+        assert_eq!(toml.to_string(), "{ a = { b = 1 }, c = {} }");
+        let CstValue::InlineTable(table) = &value else {
+            panic!("{:?} is not an inline table", value);
+        };
+        assert_eq!(table.entries[0].key, Key::from(["a", "b"]));
+        assert_eq!(table.entries[0].suffix, " ");
+        assert_eq!(table.entries[1].leading, "  ");
+        assert_eq!(table.entries[1].separator, " = ");
+        assert_eq!(table.entries[1].value.to_string(), "{ }");
+        assert_eq!(value.to_string(), "{a.b=1 ,  c = { } }");
+        assert!(cst_value::<(&str, ErrorKind)>("{ a = 1, a = 2 }").is_err());
+    }
+
+    #[test]
+    fn test_cst_header() {
+        let (rest, header) =
+            cst_header::<(&str, ErrorKind)>("\n[[ bin ]]  # targets\r\nname = 1").unwrap();
+        assert_eq!(rest, "name = 1");
+        assert_eq!(header.leading, "\n");
+        assert_eq!(header.raw, "[[ bin ]]");
+        assert_eq!(header.key, Key::from(["bin"]));
+        assert_eq!(header.kind, TableKind::ArrayOfTables);
+        assert_eq!(header.suffix, "  # targets");
+        assert_eq!(header.newline, "\r\n");
+    }
+
+    #[test]
+    fn test_cst_document_trailing() {
+        let (rest, doc) =
+            cst_document::<(&str, ErrorKind)>("a = 1\n[t]\n\n# the end\n  ").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(doc.sections.len(), 2);
+        assert_eq!(doc.trailing, "\n# the end\n  ");
+    }
+}
@@ -1,95 +1,120 @@
-use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, ParseResult};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
 use nom::{Err, IResult};
-use nom::branch::alt;
-use nom::combinator::map;
-use nom::error::{Error, ErrorKind, ParseError};
+use nom::bytes::complete::take_while1;
+use nom::character::complete::char;
+use nom::error::{ErrorKind, ParseError};
+use nom::sequence::preceded;
 
 use crate::parsers::TomlValue;
 
-// fn t_delimited_offset_datetime<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, TomlValue, E> {
-// //     1979-05-27 07:32:00Z
-//     separated_pair(is_a('-0123456789), " ", is_a(':))
-// }
-
-fn offset_datetime<'a, E: ParseError<&'a str>>(
-    input: &'a str,
-) -> IResult<&'a str, DateTime<FixedOffset>, Error<&'a str>> {
-    match DateTime::parse_from_rfc3339(input) {
-        ParseResult::Ok(dt) => IResult::Ok(("", dt)),
-        // https://stackoverflow.com/a/70240688/14311849
-        // ToDo: extract chrono's error text and use it
-        ParseResult::Err(e) => Err(Err::Error(Error::from_error_kind(input, ErrorKind::Fail))),
-    }
+fn is_datetime_char(c: char) -> bool {
+    c.is_ascii_digit() || "-:.+TtZz".contains(c)
 }
 
-fn local_date<'a, E: ParseError<&'a str>>(
-    input: &'a str,
-) -> IResult<&'a str, NaiveDate, Error<&'a str>> {
-    match NaiveDate::parse_from_str(input, "%Y-%m-%d") {
-        ParseResult::Ok(dt) => IResult::Ok(("", dt)),
-        ParseResult::Err(e) => Err(Err::Error(Error::from_error_kind(input, ErrorKind::Fail))),
+/// Tries the four kinds of datetimes on text that has already been cut out of the input.
+/// The delimiters are normalized first, as chrono only accepts an upper case `T` and `Z`.
+fn datetime_value(text: &str) -> Option<TomlValue> {
+    let text = text.replace(['t', ' '], "T").replace('z', "Z");
+    if let Ok(dt) = DateTime::parse_from_rfc3339(&text) {
+        return Some(TomlValue::OffsetDateTime(dt));
     }
-}
-
-fn local_time<'a, E: ParseError<&'a str>>(
-    input: &'a str,
-) -> IResult<&'a str, NaiveTime, Error<&'a str>> {
-    match NaiveTime::parse_from_str(input, "%H:%M:%S%.f") {
-        ParseResult::Ok(dt) => IResult::Ok(("", dt)),
-        ParseResult::Err(e) => Err(Err::Error(Error::from_error_kind(input, ErrorKind::Fail))),
+    if let Ok(dt) = NaiveDateTime::parse_from_str(&text, "%Y-%m-%dT%H:%M:%S%.f") {
+        return Some(TomlValue::LocalDateTime(dt));
     }
-}
-
-fn local_datetime<'a, E: ParseError<&'a str>>(
-    input: &'a str,
-) -> IResult<&'a str, NaiveDateTime, Error<&'a str>> {
-    match NaiveDateTime::parse_from_str(input, "%Y-%m-%dT%H:%M:%S%.f") {
-        ParseResult::Ok(dt) => IResult::Ok(("", dt)),
-        ParseResult::Err(e) => Err(Err::Error(Error::from_error_kind(input, ErrorKind::Fail))),
+    if let Ok(d) = NaiveDate::parse_from_str(&text, "%Y-%m-%d") {
+        return Some(TomlValue::LocalDate(d));
+    }
+    if let Ok(t) = NaiveTime::parse_from_str(&text, "%H:%M:%S%.f") {
+        return Some(TomlValue::LocalTime(t));
     }
+    None
 }
 
+/// Parses an offset datetime, local datetime, local date or local time. RFC 3339 allows a
+/// space instead of the `T` between date and time, so the text after a space is tried too.
 pub(crate) fn datetime<'a, E: ParseError<&'a str>>(
     input: &'a str,
-) -> IResult<&'a str, TomlValue, Error<&'a str>> {
-    alt((
-        map(offset_datetime::<'a, E>, |x| TomlValue::OffsetDateTime(x)),
-        map(local_datetime::<'a, E>, |x| TomlValue::LocalDateTime(x)),
-        map(local_date::<'a, E>, |x| TomlValue::LocalDate(x)),
-        map(local_time::<'a, E>, |x| TomlValue::LocalTime(x)),
-    ))(input)
+) -> IResult<&'a str, TomlValue, E> {
+    let (rest, date) = take_while1(is_datetime_char)(input)?;
+    if let Ok((after_time, time)) =
+        preceded(char::<_, E>(' '), take_while1(is_datetime_char))(rest)
+    {
+        if let Some(value) = datetime_value(&format!("{}T{}", date, time)) {
+            return Ok((after_time, value));
+        }
+    }
+    match datetime_value(date) {
+        Some(value) => Ok((rest, value)),
+        // ToDo: extract chrono's error text and use it
+        None => Err(Err::Error(E::from_error_kind(input, ErrorKind::Fail))),
+    }
 }
 
 #[cfg(test)]
 mod tests_datetime {
+    use chrono::{FixedOffset, TimeZone};
     use nom::error::ErrorKind;
 
     use super::*;
 
     #[test]
     fn test_offset_datetime() {
-        println!("{:?}", DateTime::parse_from_rfc3339("1979-05-27T07:32:00Z"));
-        println!(
-            "{:?}",
-            offset_datetime::<(&str, ErrorKind)>("1979-05-27T07:32:00Z")
+        let expected = FixedOffset::west_opt(7 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(1979, 5, 27, 0, 32, 0)
+            .unwrap();
+        assert_eq!(
+            datetime::<(&str, ErrorKind)>("1979-05-27T00:32:00-07:00"),
+            Ok(("", TomlValue::OffsetDateTime(expected)))
         );
-        println!(
-            "{:?}",
-            DateTime::parse_from_rfc3339("1979-05-27T00:32:00-07:00")
+        assert_eq!(
+            datetime::<(&str, ErrorKind)>("1979-05-27 07:32:00Z\n"),
+            Ok((
+                "\n",
+                TomlValue::OffsetDateTime(
+                    FixedOffset::east_opt(0)
+                        .unwrap()
+                        .with_ymd_and_hms(1979, 5, 27, 7, 32, 0)
+                        .unwrap()
+                )
+            ))
+        );
+    }
+
+    #[test]
+    fn test_local_datetime() {
+        let expected = NaiveDate::from_ymd_opt(1979, 5, 27)
+            .unwrap()
+            .and_hms_micro_opt(0, 32, 0, 999999)
+            .unwrap();
+        assert_eq!(
+            datetime::<(&str, ErrorKind)>("1979-05-27T00:32:00.999999"),
+            Ok(("", TomlValue::LocalDateTime(expected)))
         );
-        println!(
-            "{:?}",
-            DateTime::parse_from_rfc3339("1979-05-27T00:32:00.999999-07:00")
+    }
+
+    #[test]
+    fn test_local_date_and_time() {
+        assert_eq!(
+            datetime::<(&str, ErrorKind)>("1979-05-27 # a date"),
+            Ok((
+                " # a date",
+                TomlValue::LocalDate(NaiveDate::from_ymd_opt(1979, 5, 27).unwrap())
+            ))
         );
-        println!("{:?}", NaiveDate::parse_from_str("1979-05-27", "%Y-%m-%d"));
-        println!("{:?}", NaiveTime::parse_from_str("07:32:00", "%H:%M:%S"));
-        println!(
-            "{:?}",
-            NaiveTime::parse_from_str("00:32:00.999999", "%H:%M:%S%.f")
+        assert_eq!(
+            datetime::<(&str, ErrorKind)>("07:32:00"),
+            Ok((
+                "",
+                TomlValue::LocalTime(NaiveTime::from_hms_opt(7, 32, 0).unwrap())
+            ))
         );
+    }
 
-        // ToDo: The example below should be permitted based on RFC 3339 section 5.6, but it is not
-        // ToDo: Add support for space delimited rfc3339 datetimes
-        println!("{:?}", DateTime::parse_from_rfc3339("1979-05-27 07:32:00Z"));
+    #[test]
+    fn test_not_a_datetime() {
+        assert!(datetime::<(&str, ErrorKind)>("1979").is_err());
+        assert!(datetime::<(&str, ErrorKind)>("-1.5").is_err());
+        assert!(datetime::<(&str, ErrorKind)>("true").is_err());
     }
 }
@@ -0,0 +1,171 @@
+use nom::error::{ContextError, ErrorKind, FromExternalError, ParseError};
+use nom::{IResult, Parser};
+
+/// Errors that can tell what the parser expected to find where it failed. nom's own error
+/// types have no room for it, so they ignore it.
+pub(crate) trait ExpectError<I>: ParseError<I> + ContextError<I> {
+    fn expected(input: I, expected: &'static [&'static str], other: Self) -> Self;
+
+    /// An error about text that was read but is wrong, like an integer that does not fit in
+    /// 64 bits, rather than about a token that is missing
+    fn invalid(input: I, length: usize, message: &'static str) -> Self;
+}
+
+impl<I> ExpectError<I> for (I, ErrorKind) {
+    fn expected(_input: I, _expected: &'static [&'static str], other: Self) -> Self {
+        other
+    }
+
+    fn invalid(input: I, _length: usize, _message: &'static str) -> Self {
+        (input, ErrorKind::MapRes)
+    }
+}
+
+/// Runs a parser and, if it fails without having committed to its input, replaces whatever
+/// went wrong inside of it with the tokens it was `expected` to find. Failures from a `cut`
+/// are passed on as they are, as they already point at the actual mistake.
+pub(crate) fn expect<'a, O, E: ExpectError<&'a str>, F: Parser<&'a str, O, E>>(
+    expected: &'static [&'static str],
+    mut parser: F,
+) -> impl FnMut(&'a str) -> IResult<&'a str, O, E> {
+    move |input: &'a str| match parser.parse(input) {
+        Err(nom::Err::Error(e)) => Err(nom::Err::Error(E::expected(input, expected, e))),
+        result => result,
+    }
+}
+
+/// The error type [`parse`](crate::parse) runs the parsers with. It keeps what is needed to
+/// tell the user what went wrong, and is turned into an [`Error`](crate::Error) once the
+/// whole input is known.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SyntaxError<'a> {
+    /// What was left of the input where the parser failed
+    pub(crate) input: &'a str,
+    /// The tokens that would have been accepted instead
+    pub(crate) expected: Vec<&'static str>,
+    /// The labels of the parsers the error passed through, innermost first
+    pub(crate) context: Vec<&'static str>,
+    /// What is wrong with the text at `input` and how long that text is, for errors that
+    /// are not about a missing token
+    pub(crate) invalid: Option<(&'static str, usize)>,
+}
+
+impl<'a> ParseError<&'a str> for SyntaxError<'a> {
+    fn from_error_kind(input: &'a str, _kind: ErrorKind) -> Self {
+        SyntaxError {
+            input,
+            expected: vec![],
+            context: vec![],
+            invalid: None,
+        }
+    }
+
+    fn append(_input: &'a str, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    /// Keeps the error that got further into the input. If both failed at the same place,
+    /// either of their tokens would have been accepted.
+    fn or(mut self, other: Self) -> Self {
+        if other.input.len() < self.input.len() {
+            return other;
+        }
+        if other.input.len() == self.input.len() {
+            for token in other.expected {
+                if !self.expected.contains(&token) {
+                    self.expected.push(token);
+                }
+            }
+            if self.context.is_empty() {
+                self.context = other.context;
+            }
+        }
+        self
+    }
+}
+
+impl<'a> ContextError<&'a str> for SyntaxError<'a> {
+    fn add_context(_input: &'a str, context: &'static str, mut other: Self) -> Self {
+        other.context.push(context);
+        other
+    }
+}
+
+impl<'a, E> FromExternalError<&'a str, E> for SyntaxError<'a> {
+    fn from_external_error(input: &'a str, kind: ErrorKind, _e: E) -> Self {
+        Self::from_error_kind(input, kind)
+    }
+}
+
+impl<'a> ExpectError<&'a str> for SyntaxError<'a> {
+    fn expected(input: &'a str, expected: &'static [&'static str], _other: Self) -> Self {
+        SyntaxError {
+            input,
+            expected: expected.to_vec(),
+            context: vec![],
+            invalid: None,
+        }
+    }
+
+    fn invalid(input: &'a str, length: usize, message: &'static str) -> Self {
+        SyntaxError {
+            input,
+            expected: vec![],
+            context: vec![],
+            invalid: Some((message, length)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_error {
+    use nom::branch::alt;
+    use nom::character::complete::char;
+    use nom::combinator::cut;
+    use nom::error::context;
+    use nom::sequence::preceded;
+
+    use super::*;
+
+    fn error<'a>(result: IResult<&'a str, char, SyntaxError<'a>>) -> SyntaxError<'a> {
+        match result {
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => e,
+            other => panic!("expected an error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_expect() {
+        let e = error(expect(&["`=`"], char('='))("1"));
+        assert_eq!(e.input, "1");
+        assert_eq!(e.expected, vec!["`=`"]);
+    }
+
+    #[test]
+    fn test_or_merges_tokens() {
+        let e = error(alt((expect(&["`,`"], char(',')), expect(&["`]`"], char(']'))))("x"));
+        assert_eq!(e.expected, vec!["`,`", "`]`"]);
+    }
+
+    #[test]
+    fn test_or_keeps_the_furthest_error() {
+        let e = error(alt((
+            preceded(char('a'), expect(&["`b`"], char('b'))),
+            expect(&["`c`"], char('c')),
+        ))("ax"));
+        assert_eq!(e.input, "x");
+        assert_eq!(e.expected, vec!["`b`"]);
+    }
+
+    #[test]
+    fn test_context_and_cut() {
+        let mut parser = context(
+            "pair",
+            preceded(char('a'), cut(expect(&["`b`"], char('b')))),
+        );
+        let e = error(expect(&["something else"], &mut parser)("ax"));
+        assert_eq!(e.input, "x");
+        assert_eq!(e.expected, vec!["`b`"]);
+        assert_eq!(e.context, vec!["pair"]);
+    }
+}
@@ -1,12 +1,12 @@
 use nom::branch::alt;
 use nom::bytes::complete::tag;
 use nom::character::complete::one_of;
-use nom::combinator::{map, opt};
+use nom::combinator::{map, opt, recognize};
 use nom::error::ParseError;
 use nom::sequence::{pair, tuple};
 use nom::IResult;
 
-use crate::parsers::integer::{decimal_integer, underscored_decimal};
+use crate::parsers::integer::underscored_decimal;
 use crate::parsers::TomlValue;
 
 fn underscored_float<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, String, E> {
@@ -16,18 +16,17 @@ fn underscored_float<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a
     )(input)
 }
 
+/// The exponent is applied by `str::parse`, as multiplying by a power of ten is not exact
 fn exponential_float<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, f64, E> {
     map(
-        tuple((
-            decimal_integer,
+        recognize(tuple((
+            opt(one_of("-+")),
+            underscored_decimal,
             one_of("Ee"),
             opt(one_of("-+")),
-            decimal_integer,
-        )),
-        |(left, e, sign, right)| match sign {
-            Some('-') => left as f64 / 10_f64.powi(right as i32),
-            _ => left as f64 * 10_f64.powi(right as i32),
-        },
+            underscored_decimal,
+        ))),
+        parse_float,
     )(input)
 }
 
@@ -35,7 +34,7 @@ fn fractional_float<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a s
     map(
         pair(opt(one_of("-+")), underscored_float),
         |(sign, x)| match sign {
-            Some('-') => -1. * x.parse::<f64>().unwrap(),
+            Some('-') => -x.parse::<f64>().unwrap(),
             _ => x.parse::<f64>().unwrap(),
         },
     )(input)
@@ -43,19 +42,23 @@ fn fractional_float<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a s
 
 fn expo_frac_float<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, f64, E> {
     map(
-        tuple((
+        recognize(tuple((
             fractional_float,
             one_of("Ee"),
             opt(one_of("-+")),
-            decimal_integer,
-        )),
-        |(left, e, sign, right)| match sign {
-            Some('-') => left / 10_f64.powi(right as i32),
-            _ => left * 10_f64.powi(right as i32),
-        },
+            underscored_decimal,
+        ))),
+        parse_float,
     )(input)
 }
 
+/// Parses text that has already been recognized as a float, dropping its underscores
+fn parse_float(text: &str) -> f64 {
+    text.replace('_', "")
+        .parse()
+        .expect("recognized floats are valid")
+}
+
 /// Negative NaNs do not seem to currently exist in Rust
 /// https://github.com/rust-lang/rust/issues/81261
 /// For now, all NaNs map to `f64::NAN`
@@ -68,9 +71,10 @@ pub(crate) fn float<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a s
         map(tag("+nan"), |_| TomlValue::Float(f64::NAN)),
         map(tag("nan"), |_| TomlValue::Float(f64::NAN)),
         map(tag("-nan"), |_| TomlValue::Float(f64::NAN)),
-        map(fractional_float, TomlValue::Float),
-        map(exponential_float, TomlValue::Float),
+        // `fractional_float` would only consume the part before the exponent, so it goes last
         map(expo_frac_float, TomlValue::Float),
+        map(exponential_float, TomlValue::Float),
+        map(fractional_float, TomlValue::Float),
     ))(input)
 }
 
@@ -127,6 +131,18 @@ mod tests_float {
         );
     }
 
+    #[test]
+    fn test_float_exponent_is_not_dropped() {
+        assert_eq!(
+            float::<(&str, ErrorKind)>("1.5e-7"),
+            Ok(("", TomlValue::Float(1.5e-7)))
+        );
+        assert_eq!(
+            float::<(&str, ErrorKind)>("1e1_0"),
+            Ok(("", TomlValue::Float(1e10)))
+        );
+    }
+
     #[test]
     fn test_plus_signed_float() {
         assert_eq!(
@@ -136,6 +152,7 @@ mod tests_float {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_unsigned_float() {
         assert_eq!(
             float::<(&str, ErrorKind)>("3.1415"),
@@ -1,108 +1,90 @@
-use std::fmt::{Display, Formatter};
-
 use nom::branch::alt;
-use nom::bytes::complete::tag;
-use nom::combinator::map;
-use nom::error::{FromExternalError, ParseError};
+use nom::character::complete::char;
+use nom::combinator::{cut, map, map_opt};
+use nom::error::{context, FromExternalError};
 use nom::IResult;
 use nom::multi::separated_list1;
-use nom::sequence::{delimited, pair, separated_pair, tuple};
+use nom::sequence::{delimited, pair, preceded, terminated};
 
-use crate::parsers::{toml_value, TomlValue};
+use crate::parsers::{expect, toml_value, ExpectError, TomlValue};
 use crate::parsers::key_value::{key, KeyValue};
 use crate::parsers::whitespace::{sp, whitespace};
-
-#[derive(Debug, PartialEq)]
-pub(crate) struct InlineTable {
-    value: Option<KeyValue>,
-    children: Option<Vec<InlineTable>>,
-}
-
-impl Display for InlineTable {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let mut output = String::new();
-
-        match &self.value {
-            Some(key_val) => output.push_str(&format!("\t{}: {}", key_val.0, key_val.1)),
-            None => {}
-        }
-        match &self.children {
-            Some(vec) => {
-                output.push_str("\t[\n");
-                for it in vec {
-                    output.push_str(&format!("\t\t{}\n", &it.to_string()));
-                }
-                output.push_str("\t]\n");
-            }
-            None => {}
-        }
-
-        f.write_str(&output)
-    }
-}
+use crate::span::{spanned, KeyValueSpans, NestedSpans, TableSpans, ValueSpan};
+use crate::value::Table;
 
 pub(crate) fn inline_key_val_pair<
     'a,
-    E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 >(
     input: &'a str,
-) -> IResult<&'a str, KeyValue, E> {
+) -> IResult<&'a str, (KeyValue, KeyValueSpans), E> {
     map(
-        separated_pair(key, tuple((whitespace, tag("="), whitespace)), toml_value),
-        |(k, v)| KeyValue(k.to_string(), v),
+        pair(
+            key,
+            cut(preceded(
+                pair(expect(&["`=`"], char('=')), whitespace),
+                toml_value,
+            )),
+        ),
+        |((k, key), (v, value))| (KeyValue(k, v), KeyValueSpans { key, value }),
     )(input)
 }
 
-/// Parses inline table items that are not inline tables themselves
-fn inline_table_key_val_value<
-    'a,
-    E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
->(
-    input: &'a str,
-) -> IResult<&'a str, InlineTable, E> {
-    map(inline_key_val_pair, |key_val| InlineTable {
-        value: Some(key_val),
-        children: None,
-    })(input)
-}
-
+/// Parses the right side of an inline table definition such as `serde = { version = "1.0" }`.
+/// Dotted keys are expanded into nested tables, and defining a key twice fails the parser,
+/// as inline tables are self-contained and cannot be extended later on.
 fn inline_table_value<
     'a,
-    E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 >(
     input: &'a str,
-) -> IResult<&'a str, InlineTable, E> {
-    map(
-        delimited(
-            pair(tag("{"), sp),
-            separated_list1(
-                delimited(sp, tag(","), sp),
-                /// Each array item is either another array or a single TOML value, so test for both
-                alt((inline_table_value, inline_table_key_val_value)),
-            ),
-            pair(sp, tag("}")),
+) -> IResult<&'a str, (Table, TableSpans), E> {
+    let key_values = terminated(
+        separated_list1(
+            delimited(sp, char(','), sp),
+            expect(&["a key"], inline_key_val_pair),
+        ),
+        pair(sp, expect(&["`,`", "`}`"], char('}'))),
+    );
+    context(
+        "inline table",
+        preceded(
+            pair(char('{'), sp),
+            cut(map_opt(
+                alt((map(char('}'), |_| vec![]), key_values)),
+                |key_values| {
+                    let mut table = Table::new();
+                    let mut spans = Vec::with_capacity(key_values.len());
+                    for (KeyValue(key, value), key_value_spans) in key_values {
+                        if !table.insert_dotted(key.segments(), value) {
+                            return None;
+                        }
+                        spans.push((key, key_value_spans));
+                    }
+                    Some((table, spans))
+                },
+            )),
         ),
-        |inline_values| InlineTable {
-            value: None,
-            children: Some(inline_values),
-        },
     )(input)
 }
 
 pub(crate) fn inline_table<
     'a,
-    E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 >(
     input: &'a str,
-) -> IResult<&'a str, TomlValue, E> {
-    map(inline_table_value, |t| TomlValue::InlineTable(Box::new(t)))(input)
+) -> IResult<&'a str, (TomlValue, ValueSpan), E> {
+    map(spanned(inline_table_value), |((table, spans), span)| {
+        let nested = NestedSpans::Table(spans);
+        (TomlValue::Table(table), ValueSpan { span, nested })
+    })(input)
 }
 
 #[cfg(test)]
 mod tests_inline_table {
     use nom::error::ErrorKind;
 
-    use crate::parsers::array::Array;
+    use crate::parsers::without_spans;
 
     use super::*;
 
@@ -111,32 +93,56 @@ mod tests_inline_table {
         let input = r#"{ version = "1.0", features = ["derive"] }"#;
         let expected = Ok((
             "",
-            InlineTable {
-                value: None,
-                children: Some(vec![
-                    InlineTable {
-                        value: Some(KeyValue(
-                            String::from("version"),
-                            TomlValue::Str(String::from("1.0")),
-                        )),
-                        children: None,
-                    },
-                    InlineTable {
-                        value: Some(KeyValue(
-                            String::from("features"),
-                            TomlValue::Array(Box::new(Array {
-                                value: None,
-                                children: Some(vec![Array {
-                                    value: Some(TomlValue::Str(String::from("derive"))),
-                                    children: None,
-                                }]),
-                            })),
-                        )),
-                        children: None,
-                    },
-                ]),
-            },
+            Table::from_iter([
+                ("version", TomlValue::Str(String::from("1.0"))),
+                (
+                    "features",
+                    TomlValue::Array(vec![TomlValue::Str(String::from("derive"))]),
+                ),
+            ]),
         ));
-        assert_eq!(inline_table_value::<(&str, ErrorKind)>(input), expected);
+        assert_eq!(
+            inline_table_value::<(&str, ErrorKind)>(input).map(without_spans),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_nested_inline_table() {
+        let input = r#"{ name = { first = "Tom", last = "Preston-Werner" }, point.x = 1 }"#;
+        let expected = Ok((
+            "",
+            Table::from_iter([
+                (
+                    "name",
+                    TomlValue::Table(Table::from_iter([
+                        ("first", TomlValue::Str(String::from("Tom"))),
+                        ("last", TomlValue::Str(String::from("Preston-Werner"))),
+                    ])),
+                ),
+                (
+                    "point",
+                    TomlValue::Table(Table::from_iter([("x", TomlValue::Integer(1))])),
+                ),
+            ]),
+        ));
+        assert_eq!(
+            inline_table_value::<(&str, ErrorKind)>(input).map(without_spans),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_empty_inline_table() {
+        assert_eq!(
+            inline_table::<(&str, ErrorKind)>("{}").map(without_spans),
+            Ok(("", TomlValue::Table(Table::new())))
+        );
+    }
+
+    #[test]
+    fn test_inline_table_duplicate_key() {
+        assert!(inline_table_value::<(&str, ErrorKind)>("{ a = 1, a = 2 }").is_err());
+        assert!(inline_table_value::<(&str, ErrorKind)>("{ a = 1, a.b = 2 }").is_err());
     }
 }
@@ -7,7 +7,7 @@ use nom::multi::separated_list1;
 use nom::sequence::{pair, preceded};
 use nom::IResult;
 
-use crate::parsers::TomlValue;
+use crate::parsers::{ExpectError, TomlValue};
 
 // ToDo: have digit parser function as an argument to have a single `underscored_` func
 /// Matches a `_` separated sequence of digits and returns them without underscores
@@ -29,44 +29,53 @@ fn underscored_binary<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a
     map(separated_list1(tag("_"), is_a("01")), |vec| vec.concat())(input)
 }
 
-pub(crate) fn decimal_integer<'a, E: ParseError<&'a str>>(
+/// Reads the digits a parser recognized as an `i64`. Digits that do not fit are a failure
+/// rather than an error, as no other kind of value can start with them.
+fn checked<'a, E: ExpectError<&'a str>>(
+    mut digits: impl FnMut(&'a str) -> IResult<&'a str, String, E>,
+    radix: u32,
+) -> impl FnMut(&'a str) -> IResult<&'a str, i64, E> {
+    move |input: &'a str| {
+        let (rest, text) = digits(input)?;
+        match i64::from_str_radix(&text, radix) {
+            Ok(value) => Ok((rest, value)),
+            Err(_) => Err(nom::Err::Failure(E::invalid(
+                input,
+                input.len() - rest.len(),
+                "integer out of range",
+            ))),
+        }
+    }
+}
+
+/// The sign is read together with the digits, so that `-9223372036854775808` fits
+pub(crate) fn decimal_integer<'a, E: ExpectError<&'a str>>(
     input: &'a str,
 ) -> IResult<&'a str, i64, E> {
-    map(
-        pair(opt(one_of("-+")), underscored_decimal),
-        |(sign, x)| match sign {
-            Some('-') => -x.parse::<i64>().unwrap(),
-            _ => x.parse::<i64>().unwrap(),
-        },
+    checked(
+        map(pair(opt(one_of("-+")), underscored_decimal), |(sign, digits)| match sign {
+            Some(sign) => format!("{}{}", sign, digits),
+            None => digits,
+        }),
+        10,
     )(input)
 }
 
-/// ToDo: handle parse error propagation
-fn hex_integer<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, i64, E> {
-    map(
-        preceded(opt(char('+')), preceded(tag("0x"), underscored_hex)),
-        |x| i64::from_str_radix(x.as_str(), 16).unwrap(),
-    )(input)
+fn hex_integer<'a, E: ExpectError<&'a str>>(input: &'a str) -> IResult<&'a str, i64, E> {
+    preceded(opt(char('+')), checked(preceded(tag("0x"), underscored_hex), 16))(input)
 }
 
-fn oct_integer<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, i64, E> {
-    map(
-        preceded(opt(char('+')), preceded(tag("0o"), underscored_oct)),
-        |x| i64::from_str_radix(x.as_str(), 8).unwrap(),
-    )(input)
+fn oct_integer<'a, E: ExpectError<&'a str>>(input: &'a str) -> IResult<&'a str, i64, E> {
+    preceded(opt(char('+')), checked(preceded(tag("0o"), underscored_oct), 8))(input)
 }
 
-fn binary_integer<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, i64, E> {
-    map(
-        preceded(opt(char('+')), preceded(tag("0b"), underscored_binary)),
-        |x| i64::from_str_radix(x.as_str(), 2).unwrap(),
-    )(input)
+fn binary_integer<'a, E: ExpectError<&'a str>>(input: &'a str) -> IResult<&'a str, i64, E> {
+    preceded(opt(char('+')), checked(preceded(tag("0b"), underscored_binary), 2))(input)
 }
 
-///
-///
-/// ToDo: Proper error for numbers that cannot be represented losslessly
-pub(crate) fn integer<'a, E: ParseError<&'a str>>(
+/// Parses an integer in any of the bases TOML has. One that does not fit in an `i64` is a
+/// failure that says so.
+pub(crate) fn integer<'a, E: ExpectError<&'a str>>(
     input: &'a str,
 ) -> IResult<&'a str, TomlValue, E> {
     alt((
@@ -262,6 +271,22 @@ mod tests_integer {
         );
     }
 
+    #[test]
+    fn test_integer_limits() {
+        assert_eq!(
+            integer::<(&str, ErrorKind)>("-9_223_372_036_854_775_808"),
+            Ok(("", TomlValue::Integer(i64::MIN)))
+        );
+        assert_eq!(
+            integer::<(&str, ErrorKind)>("0x7FFF_FFFF_FFFF_FFFF"),
+            Ok(("", TomlValue::Integer(i64::MAX)))
+        );
+        assert!(matches!(
+            integer::<(&str, ErrorKind)>("9223372036854775808"),
+            Err(nom::Err::Failure(_))
+        ));
+    }
+
     #[test]
     fn test_plus_signed_octal_integer() {
         assert_eq!(
@@ -1,93 +1,278 @@
+use std::fmt::{Display, Formatter};
+
 use nom::branch::alt;
-use nom::bytes::complete::{is_a, tag};
-use nom::character::complete::line_ending;
-use nom::combinator::{eof, map, recognize};
-use nom::error::{FromExternalError, ParseError};
+use nom::bytes::complete::{is_a, tag, take_while};
+use nom::character::complete::char;
+use nom::combinator::{all_consuming, cut, map, peek, recognize};
+use nom::error::{context, ErrorKind, FromExternalError, ParseError};
 use nom::IResult;
 use nom::multi::separated_list1;
-use nom::sequence::{delimited, pair, preceded, separated_pair, terminated, tuple};
+use nom::sequence::{delimited, pair, preceded};
 
-use crate::parsers::{toml_value, TomlValue};
-use crate::parsers::string::{basic_string, literal_string};
+use crate::emit::write_string;
+use crate::parsers::{expect, toml_value, ExpectError, TomlValue};
+use crate::parsers::comment::line_end;
+use crate::parsers::nom_string::parse_string;
 use crate::parsers::whitespace::whitespace;
+use crate::span::{spanned, KeyValueSpans, RawSpan};
+
+const BARE_KEY_CHARS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789_-";
+
+/// A key split into its dot separated segments, with quoted segments already unescaped.
+/// `site."google.com"` has the two segments `site` and `google.com`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Key(Vec<String>);
+
+impl Key {
+    pub fn new(segments: Vec<String>) -> Self {
+        Key(segments)
+    }
+
+    pub fn segments(&self) -> &[String] {
+        &self.0
+    }
+
+    /// The key of the root table is the only one without segments
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
 
-#[derive(Debug, PartialEq)]
-pub(crate) struct KeyValue(pub String, pub TomlValue);
+impl<const N: usize> From<[&str; N]> for Key {
+    fn from(segments: [&str; N]) -> Self {
+        Key(segments.iter().map(|s| s.to_string()).collect())
+    }
+}
+
+/// Writes a key segment as a bare key if it can be one, or as a basic string otherwise
+pub(crate) fn write_key_segment(f: &mut Formatter<'_>, segment: &str) -> std::fmt::Result {
+    if !segment.is_empty() && segment.chars().all(|c| BARE_KEY_CHARS.contains(c)) {
+        return f.write_str(segment);
+    }
+    write_string(f, segment)
+}
+
+/// Writes a key the way manifests are written by hand, with the segments that cannot be bare
+/// keys in single quotes, like `target.'cfg(unix)'`. A segment that cannot be a literal
+/// string, as it holds a `'` or a control character, is written as a basic string.
+pub(crate) struct LiteralKey<'a>(pub(crate) &'a Key);
+
+impl Display for LiteralKey<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, segment) in self.0.segments().iter().enumerate() {
+            if i > 0 {
+                f.write_str(".")?;
+            }
+            let bare = !segment.is_empty() && segment.chars().all(|c| BARE_KEY_CHARS.contains(c));
+            let literal = !segment.chars().any(|c| c == '\'' || (c.is_control() && c != '\t'));
+            if !bare && literal {
+                write!(f, "'{}'", segment)?;
+            } else {
+                write_key_segment(f, segment)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(".")?;
+            }
+            write_key_segment(f, segment)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyValue(pub Key, pub TomlValue);
 
 fn bare_key<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
-    is_a("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789_-")(input)
+    is_a(BARE_KEY_CHARS)(input)
 }
 
-fn dotted_key<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
-    recognize(separated_list1(tag("."), alt((bare_key, quoted_key))))(input)
+/// Literal strings cannot be escaped, so a quoted key in single quotes is taken as is
+fn literal_key<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    delimited(char('\''), take_while(|c| c != '\'' && c != '\n'), char('\''))(input)
 }
 
-fn quoted_key<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
-    alt((literal_string, basic_string))(input)
+/// `parse_string` is a streaming parser, so it is only tried once there is a quote to parse.
+/// Otherwise, looking for another key at the end of the input would be `Incomplete`.
+fn quoted_key<'a, E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>>(
+    input: &'a str,
+) -> IResult<&'a str, String, E> {
+    alt((
+        map(literal_key, |s| s.to_string()),
+        preceded(peek(char('"')), parse_string),
+    ))(input)
+}
+
+pub(crate) fn simple_key<'a, E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>>(
+    input: &'a str,
+) -> IResult<&'a str, String, E> {
+    alt((quoted_key, map(bare_key, |s| s.to_string())))(input)
 }
 
-pub(crate) fn key<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
-    delimited(
-        whitespace,
-        alt((dotted_key, quoted_key, bare_key)),
-        whitespace,
+/// Whitespace around the dots is ignored, so `fruit . color` is the same as `fruit.color`
+pub(crate) fn dotted_key<'a, E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>>(
+    input: &'a str,
+) -> IResult<&'a str, Key, E> {
+    map(
+        separated_list1(delimited(whitespace, tag("."), whitespace), simple_key),
+        Key,
     )(input)
 }
 
+/// A dotted key as it was written, quotes and all, without the whitespace around it and its
+/// dots. `fruit . 'color'` becomes `fruit.'color'`.
+pub(crate) fn trim_key(raw: &str) -> String {
+    let segments = separated_list1(
+        delimited(whitespace, tag("."), whitespace),
+        recognize(simple_key::<(&str, ErrorKind)>),
+    );
+    match all_consuming(delimited(whitespace, segments, whitespace))(raw) {
+        Ok((_, segments)) => segments.join("."),
+        Err(_) => raw.trim().to_string(),
+    }
+}
+
+/// A key with the whitespace around it. Its span only covers the key itself.
+pub(crate) fn key<'a, E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>>(
+    input: &'a str,
+) -> IResult<&'a str, (Key, RawSpan), E> {
+    delimited(whitespace, spanned(dotted_key), whitespace)(input)
+}
+
 // ToDo: If key and sub-parsers deal with whitespace, this code can be simplified
 // ToDo: Some key value pairs can be defined in multiple lines
+/// A key value pair on a line of its own. A line that starts with a key can only be a key
+/// value pair, so anything wrong after the key is reported right away.
 pub(crate) fn key_val_pair<
     'a,
-    E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 >(
     input: &'a str,
-) -> IResult<&'a str, KeyValue, E> {
-    map(
-        terminated(
+) -> IResult<&'a str, (KeyValue, KeyValueSpans), E> {
+    context(
+        "key value pair",
+        map(
             preceded(
                 whitespace,
-                separated_pair(key, tuple((whitespace, tag("="), whitespace)), toml_value),
+                pair(
+                    key,
+                    cut(delimited(
+                        pair(expect(&["`=`"], char('=')), whitespace),
+                        toml_value,
+                        preceded(whitespace, expect(&["end of line"], line_end)),
+                    )),
+                ),
             ),
-            pair(whitespace, alt((eof, line_ending))),
+            |((k, key), (v, value))| (KeyValue(k, v), KeyValueSpans { key, value }),
         ),
-        |(k, v)| KeyValue(k.to_string(), v),
     )(input)
 }
 
 #[cfg(test)]
 mod tests_key_value {
-    use nom::error::ErrorKind;
+    use crate::parsers::without_spans;
 
     use super::*;
 
+    #[test]
+    fn test_literal_key() {
+        let key = Key::from(["target", "cfg(unix)", "it's", "dependencies"]);
+        assert_eq!(
+            LiteralKey(&key).to_string(),
+            "target.'cfg(unix)'.\"it's\".dependencies"
+        );
+    }
+
+    #[test]
+    fn test_trim_key() {
+        let key = trim_key(" target . 'cfg(unix)'\t.dependencies ");
+        assert_eq!(key, "target.'cfg(unix)'.dependencies");
+        assert_eq!(trim_key("\"a . b\".c"), "\"a . b\".c");
+    }
+
     #[test]
     fn test_key_val_pair() {
         // ToDo: escaped strings are buggy
         assert_eq!(
-            key_val_pair::<(&str, ErrorKind)>("key = true"),
-            Ok(("", KeyValue("key".to_string(), TomlValue::Boolean(true))))
+            key_val_pair::<(&str, ErrorKind)>("key = true").map(without_spans),
+            Ok(("", KeyValue(Key::from(["key"]), TomlValue::Boolean(true))))
         );
         assert_eq!(
-            key_val_pair::<(&str, ErrorKind)>("key = false"),
-            Ok(("", KeyValue("key".to_string(), TomlValue::Boolean(false))))
+            key_val_pair::<(&str, ErrorKind)>("key = false").map(without_spans),
+            Ok(("", KeyValue(Key::from(["key"]), TomlValue::Boolean(false))))
         );
         assert_eq!(
-            key_val_pair::<(&str, ErrorKind)>("key = 12"),
-            Ok(("", KeyValue("key".to_string(), TomlValue::Integer(12))))
+            key_val_pair::<(&str, ErrorKind)>("key = 12").map(without_spans),
+            Ok(("", KeyValue(Key::from(["key"]), TomlValue::Integer(12))))
         );
         assert_eq!(
-            key_val_pair::<(&str, ErrorKind)>("key = 12.2"),
-            Ok(("", KeyValue("key".to_string(), TomlValue::Float(12.2))))
+            key_val_pair::<(&str, ErrorKind)>("key = 12.2").map(without_spans),
+            Ok(("", KeyValue(Key::from(["key"]), TomlValue::Float(12.2))))
         );
         assert_eq!(
-            key_val_pair::<(&str, ErrorKind)>(r#"key = "This is a string""#),
+            key_val_pair::<(&str, ErrorKind)>(r#"key = "This is a string""#).map(without_spans),
             Ok((
                 "",
-                KeyValue(
-                    "key".to_string(),
+                KeyValue(Key::from(["key"]),
                     TomlValue::Str(String::from("This is a string")),
                 )
             ))
         );
     }
+
+    #[test]
+    fn test_dotted_key() {
+        assert_eq!(
+            key::<(&str, ErrorKind)>("apple.taste.sweet = true").map(without_spans),
+            Ok(("= true", Key::from(["apple", "taste", "sweet"])))
+        );
+        assert_eq!(
+            key::<(&str, ErrorKind)>("fruit . color").map(without_spans),
+            Ok(("", Key::from(["fruit", "color"])))
+        );
+    }
+
+    #[test]
+    fn test_quoted_key() {
+        assert_eq!(
+            key::<(&str, ErrorKind)>(r#"site."google.com""#).map(without_spans),
+            Ok(("", Key::from(["site", "google.com"])))
+        );
+        assert_eq!(
+            key::<(&str, ErrorKind)>(r#"target.'cfg(unix)'.dependencies"#).map(without_spans),
+            Ok(("", Key::from(["target", "cfg(unix)", "dependencies"])))
+        );
+        assert_eq!(
+            key::<(&str, ErrorKind)>(r#""tab\tkey""#).map(without_spans),
+            Ok(("", Key::from(["tab\tkey"])))
+        );
+        assert_eq!(
+            key::<(&str, ErrorKind)>("''").map(without_spans),
+            Ok(("", Key::from([""])))
+        );
+    }
+
+    #[test]
+    fn test_key_val_pair_spans() {
+        let (_, (_, spans)) = key_val_pair::<(&str, ErrorKind)>("  a.b = [1, 2] # c\n").unwrap();
+        // Spans are counted from the end of the input
+        assert_eq!(spans.key, RawSpan { start: 17, end: 14 });
+        assert_eq!(spans.value.span, RawSpan { start: 11, end: 5 });
+    }
+
+    #[test]
+    fn test_key_display() {
+        assert_eq!(
+            Key::from(["site", "google.com", "a\"b"]).to_string(),
+            r#"site."google.com"."a\"b""#
+        );
+        assert_eq!(Key::from(["bare-key_1"]).to_string(), "bare-key_1");
+    }
 }
@@ -1,37 +1,42 @@
-use std::collections::HashMap;
-use std::fmt::{Display, Formatter};
-
-use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime};
 use nom::branch::alt;
 use nom::combinator::{map, opt};
-use nom::error::{FromExternalError, ParseError};
+use nom::error::FromExternalError;
 use nom::IResult;
 use nom::multi::many0;
-use nom::sequence::delimited;
+use nom::sequence::{delimited, pair};
 
-use array::{array, Array};
+use array::array;
 use boolean::boolean;
+pub(crate) use cst::cst_document;
 use datetime::datetime;
+pub(crate) use error::{expect, ExpectError, SyntaxError};
 use float::float;
 use integer::integer;
-use key_value::key_val_pair;
-use nom_string::parse_string;
-use table::full_table;
-
-use crate::parsers::inline_table::{inline_table, InlineTable};
-use crate::parsers::key_value::KeyValue;
-use crate::parsers::table::Table;
+pub use key_value::{Key, KeyValue};
+pub(crate) use key_value::{trim_key, LiteralKey};
+pub(crate) use path::{parse_path, DisplayPath, PathSegment};
+pub use table::{Section, TableKind};
+use string::string;
+pub(crate) use table::recovering_root;
+use table::{full_table, root_table};
+
+use crate::parsers::inline_table::inline_table;
 use crate::parsers::whitespace::sp;
+use crate::span::{spanned, NestedSpans, ValueSpan};
+pub(crate) use crate::value::TomlValue;
 
 mod array;
 mod boolean;
 mod comment;
+mod cst;
 mod datetime;
+mod error;
 mod float;
 mod inline_table;
 mod integer;
 mod key_value;
 mod nom_string;
+mod path;
 mod string;
 mod table;
 mod whitespace;
@@ -40,54 +45,51 @@ mod whitespace;
 // ToDo: should common imports be declared at the mod.rs file?
 // ToDo: add documentation
 
-fn toml_value<'a, E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>>(
+/// Parses a value along with its span, and the spans of the values nested inside of it.
+/// If no value starts here at all, the error only says that one was expected.
+fn toml_value<
+    'a,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+>(
     input: &'a str,
-) -> IResult<&'a str, TomlValue, E> {
-    alt((
-        float,
-        integer,
-        boolean,
-        map(parse_string, |s| TomlValue::Str(s)),
-        // datetime,
-        array,
-        inline_table,
-    ))(input)
+) -> IResult<&'a str, (TomlValue, ValueSpan), E> {
+    expect(
+        &["a value"],
+        alt((
+            map(
+                spanned(alt((
+                    // Dates and times start with digits too, so they are tried before numbers
+                    datetime,
+                    float,
+                    integer,
+                    boolean,
+                    map(string, TomlValue::Str),
+                ))),
+                |(value, span)| {
+                    let nested = NestedSpans::None;
+                    (value, ValueSpan { span, nested })
+                },
+            ),
+            array,
+            inline_table,
+        )),
+    )(input)
 }
 
-#[derive(Debug, PartialEq)]
-pub(crate) enum TomlValue {
-    Str(String),
-    Integer(i64),
-    Float(f64),
-    Boolean(bool),
-    OffsetDateTime(DateTime<FixedOffset>),
-    LocalDateTime(NaiveDateTime),
-    LocalDate(NaiveDate),
-    LocalTime(NaiveTime),
-    Array(Box<Array>),
-    InlineTable(Box<InlineTable>),
-}
-
-impl Display for TomlValue {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let mut output = String::new();
-        match self {
-            TomlValue::Str(s) => output.push_str(s),
-            TomlValue::Integer(i) => output.push_str(&i.to_string()),
-            TomlValue::Float(f) => output.push_str(&f.to_string()),
-            TomlValue::Boolean(b) => output.push_str(&b.to_string()),
-            TomlValue::Array(a) => output.push_str(&(*a.to_string())),
-            TomlValue::InlineTable(b) => output.push_str(&b.to_string()),
-            _ => {}
-        }
-        f.write_str(&output)
-    }
+/// Parses a document into its root section and the sections that follow it
+pub(crate) fn cargo_root<
+    'a,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+>(
+    input: &'a str,
+) -> IResult<&'a str, (Section, Vec<Section>), E> {
+    delimited(sp, pair(root_table, many0(full_table)), opt(sp))(input)
 }
 
-fn cargo_root<'a, E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>>(
-    input: &'a str,
-) -> IResult<&'a str, Vec<Table>, E> {
-    delimited(sp, many0(full_table), opt(sp))(input)
+/// Drops the spans from the output of a parser, for tests that only look at what was parsed
+#[cfg(test)]
+pub(crate) fn without_spans<T, S>((rest, (output, _)): (&str, (T, S))) -> (&str, T) {
+    (rest, output)
 }
 
 #[cfg(test)]
@@ -98,12 +100,12 @@ mod tests {
 
     use super::*;
 
-    fn table_vec_to_string(vec: Vec<Table>) -> String {
+    fn table_vec_to_string(vec: Vec<Section>) -> String {
         let mut output = String::new();
         // output.push_str("[");
         for table in vec {
             output.push_str(&table.to_string());
-            output.push_str("\n")
+            output.push('\n')
         }
         // output.push_str("]");
         output
@@ -115,7 +117,6 @@ mod tests {
         println!("{:?}", cargo_root::<(&str, ErrorKind)>(&input));
     }
 
-    // ToDo: Multiline array
     #[test]
     fn test_cargo_lalrpop() {
         let input = read_to_string("assets/cargo_examples/lalrpop.toml").unwrap();
@@ -123,15 +124,14 @@ mod tests {
     }
 
     #[test]
-    // ToDo: inline tables
     fn test_cargo_expand() {
         let input = read_to_string("assets/cargo_examples/cargo-expand.toml").unwrap();
         let res = cargo_root::<(&str, ErrorKind)>(&input);
-        println!("{}", table_vec_to_string(res.unwrap().1));
+        let (_, (_, tables)) = res.unwrap();
+        println!("{}", table_vec_to_string(tables));
     }
 
     #[test]
-    // ToDo: inline comment
     fn test_cargo_nom_locate() {
         let input = read_to_string("assets/cargo_examples/nom-locate.toml").unwrap();
         println!("{:?}", cargo_root::<(&str, ErrorKind)>(&input));
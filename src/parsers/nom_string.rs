@@ -1,12 +1,12 @@
 // Taken from https://github.com/Geal/nom/blob/main/examples/string.rs
-//! This example shows an example of how to parse an escaped string. The
-//! rules for the string are similar to JSON and rust. A string is:
+//! This example shows an example of how to parse an escaped string. It has been
+//! changed to follow the rules of TOML basic strings. A string is:
 //!
 //! - Enclosed by double quotes
 //! - Can contain any raw unescaped code point besides \ and "
-//! - Matches the following escape sequences: \b, \f, \n, \r, \t, \", \\, \/
-//! - Matches code points like Rust: \u{XXXX}, where XXXX can be up to 6
-//!   hex characters
+//! - Matches the following escape sequences: \b, \f, \n, \r, \t, \", \\
+//! - Matches code points as \uXXXX or \UXXXXXXXX, with exactly 4 or 8 hex
+//!   characters
 //! - an escape followed by whitespace consumes all whitespace between the
 //!   escape and the next non-whitespace character
 
@@ -23,40 +23,33 @@ use nom::sequence::{delimited, preceded};
 // first we write parsers for the smallest elements (escaped characters),
 // then combine them into larger parsers.
 
-/// Parse a unicode sequence, of the form u{XXXX}, where XXXX is 1 to 6
+/// Parse a unicode sequence, of the form uXXXX or UXXXXXXXX, where the X are exactly 4 or 8
 /// hexadecimal numerals. We will combine this later with parse_escaped_char
-/// to parse sequences like \u{00AC}.
+/// to parse sequences like \u00AC.
 fn parse_unicode<'a, E>(input: &'a str) -> IResult<&'a str, char, E>
 where
     E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 {
     // `take_while_m_n` parses between `m` and `n` bytes (inclusive) that match
-    // a predicate. `parse_hex` here parses between 1 and 6 hexadecimal numerals.
-    let parse_hex = take_while_m_n(1, 6, |c: char| c.is_ascii_hexdigit());
-
-    // `preceded` takes a prefix parser, and if it succeeds, returns the result
-    // of the body parser. In this case, it parses u{XXXX}.
-    let parse_delimited_hex = preceded(
-        char('u'),
-        // `delimited` is like `preceded`, but it parses both a prefix and a suffix.
-        // It returns the result of the middle parser. In this case, it parses
-        // {XXXX}, where XXXX is 1 to 6 hex numerals, and returns XXXX
-        delimited(char('{'), parse_hex, char('}')),
-    );
+    // a predicate. Setting both to the same number parses exactly that many.
+    let parse_hex = alt((
+        preceded(char('u'), take_while_m_n(4, 4, |c: char| c.is_ascii_hexdigit())),
+        preceded(char('U'), take_while_m_n(8, 8, |c: char| c.is_ascii_hexdigit())),
+    ));
 
     // `map_res` takes the result of a parser and applies a function that returns
     // a Result. In this case we take the hex bytes from parse_hex and attempt to
     // convert them to a u32.
-    let parse_u32 = map_res(parse_delimited_hex, move |hex| u32::from_str_radix(hex, 16));
+    let parse_u32 = map_res(parse_hex, move |hex| u32::from_str_radix(hex, 16));
 
     // map_opt is like map_res, but it takes an Option instead of a Result. If
     // the function returns None, map_opt returns an error. In this case, because
     // not all u32 values are valid unicode code points, we have to fallibly
     // convert to char with from_u32.
-    map_opt(parse_u32, |value| std::char::from_u32(value))(input)
+    map_opt(parse_u32, std::char::from_u32)(input)
 }
 
-/// Parse an escaped character: \n, \t, \r, \u{00AC}, etc.
+/// Parse an escaped character: \n, \t, \r, \u00AC, etc.
 fn parse_escaped_char<'a, E>(input: &'a str) -> IResult<&'a str, char, E>
 where
     E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
@@ -77,7 +70,6 @@ where
             value('\u{08}', char('b')),
             value('\u{0C}', char('f')),
             value('\\', char('\\')),
-            value('/', char('/')),
             value('"', char('"')),
         )),
     )(input)
@@ -183,3 +175,28 @@ pub(crate) fn parse_string<'a, E>(input: &'a str) -> IResult<&'a str, String, E>
 //     );
 //     println!("Result:\n\n{}", result.unwrap().1);
 // }
+
+#[cfg(test)]
+mod tests_nom_string {
+    use nom::error::ErrorKind;
+
+    use super::*;
+
+    #[test]
+    fn test_escaped_string() {
+        assert_eq!(
+            parse_string::<(&str, ErrorKind)>(r#""tab:\t quote:\" slash:\\ end""#),
+            Ok(("", String::from("tab:\t quote:\" slash:\\ end")))
+        );
+    }
+
+    #[test]
+    fn test_unicode_escapes() {
+        assert_eq!(
+            parse_string::<(&str, ErrorKind)>(r#""é \U0001F602 \u001F""#),
+            Ok(("", String::from("é 😂 \u{1F}")))
+        );
+        assert!(parse_string::<(&str, ErrorKind)>(r#""\u{1F602}""#).is_err());
+        assert!(parse_string::<(&str, ErrorKind)>(r#""\uD800""#).is_err());
+    }
+}
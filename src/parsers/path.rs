@@ -0,0 +1,134 @@
+use nom::bytes::complete::tag;
+use nom::character::complete::{char, digit1};
+use nom::combinator::{all_consuming, map, map_res};
+use nom::error::{FromExternalError, ParseError};
+use nom::IResult;
+use nom::multi::{many0, separated_list1};
+use nom::sequence::{delimited, pair};
+
+use crate::parsers::key_value::simple_key;
+use crate::parsers::whitespace::whitespace;
+
+/// A step of a path into a document, either the key of a table or the index of an array
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+fn index<'a, E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>>(
+    input: &'a str,
+) -> IResult<&'a str, usize, E> {
+    delimited(char('['), map_res(digit1, str::parse::<usize>), char(']'))(input)
+}
+
+/// A key segment followed by any number of array indices, such as `bin[0]`
+fn indexed_key<'a, E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>>(
+    input: &'a str,
+) -> IResult<&'a str, Vec<PathSegment>, E> {
+    map(pair(simple_key, many0(index)), |(key, indices)| {
+        let mut segments = vec![PathSegment::Key(key)];
+        segments.extend(indices.into_iter().map(PathSegment::Index));
+        segments
+    })(input)
+}
+
+/// Paths are written like dotted keys, so segments can be quoted, and array indices can
+/// follow any segment: `package.name`, `target."cfg(unix)".dependencies`, `bin[0].name`
+fn path<'a, E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>>(
+    input: &'a str,
+) -> IResult<&'a str, Vec<PathSegment>, E> {
+    map(
+        separated_list1(delimited(whitespace, tag("."), whitespace), indexed_key),
+        |segments| segments.concat(),
+    )(input)
+}
+
+/// Splits a whole path into its segments, or returns `None` if it is not a valid path
+pub(crate) fn parse_path(input: &str) -> Option<Vec<PathSegment>> {
+    all_consuming(delimited(
+        whitespace,
+        path::<nom::error::Error<&str>>,
+        whitespace,
+    ))(input)
+    .ok()
+    .map(|(_, segments)| segments)
+}
+
+/// Shows a path the way [`parse_path`] reads it, like `bin[0].name`
+pub(crate) struct DisplayPath<'a>(pub(crate) &'a [PathSegment]);
+
+impl std::fmt::Display for DisplayPath<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) => {
+                    if i > 0 {
+                        f.write_str(".")?;
+                    }
+                    crate::parsers::key_value::write_key_segment(f, key)?;
+                }
+                PathSegment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests_path {
+    use super::*;
+
+    fn key(s: &str) -> PathSegment {
+        PathSegment::Key(s.to_string())
+    }
+
+    #[test]
+    fn test_dotted_path() {
+        assert_eq!(
+            parse_path("dependencies.serde.version"),
+            Some(vec![key("dependencies"), key("serde"), key("version")])
+        );
+    }
+
+    #[test]
+    fn test_quoted_path() {
+        assert_eq!(
+            parse_path(r#"target."cfg(unix)".dependencies"#),
+            Some(vec![key("target"), key("cfg(unix)"), key("dependencies")])
+        );
+        assert_eq!(
+            parse_path("site.'google.com'"),
+            Some(vec![key("site"), key("google.com")])
+        );
+    }
+
+    #[test]
+    fn test_display_path() {
+        for path in ["package.name", "bin[0].name", "target.\"cfg(unix)\".dependencies", "a[1][2]"] {
+            assert_eq!(DisplayPath(&parse_path(path).unwrap()).to_string(), path);
+        }
+        assert_eq!(DisplayPath(&[]).to_string(), "");
+    }
+
+    #[test]
+    fn test_indexed_path() {
+        assert_eq!(
+            parse_path("bin[0].name"),
+            Some(vec![key("bin"), PathSegment::Index(0), key("name")])
+        );
+        assert_eq!(
+            parse_path("matrix[1][12]"),
+            Some(vec![key("matrix"), PathSegment::Index(1), PathSegment::Index(12)])
+        );
+    }
+
+    #[test]
+    fn test_invalid_path() {
+        assert_eq!(parse_path(""), None);
+        assert_eq!(parse_path("a..b"), None);
+        assert_eq!(parse_path("bin[x]"), None);
+        assert_eq!(parse_path("bin[0"), None);
+        assert_eq!(parse_path("a b"), None);
+    }
+}
@@ -1,47 +1,166 @@
 use nom::branch::alt;
-use nom::bytes::complete::{tag, take_until1};
+use nom::bytes::complete::{tag, take_while};
 use nom::character::complete::{char, line_ending};
-use nom::combinator::{map, opt};
-use nom::error::ParseError;
-use nom::sequence::{pair, preceded};
-use nom::IResult;
+use nom::combinator::{map, opt, peek};
+use nom::error::{ErrorKind, FromExternalError, ParseError};
+use nom::sequence::{delimited, preceded};
+use nom::{Err, IResult};
 
-use crate::parsers::TomlValue;
+use crate::parsers::nom_string::parse_string;
 
 // ToDo: Check for allowed control sequences
-pub(crate) fn basic_string<'a, E: ParseError<&'a str>>(
-    input: &'a str,
-) -> IResult<&'a str, &'a str, E> {
-    preceded(tag("\""), take_until1("\""))(input)
+
+/// Literal strings are taken as is, there are no escapes
+fn literal_string<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    delimited(
+        char('\''),
+        take_while(|c| c != '\'' && c != '\n' && c != '\r'),
+        char('\''),
+    )(input)
+}
+
+/// Splits the body of a multiline string at its closing delimiter. Up to two quotes right
+/// before the delimiter are still part of the string, so `""""a"""""` holds `"a""`.
+/// `escapes` skips the character after a backslash, which cannot end a basic string.
+fn split_multiline<'a>(input: &'a str, delimiter: &str, escapes: bool) -> Option<(&'a str, &'a str)> {
+    let quote = delimiter.chars().next()?;
+    let mut chars = input.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if escapes && c == '\\' {
+            chars.next();
+        } else if input[i..].starts_with(delimiter) {
+            let extra = input[i + delimiter.len()..]
+                .chars()
+                .take_while(|&c| c == quote)
+                .count()
+                .min(2);
+            let end = i + extra;
+            return Some((&input[..end], &input[end + delimiter.len()..]));
+        }
+    }
+    None
+}
+
+/// Replaces the escapes of a multiline basic string. A backslash at the end of a line
+/// removes the line ending and all whitespace up to the next character.
+fn unescape(raw: &str) -> Option<String> {
+    let mut output = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'b' => output.push('\u{08}'),
+            't' => output.push('\t'),
+            'n' => output.push('\n'),
+            'f' => output.push('\u{0C}'),
+            'r' => output.push('\r'),
+            '"' => output.push('"'),
+            '\\' => output.push('\\'),
+            c @ ('u' | 'U') => {
+                let len = if c == 'u' { 4 } else { 8 };
+                let hex: String = (0..len).filter_map(|_| chars.next()).collect();
+                if hex.len() != len {
+                    return None;
+                }
+                output.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+            }
+            ' ' | '\t' | '\r' | '\n' => {
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            }
+            _ => return None,
+        }
+    }
+    Some(output)
 }
 
-// ToDo: line ending backslash
-pub(crate) fn multiline_basic_string<'a, E: ParseError<&'a str>>(
+/// A newline right after the opening delimiter is not part of a multiline string
+fn multiline_basic_string<'a, E: ParseError<&'a str>>(
     input: &'a str,
-) -> IResult<&'a str, &'a str, E> {
-    preceded(pair(tag("\"\"\""), opt(line_ending)), take_until1("\"\"\""))(input)
+) -> IResult<&'a str, String, E> {
+    let (body, _) = preceded(tag("\"\"\""), opt(line_ending))(input)?;
+    split_multiline(body, "\"\"\"", true)
+        .and_then(|(raw, rest)| Some((rest, unescape(raw)?)))
+        .ok_or_else(|| Err::Error(E::from_error_kind(input, ErrorKind::Fail)))
 }
 
-pub(crate) fn literal_string<'a, E: ParseError<&'a str>>(
+fn multiline_literal_string<'a, E: ParseError<&'a str>>(
     input: &'a str,
-) -> IResult<&'a str, &'a str, E> {
-    preceded(char('\''), take_until1("'"))(input)
+) -> IResult<&'a str, String, E> {
+    let (body, _) = preceded(tag("'''"), opt(line_ending))(input)?;
+    split_multiline(body, "'''", false)
+        .map(|(raw, rest)| (rest, raw.to_string()))
+        .ok_or_else(|| Err::Error(E::from_error_kind(input, ErrorKind::Fail)))
 }
 
-pub(crate) fn multiline_literal_string<'a, E: ParseError<&'a str>>(
+/// Parses any of the four kinds of strings. The multiline ones go first, as `"""` would
+/// otherwise be read as an empty string followed by a quote.
+pub(crate) fn string<'a, E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>>(
     input: &'a str,
-) -> IResult<&'a str, &'a str, E> {
-    preceded(pair(tag("'''"), opt(line_ending)), take_until1("'''"))(input)
+) -> IResult<&'a str, String, E> {
+    alt((
+        multiline_basic_string,
+        multiline_literal_string,
+        preceded(peek(char('"')), parse_string),
+        map(literal_string, String::from),
+    ))(input)
 }
 
-pub(crate) fn string<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, TomlValue, E> {
-    map(
-        alt((
-            basic_string,
-            multiline_basic_string,
-            literal_string,
-            multiline_literal_string,
-        )),
-        |s| TomlValue::Str(s.to_string()),
-    )(input)
+#[cfg(test)]
+mod tests_string {
+    use nom::error::ErrorKind;
+
+    use super::*;
+
+    #[test]
+    fn test_literal_string() {
+        assert_eq!(
+            string::<(&str, ErrorKind)>(r"'C:\Users\nodejs\templates' # path"),
+            Ok((" # path", String::from(r"C:\Users\nodejs\templates")))
+        );
+        assert!(string::<(&str, ErrorKind)>("'no\nnewlines'").is_err());
+    }
+
+    #[test]
+    fn test_multiline_literal_string() {
+        assert_eq!(
+            string::<(&str, ErrorKind)>("'''\nThe first newline is\ntrimmed in raw strings.\n'''"),
+            Ok(("", String::from("The first newline is\ntrimmed in raw strings.\n")))
+        );
+        assert_eq!(
+            string::<(&str, ErrorKind)>("''''That,' she said, 'is still pointless.''''"),
+            Ok(("", String::from("'That,' she said, 'is still pointless.'")))
+        );
+    }
+
+    #[test]
+    fn test_multiline_basic_string() {
+        assert_eq!(
+            string::<(&str, ErrorKind)>("\"\"\"\nRoses are red\nViolets are \\\"blue\\\"\"\"\""),
+            Ok(("", String::from("Roses are red\nViolets are \"blue\"")))
+        );
+        assert_eq!(
+            string::<(&str, ErrorKind)>("\"\"\"The quick brown \\\n\n    fox jumps.\"\"\""),
+            Ok(("", String::from("The quick brown fox jumps.")))
+        );
+        assert_eq!(
+            string::<(&str, ErrorKind)>("\"\"\"\"This,\" she said, \"is just a pointless statement.\"\"\"\""),
+            Ok(("", String::from("\"This,\" she said, \"is just a pointless statement.\"")))
+        );
+    }
+
+    #[test]
+    fn test_unterminated_multiline_string() {
+        assert!(multiline_basic_string::<(&str, ErrorKind)>("\"\"\"no end\"\"").is_err());
+        assert!(multiline_literal_string::<(&str, ErrorKind)>("'''no end''").is_err());
+    }
+
+    #[test]
+    fn test_empty_strings() {
+        assert_eq!(string::<(&str, ErrorKind)>(r#""""#), Ok(("", String::new())));
+        assert_eq!(string::<(&str, ErrorKind)>("''"), Ok(("", String::new())));
+        assert_eq!(string::<(&str, ErrorKind)>("''''''"), Ok(("", String::new())));
+    }
 }
@@ -1,113 +1,541 @@
-use std::fmt::{Display, format, Formatter};
+use std::fmt::{Display, Formatter};
 
 use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::character::complete::line_ending;
-use nom::combinator::{eof, map, opt, peek};
-use nom::error::{FromExternalError, ParseError};
+use nom::character::complete::{char, line_ending};
+use nom::combinator::{cut, eof, map, peek, recognize};
+use nom::error::{context, FromExternalError, ParseError};
 use nom::IResult;
-use nom::multi::{many0, many1, separated_list0, separated_list1};
-use nom::sequence::{delimited, pair, preceded, separated_pair, terminated, tuple};
+use nom::multi::many0;
+use nom::sequence::{delimited, pair, preceded, terminated};
 
-use crate::parsers::{toml_value, TomlValue};
-use crate::parsers::comment::comment;
-use crate::parsers::key_value::{key, key_val_pair, KeyValue};
-use crate::parsers::whitespace::{sp, whitespace};
+use crate::emit::write_key_value;
+use crate::parsers::{expect, ExpectError, SyntaxError};
+use crate::parsers::comment::{comment, line_end};
+use crate::parsers::key_value::{key, key_val_pair, Key, KeyValue};
+use crate::parsers::whitespace::whitespace;
+use crate::span::{spanned, HeaderSpans, KeyValueSpans, RawSpan, SectionSpans};
 
-// ToDo: Test for inline comment on table headers
+/// Tables are either standard tables, defined by a `[header]`, or elements of an array of
+/// tables, each one defined by repeating the same `[[header]]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableKind {
+    Standard,
+    ArrayOfTables,
+}
+
+fn standard_table_header<
+    'a,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+>(
+    input: &'a str,
+) -> IResult<&'a str, (Key, RawSpan), E> {
+    preceded(
+        char('['),
+        cut(terminated(expect(&["a key"], key), expect(&["`]`"], char(']')))),
+    )(input)
+}
+
+fn array_table_header<
+    'a,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+>(
+    input: &'a str,
+) -> IResult<&'a str, (Key, RawSpan), E> {
+    preceded(
+        tag("[["),
+        cut(terminated(expect(&["a key"], key), expect(&["`]]`"], tag("]]")))),
+    )(input)
+}
+
+/// A table header without the rest of its line
+pub(crate) fn table_header_key<
+    'a,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+>(
+    input: &'a str,
+) -> IResult<&'a str, ((Key, TableKind), HeaderSpans), E> {
+    map(
+        spanned(alt((
+            map(array_table_header, |h| (h, TableKind::ArrayOfTables)),
+            map(standard_table_header, |h| (h, TableKind::Standard)),
+        ))),
+        |(((header, key), kind), span)| ((header, kind), HeaderSpans { key, header: span }),
+    )(input)
+}
+
+/// A whole header line, which may be indented
+fn table_header<
+    'a,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+>(
+    input: &'a str,
+) -> IResult<&'a str, ((Key, TableKind), HeaderSpans), E> {
+    context(
+        "table header",
+        delimited(
+            whitespace,
+            table_header_key,
+            cut(preceded(whitespace, expect(&["end of line"], line_end))),
+        ),
+    )(input)
+}
 
-fn table_header<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
-    terminated(delimited(tag("["), key, tag("]")), alt((line_ending, eof)))(input)
+/// Lines without a key value pair, that are either empty or only hold a comment
+fn empty_line<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
+    recognize(pair(whitespace, alt((comment, line_ending))))(input)
 }
 
 /// Returns all of the key value pairs belonging to a table
 /// Key value pairs can be separated and delimited by a variable number of
-/// newlines (`\n` or `\r\n`). The last key pair can also have no newline
+/// empty lines and comments. The last key pair can also have no newline
 ///  or eof as that can be taken by `table` parser.
-fn table_body<'a, E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>>(
+fn table_body<
+    'a,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+>(
     input: &'a str,
-) -> IResult<&'a str, Vec<KeyValue>, E> {
-    terminated(
-        many0(preceded(many0(alt((comment, line_ending))), key_val_pair)),
-        many0(alt((comment, line_ending))),
+) -> IResult<&'a str, (Vec<KeyValue>, Vec<KeyValueSpans>), E> {
+    map(
+        terminated(
+            many0(preceded(many0(empty_line), key_val_pair)),
+            many0(empty_line),
+        ),
+        |key_values| key_values.into_iter().unzip(),
     )(input)
 }
 
-#[derive(Debug, PartialEq)]
-pub(crate) struct Table {
-    pub(crate) header: String,
+/// What has to follow the key value pairs of a section. Anything else is a mistake in the
+/// document rather than the end of it, so it is reported right away.
+fn section_end<
+    'a,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+>(
+    input: &'a str,
+) -> IResult<&'a str, &'a str, E> {
+    cut(expect(
+        &["a key", "a table header"],
+        peek(alt((eof, recognize(table_header)))),
+    ))(input)
+}
+
+/// A table header together with the key value pairs that follow it, exactly as they appear
+/// in the document. The tables they define are only put together by [`Document`](crate::Document).
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub(crate) header: Key,
+    pub(crate) kind: TableKind,
     pub(crate) key_val_vec: Vec<KeyValue>,
+    pub(crate) spans: SectionSpans,
 }
 
-impl Display for Table {
+/// Sections are equal if they define the same keys, wherever they are in the input
+impl PartialEq for Section {
+    fn eq(&self, other: &Self) -> bool {
+        self.header == other.header
+            && self.kind == other.kind
+            && self.key_val_vec == other.key_val_vec
+    }
+}
+
+impl Section {
+    /// The key between the brackets of the table header
+    pub fn header(&self) -> &Key {
+        &self.header
+    }
+
+    /// Whether the table was defined by a `[header]` or a `[[header]]`
+    pub fn kind(&self) -> TableKind {
+        self.kind
+    }
+
+    /// The key value pairs of the table in the order they were defined
+    pub fn key_values(&self) -> &[KeyValue] {
+        &self.key_val_vec
+    }
+}
+
+/// Writes the section back out as TOML, its header followed by its key value pairs
+impl Display for Section {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let mut output = String::new();
-        output.push_str(&format!("Table: {}\n", &self.header));
-        for key_val in &self.key_val_vec {
-            output.push_str(&format!("\t{}: {}\n", key_val.0, key_val.1));
+        match (self.header.is_empty(), self.kind) {
+            (true, _) => {}
+            (false, TableKind::Standard) => writeln!(f, "[{}]", self.header)?,
+            (false, TableKind::ArrayOfTables) => writeln!(f, "[[{}]]", self.header)?,
         }
-
-        f.write_str(&output)
+        for KeyValue(key, value) in &self.key_val_vec {
+            write_key_value(f, key, value)?;
+            writeln!(f)?;
+        }
+        Ok(())
     }
 }
 
+/// The root section starts at the beginning of the document and ends just before the first table
+/// header (or EOF). It is nameless, so its `header` is left empty.
+pub(crate) fn root_table<
+    'a,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+>(
+    input: &'a str,
+) -> IResult<&'a str, Section, E> {
+    map(
+        terminated(table_body, section_end),
+        |(key_val_vec, key_values)| Section {
+            header: Key::default(),
+            kind: TableKind::Standard,
+            key_val_vec,
+            spans: SectionSpans {
+                header: None,
+                key_values,
+            },
+        },
+    )(input)
+}
+
 // ToDo: does terminate consume the termination slice?
 pub(crate) fn full_table<
     'a,
-    E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
+    E: ExpectError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
 >(
     input: &'a str,
-) -> IResult<&'a str, Table, E> {
+) -> IResult<&'a str, Section, E> {
     map(
-        terminated(
-            pair(table_header, table_body),
-            peek(alt((eof, table_header))),
-        ),
-        |(header, key_val_vec)| {
-            let header = header.to_string();
-            Table {
-                header,
-                key_val_vec,
-            }
+        terminated(pair(table_header, table_body), section_end),
+        |(((header, kind), header_spans), (key_val_vec, key_values))| Section {
+            header,
+            kind,
+            key_val_vec,
+            spans: SectionSpans {
+                header: Some(header_spans),
+                key_values,
+            },
         },
     )(input)
 }
 
+/// Turns the error of a parser that started at `input` into the error and the input to
+/// carry on with. That is the line after the error, unless the parser already went past a
+/// line ending and failed at the start of a line, like an inline table that is not closed.
+/// Then that line is kept, as it is most likely fine.
+fn recover<'a>(input: &'a str, error: nom::Err<SyntaxError<'a>>) -> (SyntaxError<'a>, &'a str) {
+    let error = match error {
+        nom::Err::Error(e) | nom::Err::Failure(e) => e,
+        nom::Err::Incomplete(_) => SyntaxError {
+            input: &input[input.len()..],
+            expected: vec![],
+            context: vec![],
+            invalid: None,
+        },
+    };
+    let consumed = &input[..input.len() - error.input.len()];
+    let rest = match error.input.find('\n') {
+        _ if consumed.ends_with('\n') => error.input,
+        Some(end) => &error.input[end + 1..],
+        None => "",
+    };
+    (error, rest)
+}
+
+/// Where to carry on after an error in a key value pair that starts at `input` and failed at
+/// `error`. If the error is inside an array that spans lines, the lines up to the one that
+/// closes it are skipped too, as reading them as key value pairs would only report more
+/// errors. A line that starts with `[` is taken for the next header and ends the array. Inline
+/// tables end with their line, as they cannot span lines.
+fn resynchronize<'a>(input: &'a str, error: &'a str) -> &'a str {
+    let failed_at = input.len() - error.len();
+    let mut open: Vec<char> = vec![];
+    let mut quote: Option<&str> = None;
+    let mut i = 0;
+    while i < input.len() {
+        let rest = &input[i..];
+        if i >= failed_at && open.is_empty() && quote.is_none() {
+            if i == failed_at && input[..i].ends_with('\n') {
+                return rest;
+            }
+            return rest.find('\n').map_or("", |end| &rest[end + 1..]);
+        }
+        let c = rest.chars().next().unwrap_or_default();
+        if let Some(q) = quote {
+            if rest.starts_with(q) {
+                quote = None;
+                i += q.len();
+                continue;
+            }
+            if c == '\\' && q.starts_with('"') {
+                i += 1 + rest[1..].chars().next().map_or(0, char::len_utf8);
+                continue;
+            }
+            if c == '\n' && q.len() == 1 {
+                quote = None;
+            }
+            i += c.len_utf8();
+            continue;
+        }
+        match c {
+            '"' | '\'' => {
+                let q = ["\"\"\"", "\'\'\'", "\"", "\'"]
+                    .into_iter()
+                    .find(|q| rest.starts_with(q))
+                    .unwrap_or_default();
+                quote = Some(q);
+                i += q.len();
+                continue;
+            }
+            '#' => {
+                i += rest.find('\n').unwrap_or(rest.len());
+                continue;
+            }
+            '[' | '{' => open.push(c),
+            ']' | '}' => {
+                open.pop();
+            }
+            '\n' => {
+                if let Some(brace) = open.iter().position(|&c| c == '{') {
+                    open.truncate(brace);
+                }
+                let next = &input[i + 1..];
+                if i >= failed_at && (open.is_empty() || next.starts_with('[')) {
+                    return next;
+                }
+            }
+            _ => {}
+        }
+        i += c.len_utf8();
+    }
+    ""
+}
+
+/// Parses a document line by line, carrying on after syntax errors instead of stopping at
+/// the first one. A line with an error is skipped. A header with an error skips the whole
+/// table, as there is no telling where its key value pairs belong, though they are still
+/// parsed to report their errors. Returns the sections that could be parsed and every error.
+pub(crate) fn recovering_root(input: &str) -> (Section, Vec<Section>, Vec<SyntaxError<'_>>) {
+    let mut root = Section {
+        header: Key::default(),
+        kind: TableKind::Standard,
+        key_val_vec: vec![],
+        spans: SectionSpans::default(),
+    };
+    let mut sections: Vec<Section> = vec![];
+    let mut errors = vec![];
+    // Whether the key value pairs that follow belong to a header that had an error
+    let mut skipping = false;
+    let mut rest = input;
+
+    loop {
+        if let Ok((next, _)) = many0(empty_line::<SyntaxError>)(rest) {
+            rest = next;
+        }
+        if rest.trim_start_matches([' ', '\t', '\r', '\n']).is_empty() {
+            break;
+        }
+
+        let is_header = peek(preceded(whitespace, char::<_, SyntaxError>('[')))(rest).is_ok();
+        if is_header {
+            match table_header::<SyntaxError>(rest) {
+                Ok((next, ((header, kind), header_spans))) => {
+                    sections.push(Section {
+                        header,
+                        kind,
+                        key_val_vec: vec![],
+                        spans: SectionSpans {
+                            header: Some(header_spans),
+                            key_values: vec![],
+                        },
+                    });
+                    skipping = false;
+                    rest = next;
+                }
+                Err(e) => {
+                    let (error, next) = recover(rest, e);
+                    errors.push(error);
+                    skipping = true;
+                    rest = next;
+                }
+            }
+            continue;
+        }
+
+        match expect(&["a key", "a table header"], key_val_pair::<SyntaxError>)(rest) {
+            Ok((next, (key_val, spans))) => {
+                if !skipping {
+                    let section = sections.last_mut().unwrap_or(&mut root);
+                    section.key_val_vec.push(key_val);
+                    section.spans.key_values.push(spans);
+                }
+                rest = next;
+            }
+            Err(e) => {
+                let (error, _) = recover(rest, e);
+                let next = resynchronize(rest, error.input);
+                errors.push(error);
+                rest = next;
+            }
+        }
+    }
+
+    (root, sections, errors)
+}
+
 #[cfg(test)]
 mod tests_table {
     use std::fs::read_to_string;
 
-    use nom::character::complete::digit1;
     use nom::error::ErrorKind;
 
-    use crate::parsers::array::Array;
-    use crate::parsers::TomlValue;
+    use crate::parsers::{without_spans, TomlValue};
 
     use super::*;
 
+    #[test]
+    fn test_recovering_root() {
+        let input = "a = 1\n]\nb = [1,\n  2x,\n]\n[t]]\nc = 3\n[u]\nd = 4\n  ";
+        let (root, sections, errors) = recovering_root(input);
+        assert_eq!(root.key_val_vec, vec![KeyValue(Key::from(["a"]), TomlValue::Integer(1))]);
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0].header, Key::from(["u"]));
+        assert_eq!(
+            sections[0].key_val_vec,
+            vec![KeyValue(Key::from(["d"]), TomlValue::Integer(4))]
+        );
+        let errors: Vec<(usize, Vec<&str>)> = errors
+            .iter()
+            .map(|e| (input.len() - e.input.len(), e.expected.clone()))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (6, vec!["a key", "a table header"]),
+                (19, vec!["`,`", "`]`"]),
+                (27, vec!["end of line"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resynchronize() {
+        let input = "b = [\n  1x,\n  \"]\", # ]\n  [2, 3],\n]\nc = 1\n";
+        assert_eq!(resynchronize(input, &input[8..]), "c = 1\n");
+        let input = "b = [1,\n  2x\n[t]\nc = 1\n";
+        assert_eq!(resynchronize(input, &input[11..]), "[t]\nc = 1\n");
+        let input = "b = { c = [1x }\nd = 1\n";
+        assert_eq!(resynchronize(input, &input[12..]), "d = 1\n");
+        let input = "b = { c = 1\nd = 1\n";
+        assert_eq!(resynchronize(input, &input[12..]), "d = 1\n");
+    }
+
     #[test]
     fn test_table_header() {
         assert_eq!(
-            table_header::<(&str, ErrorKind)>("[table]\n"),
-            Ok(("", "table"))
+            table_header::<(&str, ErrorKind)>("[table]\n").map(without_spans),
+            Ok(("", (Key::from(["table"]), TableKind::Standard)))
         )
     }
 
+    #[test]
+    fn test_array_table_header() {
+        assert_eq!(
+            table_header::<(&str, ErrorKind)>("[[bin]]\n").map(without_spans),
+            Ok(("", (Key::from(["bin"]), TableKind::ArrayOfTables)))
+        );
+        assert_eq!(
+            table_header::<(&str, ErrorKind)>("[[fruit.variety]]").map(without_spans),
+            Ok(("", (Key::from(["fruit", "variety"]), TableKind::ArrayOfTables)))
+        );
+    }
+
+    #[test]
+    fn test_array_of_tables() {
+        let input = "[[bin]]\nname = \"manager\"\n\n[[bin]]\nname = \"other\"\n";
+        assert_eq!(
+            full_table::<(&str, ErrorKind)>(input),
+            Ok((
+                "[[bin]]\nname = \"other\"\n",
+                Section {
+                    header: Key::from(["bin"]),
+                    kind: TableKind::ArrayOfTables,
+                    key_val_vec: vec![KeyValue(Key::from(["name"]),
+                        TomlValue::Str("manager".to_string()),
+                    )],
+                    spans: SectionSpans::default(),
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_inline_comments() {
+        let input = "[table] # header comment\nkey = 1 # value comment\n  # indented\n\t\nother = 2 # last line";
+        assert_eq!(
+            full_table::<(&str, ErrorKind)>(input),
+            Ok((
+                "",
+                Section {
+                    header: Key::from(["table"]),
+                    kind: TableKind::Standard,
+                    key_val_vec: vec![
+                        KeyValue(Key::from(["key"]), TomlValue::Integer(1)),
+                        KeyValue(Key::from(["other"]), TomlValue::Integer(2)),
+                    ],
+                    spans: SectionSpans::default(),
+                }
+            ))
+        );
+    }
+
     #[test]
     fn test_table_body() {
         let input = read_to_string("assets/table-no-header.toml").unwrap();
         assert_eq!(
-            table_body::<(&str, ErrorKind)>(&input),
+            table_body::<(&str, ErrorKind)>(&input).map(without_spans),
             Ok((
                 "",
                 vec![
-                    KeyValue("key1".to_string(), TomlValue::Boolean(false)),
-                    KeyValue("key2".to_string(), TomlValue::Integer(123)),
+                    KeyValue(Key::from(["key1"]), TomlValue::Boolean(false)),
+                    KeyValue(Key::from(["key2"]), TomlValue::Integer(123)),
                 ]
             ))
         )
     }
 
+    #[test]
+    fn test_root_table() {
+        let input = read_to_string("assets/root-table.toml").unwrap();
+        assert_eq!(
+            root_table::<(&str, ErrorKind)>(&input),
+            Ok((
+                "[table-1]\nkey1 = 1.23\n",
+                Section {
+                    header: Key::default(),
+                    kind: TableKind::Standard,
+                    key_val_vec: vec![
+                        KeyValue(Key::from(["title"]), TomlValue::Str("root".to_string())),
+                        KeyValue(Key::from(["key1"]), TomlValue::Integer(1)),
+                    ],
+                    spans: SectionSpans::default(),
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_empty_root_table() {
+        let input = read_to_string("assets/single-table.toml").unwrap();
+        assert_eq!(
+            root_table::<(&str, ErrorKind)>(&input),
+            Ok((
+                input.as_str(),
+                Section {
+                    header: Key::default(),
+                    kind: TableKind::Standard,
+                    key_val_vec: vec![],
+                    spans: SectionSpans::default(),
+                }
+            ))
+        );
+    }
+
     #[test]
     fn test_empty_table() {
         let input = read_to_string("assets/empty-table.toml").unwrap();
@@ -115,9 +543,11 @@ mod tests_table {
             full_table::<(&str, ErrorKind)>(&input),
             Ok((
                 "",
-                Table {
-                    header: "table-1".to_string(),
+                Section {
+                    header: Key::from(["table-1"]),
+                    kind: TableKind::Standard,
                     key_val_vec: vec![],
+                    spans: SectionSpans::default(),
                 }
             ))
         );
@@ -131,9 +561,11 @@ mod tests_table {
             full_table::<(&str, ErrorKind)>(&input),
             Ok((
                 "",
-                Table {
-                    header: "table-1".to_string(),
-                    key_val_vec: vec![KeyValue("key1".to_string(), TomlValue::Float(1.23))],
+                Section {
+                    header: Key::from(["table-1"]),
+                    kind: TableKind::Standard,
+                    key_val_vec: vec![KeyValue(Key::from(["key1"]), TomlValue::Float(1.23))],
+                    spans: SectionSpans::default(),
                 }
             ))
         )
@@ -147,9 +579,11 @@ mod tests_table {
             full_table::<(&str, ErrorKind)>(&input),
             Ok((
                 "",
-                Table {
-                    header: "table".to_string(),
-                    key_val_vec: vec![KeyValue("key".to_string(), TomlValue::Integer(123))],
+                Section {
+                    header: Key::from(["table"]),
+                    kind: TableKind::Standard,
+                    key_val_vec: vec![KeyValue(Key::from(["key"]), TomlValue::Integer(123))],
+                    spans: SectionSpans::default(),
                 }
             ))
         )
@@ -163,12 +597,13 @@ mod tests_table {
             full_table::<(&str, ErrorKind)>(&input),
             Ok((
                 "",
-                Table {
-                    header: "table-1".to_string(),
-                    key_val_vec: vec![KeyValue(
-                        "key1".to_string(),
+                Section {
+                    header: Key::from(["table-1"]),
+                    kind: TableKind::Standard,
+                    key_val_vec: vec![KeyValue(Key::from(["key1"]),
                         TomlValue::Str(String::from("this is a string")),
                     )],
+                    spans: SectionSpans::default(),
                 }
             ))
         )
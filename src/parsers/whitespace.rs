@@ -20,10 +20,7 @@ pub(crate) fn sp<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'a
 
 #[cfg(test)]
 mod tests_whitespace {
-    use nom::character::complete::line_ending;
-    use nom::Err;
     use nom::error::ErrorKind;
-    use nom::error::ErrorKind::CrLf;
 
     use super::*;
 
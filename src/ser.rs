@@ -0,0 +1,854 @@
+use serde::ser::{
+    Error as _, Impossible, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
+    SerializeTuple, SerializeTupleStruct, SerializeTupleVariant,
+};
+use serde::{Serialize, Serializer};
+
+use crate::de::SerdeError;
+use crate::error::Error;
+use crate::parsers::{DisplayPath, PathSegment};
+use crate::value::{Table, TomlValue};
+
+/// Serializes `value` as a TOML document. It has to serialize as a table, like a struct or a
+/// map does.
+///
+/// Plain values are written before the subtables of their table, and sequences of structs
+/// or maps become `[[array]]` tables. `None` fields and map entries are left out. Values TOML
+/// has no way to write, like `None` in a sequence, `()` or map keys that are not strings,
+/// are errors that tell the path of the value.
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, Error> {
+    let into_error = |e: SerdeError| {
+        let path = e.path.unwrap_or_default();
+        Error::serialize(e.message, DisplayPath(&path).to_string())
+    };
+    match value.serialize(ValueSerializer { path: vec![] }) {
+        Ok(Some(TomlValue::Table(table))) => Ok(table.to_string()),
+        Ok(Some(value)) => Err(into_error(SerdeError::custom(format!(
+            "only a table can be written as a document, found {}",
+            value.type_name()
+        )))),
+        Ok(None) => Err(into_error(SerdeError::custom(
+            "only a table can be written as a document, found `None`",
+        ))),
+        Err(e) => Err(into_error(e)),
+    }
+}
+
+impl serde::ser::Error for SerdeError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        SerdeError {
+            message: msg.to_string(),
+            path: None,
+        }
+    }
+}
+
+fn child_path(path: &[PathSegment], segment: PathSegment) -> Vec<PathSegment> {
+    let mut path = path.to_vec();
+    path.push(segment);
+    path
+}
+
+/// Serializes a value into a [`TomlValue`], or into `None` for a missing optional value,
+/// which only a table can leave out
+struct ValueSerializer {
+    path: Vec<PathSegment>,
+}
+
+impl ValueSerializer {
+    fn child(&self, segment: PathSegment) -> Self {
+        ValueSerializer {
+            path: child_path(&self.path, segment),
+        }
+    }
+
+    fn seq(self, len: Option<usize>) -> SeqSerializer {
+        SeqSerializer {
+            values: Vec::with_capacity(len.unwrap_or(0)),
+            parent: self,
+            variant: None,
+        }
+    }
+
+    fn table(self) -> TableSerializer {
+        TableSerializer {
+            table: Table::new(),
+            key: None,
+            parent: self,
+            variant: None,
+        }
+    }
+
+    fn unsupported(&self, what: &str) -> SerdeError {
+        SerdeError::custom(format!("{} cannot be written as TOML", what)).at(&self.path)
+    }
+}
+
+/// Variants that hold data are written as a table with a single key, the name of the variant
+fn variant_table(variant: &'static str, value: TomlValue) -> TomlValue {
+    let mut table = Table::new();
+    table.insert(variant, value);
+    TomlValue::Table(table)
+}
+
+impl Serializer for ValueSerializer {
+    type Ok = Option<TomlValue>;
+    type Error = SerdeError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = TableSerializer;
+    type SerializeStruct = TableSerializer;
+    type SerializeStructVariant = TableSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, SerdeError> {
+        Ok(Some(TomlValue::Boolean(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, SerdeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, SerdeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, SerdeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, SerdeError> {
+        Ok(Some(TomlValue::Integer(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, SerdeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, SerdeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, SerdeError> {
+        self.serialize_i64(v.into())
+    }
+
+    /// TOML integers are 64 bit signed integers, larger ones are an error
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, SerdeError> {
+        match i64::try_from(v) {
+            Ok(v) => self.serialize_i64(v),
+            Err(_) => Err(SerdeError::custom(format!(
+                "{} is out of range for a TOML integer",
+                v
+            ))
+            .at(&self.path)),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, SerdeError> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, SerdeError> {
+        Ok(Some(TomlValue::Float(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, SerdeError> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, SerdeError> {
+        Ok(Some(TomlValue::Str(v.to_string())))
+    }
+
+    /// Bytes are written as an array of integers
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, SerdeError> {
+        let values = v.iter().map(|b| TomlValue::Integer((*b).into())).collect();
+        Ok(Some(TomlValue::Array(values)))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, SerdeError> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, SerdeError> {
+        Err(self.unsupported("`()`"))
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Self::Ok, SerdeError> {
+        Err(self.unsupported(&format!("unit struct `{}`", name)))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, SerdeError> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, SerdeError> {
+        let child = self.child(PathSegment::Key(variant.to_string()));
+        let path = child.path.clone();
+        match value.serialize(child).map_err(|e| e.at(&path))? {
+            Some(value) => Ok(Some(variant_table(variant, value))),
+            None => Err(self.unsupported("`None` as the value of an enum variant")),
+        }
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, SerdeError> {
+        Ok(self.seq(len))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, SerdeError> {
+        Ok(self.seq(Some(len)))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, SerdeError> {
+        Ok(self.seq(Some(len)))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerdeError> {
+        let mut seq = self
+            .child(PathSegment::Key(variant.to_string()))
+            .seq(Some(len));
+        seq.variant = Some(variant);
+        Ok(seq)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, SerdeError> {
+        Ok(self.table())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, SerdeError> {
+        Ok(self.table())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerdeError> {
+        let mut table = self.child(PathSegment::Key(variant.to_string())).table();
+        table.variant = Some(variant);
+        Ok(table)
+    }
+}
+
+/// Collects the elements of anything serialized as a sequence into an array
+struct SeqSerializer {
+    values: Vec<TomlValue>,
+    parent: ValueSerializer,
+    /// The name of the variant, for tuple variants
+    variant: Option<&'static str>,
+}
+
+impl SeqSerializer {
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let child = self.parent.child(PathSegment::Index(self.values.len()));
+        let path = child.path.clone();
+        match value.serialize(child).map_err(|e| e.at(&path))? {
+            Some(value) => {
+                self.values.push(value);
+                Ok(())
+            }
+            None => Err(SerdeError::custom("`None` cannot be an element of an array").at(&path)),
+        }
+    }
+
+    fn finish(self) -> Result<Option<TomlValue>, SerdeError> {
+        let array = TomlValue::Array(self.values);
+        Ok(Some(match self.variant {
+            Some(variant) => variant_table(variant, array),
+            None => array,
+        }))
+    }
+}
+
+impl SerializeSeq for SeqSerializer {
+    type Ok = Option<TomlValue>;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, SerdeError> {
+        self.finish()
+    }
+}
+
+impl SerializeTuple for SeqSerializer {
+    type Ok = Option<TomlValue>;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, SerdeError> {
+        self.finish()
+    }
+}
+
+impl SerializeTupleStruct for SeqSerializer {
+    type Ok = Option<TomlValue>;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, SerdeError> {
+        self.finish()
+    }
+}
+
+impl SerializeTupleVariant for SeqSerializer {
+    type Ok = Option<TomlValue>;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, SerdeError> {
+        self.finish()
+    }
+}
+
+/// Collects the entries of maps and the fields of structs into a table
+struct TableSerializer {
+    table: Table,
+    /// The key of the map entry whose value comes next
+    key: Option<String>,
+    parent: ValueSerializer,
+    /// The name of the variant, for struct variants
+    variant: Option<&'static str>,
+}
+
+impl TableSerializer {
+    /// Entries whose value is `None` are left out
+    fn entry<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<(), SerdeError> {
+        let child = self.parent.child(PathSegment::Key(key.clone()));
+        let path = child.path.clone();
+        if let Some(value) = value.serialize(child).map_err(|e| e.at(&path))? {
+            self.table.insert(key, value);
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<TomlValue>, SerdeError> {
+        let table = TomlValue::Table(self.table);
+        Ok(Some(match self.variant {
+            Some(variant) => variant_table(variant, table),
+            None => table,
+        }))
+    }
+}
+
+impl SerializeMap for TableSerializer {
+    type Ok = Option<TomlValue>;
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        let key = key
+            .serialize(KeySerializer)
+            .map_err(|e| e.at(&self.parent.path))?;
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let key = self.key.take().expect("a key is serialized before its value");
+        self.entry(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, SerdeError> {
+        self.finish()
+    }
+}
+
+impl SerializeStruct for TableSerializer {
+    type Ok = Option<TomlValue>;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.entry(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, SerdeError> {
+        self.finish()
+    }
+}
+
+impl SerializeStructVariant for TableSerializer {
+    type Ok = Option<TomlValue>;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.entry(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, SerdeError> {
+        self.finish()
+    }
+}
+
+/// Serializes the keys of maps, which TOML only allows to be strings. Enums with unit
+/// variants count as strings, like they do for values.
+struct KeySerializer;
+
+fn key_error(found: &str) -> SerdeError {
+    SerdeError::custom(format!("map keys have to be strings, found {}", found))
+}
+
+impl Serializer for KeySerializer {
+    type Ok = String;
+    type Error = SerdeError;
+    type SerializeSeq = Impossible<String, SerdeError>;
+    type SerializeTuple = Impossible<String, SerdeError>;
+    type SerializeTupleStruct = Impossible<String, SerdeError>;
+    type SerializeTupleVariant = Impossible<String, SerdeError>;
+    type SerializeMap = Impossible<String, SerdeError>;
+    type SerializeStruct = Impossible<String, SerdeError>;
+    type SerializeStructVariant = Impossible<String, SerdeError>;
+
+    fn serialize_str(self, v: &str) -> Result<String, SerdeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, SerdeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, SerdeError> {
+        Ok(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<String, SerdeError> {
+        Err(key_error("a boolean"))
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<String, SerdeError> {
+        Err(key_error("an integer"))
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<String, SerdeError> {
+        Err(key_error("an integer"))
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<String, SerdeError> {
+        Err(key_error("an integer"))
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<String, SerdeError> {
+        Err(key_error("an integer"))
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<String, SerdeError> {
+        Err(key_error("an integer"))
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<String, SerdeError> {
+        Err(key_error("an integer"))
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<String, SerdeError> {
+        Err(key_error("an integer"))
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<String, SerdeError> {
+        Err(key_error("an integer"))
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<String, SerdeError> {
+        Err(key_error("a float"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<String, SerdeError> {
+        Err(key_error("a float"))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, SerdeError> {
+        Err(key_error("bytes"))
+    }
+
+    fn serialize_none(self) -> Result<String, SerdeError> {
+        Err(key_error("`None`"))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<String, SerdeError> {
+        Err(key_error("an option"))
+    }
+
+    fn serialize_unit(self) -> Result<String, SerdeError> {
+        Err(key_error("`()`"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, SerdeError> {
+        Err(key_error("a unit struct"))
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, SerdeError> {
+        Err(key_error("an enum variant with data"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, SerdeError> {
+        Err(key_error("a sequence"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, SerdeError> {
+        Err(key_error("a tuple"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, SerdeError> {
+        Err(key_error("a tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerdeError> {
+        Err(key_error("an enum variant with data"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, SerdeError> {
+        Err(key_error("a map"))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, SerdeError> {
+        Err(key_error("a struct"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerdeError> {
+        Err(key_error("an enum variant with data"))
+    }
+}
+
+#[cfg(test)]
+mod tests_ser {
+    use std::collections::{BTreeMap, HashMap};
+
+    use serde::Deserialize;
+
+    use crate::{from_str, ErrorKind};
+
+    use super::*;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Manifest {
+        package: Package,
+        dependencies: BTreeMap<String, Dependency>,
+        bin: Vec<Target>,
+        workspace: Option<BTreeMap<String, String>>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Package {
+        metadata: BTreeMap<String, String>,
+        name: String,
+        version: String,
+        authors: Vec<String>,
+        publish: Option<bool>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    #[serde(untagged)]
+    enum Dependency {
+        Version(String),
+        Detailed { version: String, optional: bool },
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Target {
+        name: String,
+        path: Option<String>,
+    }
+
+    fn manifest() -> Manifest {
+        Manifest {
+            package: Package {
+                metadata: BTreeMap::from([("docs".to_string(), "all".to_string())]),
+                name: "manager".to_string(),
+                version: "0.1.0".to_string(),
+                authors: vec!["someone".to_string()],
+                publish: None,
+            },
+            dependencies: BTreeMap::from([
+                ("nom".to_string(), Dependency::Version("7".to_string())),
+                (
+                    "serde".to_string(),
+                    Dependency::Detailed {
+                        version: "1".to_string(),
+                        optional: true,
+                    },
+                ),
+            ]),
+            bin: vec![
+                Target {
+                    name: "manager".to_string(),
+                    path: None,
+                },
+                Target {
+                    name: "other".to_string(),
+                    path: Some("src/other.rs".to_string()),
+                },
+            ],
+            workspace: None,
+        }
+    }
+
+    #[test]
+    fn test_manifest() {
+        assert_eq!(
+            to_string(&manifest()).unwrap(),
+            r#"[package]
+name = "manager"
+version = "0.1.0"
+authors = ["someone"]
+
+[package.metadata]
+docs = "all"
+
+[dependencies]
+nom = "7"
+
+[dependencies.serde]
+version = "1"
+optional = true
+
+[[bin]]
+name = "manager"
+
+[[bin]]
+name = "other"
+path = "src/other.rs"
+"#
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let manifest = manifest();
+        assert_eq!(from_str::<Manifest>(&to_string(&manifest).unwrap()).unwrap(), manifest);
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    #[serde(rename_all = "kebab-case")]
+    enum Source {
+        Registry,
+        Git { url: String },
+        Path(String),
+        Pinned(String, i64),
+    }
+
+    #[test]
+    fn test_enums() {
+        let sources = BTreeMap::from([
+            ("a", Source::Registry),
+            ("b", Source::Git { url: "https://example.com".to_string() }),
+            ("c", Source::Path("../c".to_string())),
+            ("d", Source::Pinned("x".to_string(), 1)),
+        ]);
+        let output = to_string(&sources).unwrap();
+        assert_eq!(
+            output,
+            "a = \"registry\"\n\n[b.git]\nurl = \"https://example.com\"\n\n[c]\npath = \"../c\"\n\n[d]\npinned = [\"x\", 1]\n"
+        );
+        let read: HashMap<String, Source> = from_str(&output).unwrap();
+        assert_eq!(read["b"], sources["b"]);
+        assert_eq!(read["d"], sources["d"]);
+    }
+
+    #[test]
+    fn test_values() {
+        #[derive(Serialize)]
+        struct Values {
+            small: u8,
+            float: f32,
+            letter: char,
+            bytes: serde_bytes_like::Bytes,
+            tuple: (i32, String),
+            empty: Vec<i32>,
+            nested: Vec<Vec<i64>>,
+        }
+        mod serde_bytes_like {
+            pub struct Bytes(pub Vec<u8>);
+
+            impl serde::Serialize for Bytes {
+                fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                    s.serialize_bytes(&self.0)
+                }
+            }
+        }
+        let values = Values {
+            small: 7,
+            float: 1.5,
+            letter: 'x',
+            bytes: serde_bytes_like::Bytes(vec![1, 2]),
+            tuple: (1, "a".to_string()),
+            empty: vec![],
+            nested: vec![vec![1], vec![]],
+        };
+        assert_eq!(
+            to_string(&values).unwrap(),
+            "small = 7\nfloat = 1.5\nletter = \"x\"\nbytes = [1, 2]\ntuple = [1, \"a\"]\nempty = []\nnested = [[1], []]\n"
+        );
+    }
+
+    fn error(result: Result<String, Error>) -> (String, String) {
+        let error = result.unwrap_err();
+        match error.kind() {
+            ErrorKind::Serialize { path } => (path.clone(), error.to_string()),
+            kind => panic!("expected a serialize error, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn test_none_in_array() {
+        #[derive(Serialize)]
+        struct Options {
+            values: Vec<Option<i32>>,
+        }
+        let options = Options {
+            values: vec![Some(1), None],
+        };
+        assert_eq!(
+            error(to_string(&options)),
+            (
+                "values[1]".to_string(),
+                "`None` cannot be an element of an array for `values[1]`".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_non_string_keys() {
+        let mut table = HashMap::new();
+        table.insert("numbers", BTreeMap::from([(1, "one")]));
+        assert_eq!(
+            error(to_string(&table)),
+            (
+                "numbers".to_string(),
+                "map keys have to be strings, found an integer for `numbers`".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_unsupported_values() {
+        assert_eq!(
+            error(to_string(&BTreeMap::from([("big", u64::MAX)]))).1,
+            "18446744073709551615 is out of range for a TOML integer for `big`"
+        );
+        assert_eq!(
+            error(to_string(&BTreeMap::from([("unit", ())]))).1,
+            "`()` cannot be written as TOML for `unit`"
+        );
+        assert_eq!(
+            error(to_string(&[1, 2])).1,
+            "only a table can be written as a document, found array"
+        );
+        assert_eq!(
+            error(to_string(&None::<Manifest>)).1,
+            "only a table can be written as a document, found `None`"
+        );
+    }
+
+    #[test]
+    fn test_custom_errors_have_paths() {
+        struct Failing;
+
+        impl Serialize for Failing {
+            fn serialize<S: Serializer>(&self, _s: S) -> Result<S::Ok, S::Error> {
+                Err(S::Error::custom("cannot write this"))
+            }
+        }
+        let table = BTreeMap::from([("a", BTreeMap::from([("b", vec![Failing])]))]);
+        assert_eq!(
+            error(to_string(&table)),
+            ("a.b[0]".to_string(), "cannot write this for `a.b[0]`".to_string())
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+
+use nom::error::ParseError;
+use nom::{IResult, Parser};
+
+use crate::parsers::Key;
+
+/// A range of bytes in the input, along with the line and column it starts at. Lines and
+/// columns start at 1, and columns count characters rather than bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    start: usize,
+    end: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Span {
+    /// The byte offset of the first character
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// The byte offset just past the last character
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Where a key, value or table of a document was defined, as returned by
+/// [`Document::spans`](crate::Document::spans).
+///
+/// Tables that are only defined implicitly, like `a` by `[a.b]` or `a.b = 1`, point at the
+/// first header or key that mentions them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Spans {
+    /// The key, or the key between the brackets of a table header. Array elements have none.
+    pub key: Option<Span>,
+    /// The value. For a table defined by a header, everything from the header to the end of
+    /// its last key value pair.
+    pub value: Span,
+    /// The `[header]` or `[[header]]` of a table defined by one
+    pub header: Option<Span>,
+}
+
+/// A span as the parsers see it. Every parser is handed what is left of the whole input, so
+/// the length of that rest is enough to know where a parser started and stopped. Once the
+/// whole input is known, a [`LineIndex`] turns these into byte offsets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct RawSpan {
+    /// The length of the input left when the parser started
+    pub(crate) start: usize,
+    /// The length of the input left when the parser stopped
+    pub(crate) end: usize,
+}
+
+impl RawSpan {
+    /// The span from the start of `self` to the end of `other`
+    pub(crate) fn to(self, other: RawSpan) -> RawSpan {
+        RawSpan {
+            start: self.start,
+            end: other.end,
+        }
+    }
+}
+
+/// Runs a parser and returns where its output came from, along with the output
+pub(crate) fn spanned<'a, O, E: ParseError<&'a str>, F: Parser<&'a str, O, E>>(
+    mut parser: F,
+) -> impl FnMut(&'a str) -> IResult<&'a str, (O, RawSpan), E> {
+    move |input: &'a str| {
+        let (rest, output) = parser.parse(input)?;
+        let span = RawSpan {
+            start: input.len(),
+            end: rest.len(),
+        };
+        Ok((rest, (output, span)))
+    }
+}
+
+/// The spans of a value, and of the values nested inside of it
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct ValueSpan {
+    pub(crate) span: RawSpan,
+    pub(crate) nested: NestedSpans,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) enum NestedSpans {
+    #[default]
+    None,
+    Array(Vec<ValueSpan>),
+    Table(TableSpans),
+}
+
+/// The keys of an inline table along with their spans
+pub(crate) type TableSpans = Vec<(Key, KeyValueSpans)>;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct KeyValueSpans {
+    pub(crate) key: RawSpan,
+    pub(crate) value: ValueSpan,
+}
+
+impl KeyValueSpans {
+    /// From the start of the key to the end of the value
+    pub(crate) fn whole(&self) -> RawSpan {
+        self.key.to(self.value.span)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct HeaderSpans {
+    /// The key between the brackets
+    pub(crate) key: RawSpan,
+    /// The header from its opening to its closing brackets
+    pub(crate) header: RawSpan,
+}
+
+/// The spans of a section's header and of each of its key value pairs, in the same order
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct SectionSpans {
+    pub(crate) header: Option<HeaderSpans>,
+    pub(crate) key_values: Vec<KeyValueSpans>,
+}
+
+impl SectionSpans {
+    /// From the start of the header to the end of the last key value pair, ignoring the
+    /// empty lines and comments that follow it. `None` for an empty root section.
+    pub(crate) fn whole(&self) -> Option<RawSpan> {
+        let first = match &self.header {
+            Some(header) => header.header,
+            None => self.key_values.first()?.whole(),
+        };
+        let last = match self.key_values.last() {
+            Some(key_value) => key_value.whole(),
+            None => first,
+        };
+        Some(first.to(last))
+    }
+}
+
+/// Turns offsets into lines and columns
+#[derive(Debug)]
+pub(crate) struct LineIndex<'a> {
+    source: &'a str,
+    /// The byte offset each line starts at
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub(crate) fn new(source: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        LineIndex {
+            source,
+            line_starts,
+        }
+    }
+
+    /// The line and column of a byte offset
+    pub(crate) fn position(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+        let column = self.source[line_start..offset].chars().count() + 1;
+        (line, column)
+    }
+
+    /// The byte offset a line starts at, starting at 1
+    pub(crate) fn line_start(&self, line: usize) -> usize {
+        self.line_starts[line - 1]
+    }
+
+    /// The text of a line, starting at 1, without its line ending
+    pub(crate) fn line(&self, line: usize) -> &'a str {
+        let start = self.line_start(line);
+        let end = match self.line_starts.get(line) {
+            Some(next) => next - 1,
+            None => self.source.len(),
+        };
+        self.source[start..end].trim_end_matches('\r')
+    }
+
+    pub(crate) fn span(&self, start: usize, end: usize) -> Span {
+        let (line, column) = self.position(start);
+        Span {
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+
+    pub(crate) fn resolve(&self, raw: RawSpan) -> Span {
+        let len = self.source.len();
+        self.span(len - raw.start, len - raw.end)
+    }
+}
+
+#[cfg(test)]
+mod tests_span {
+    use nom::bytes::complete::tag;
+    use nom::error::ErrorKind;
+
+    use super::*;
+
+    #[test]
+    fn test_spanned() {
+        let input = "key = 1";
+        let (rest, (_, raw)) = spanned(tag::<_, _, (&str, ErrorKind)>("key"))(input).unwrap();
+        assert_eq!(rest, " = 1");
+        assert_eq!(LineIndex::new(input).resolve(raw).range(), 0..3);
+    }
+
+    #[test]
+    fn test_line_index() {
+        let index = LineIndex::new("a = 1\r\n\nb = \"é\" # c\n");
+        assert_eq!(index.position(0), (1, 1));
+        assert_eq!(index.position(4), (1, 5));
+        assert_eq!(index.position(7), (2, 1));
+        assert_eq!(index.position(8), (3, 1));
+        // `é` takes two bytes but one column
+        assert_eq!(index.position(15), (3, 7));
+        let span = index.span(12, 16);
+        assert_eq!((span.line(), span.column()), (3, 5));
+        assert_eq!(span.to_string(), "3:5");
+        assert_eq!(index.line(1), "a = 1");
+        assert_eq!(index.line(3), "b = \"é\" # c");
+        assert_eq!(index.line(4), "");
+    }
+}
@@ -0,0 +1,655 @@
+use std::fmt::{Display, Formatter};
+use std::ops::{Index, IndexMut};
+use std::path::PathBuf;
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::emit::{write_document, write_value};
+use crate::error::{Error, ErrorKind};
+use crate::parsers::PathSegment;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TomlValue {
+    Str(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    OffsetDateTime(DateTime<FixedOffset>),
+    LocalDateTime(NaiveDateTime),
+    LocalDate(NaiveDate),
+    LocalTime(NaiveTime),
+    Array(Vec<TomlValue>),
+    /// Both inline tables and the tables defined by headers or dotted keys
+    Table(Table),
+}
+
+impl TomlValue {
+    /// The name of the value's type, as used in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            TomlValue::Str(_) => "string",
+            TomlValue::Integer(_) => "integer",
+            TomlValue::Float(_) => "float",
+            TomlValue::Boolean(_) => "boolean",
+            TomlValue::OffsetDateTime(_) => "offset datetime",
+            TomlValue::LocalDateTime(_) => "local datetime",
+            TomlValue::LocalDate(_) => "local date",
+            TomlValue::LocalTime(_) => "local time",
+            TomlValue::Array(_) => "array",
+            TomlValue::Table(_) => "table",
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            TomlValue::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            TomlValue::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Integers are not converted, use [`as_integer`](TomlValue::as_integer) for them
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            TomlValue::Float(x) => Some(*x),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            TomlValue::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Only offset datetimes, which name a single instant, are returned. The local
+    /// variants have their own accessors.
+    pub fn as_datetime(&self) -> Option<&DateTime<FixedOffset>> {
+        match self {
+            TomlValue::OffsetDateTime(dt) => Some(dt),
+            _ => None,
+        }
+    }
+
+    pub fn as_local_datetime(&self) -> Option<&NaiveDateTime> {
+        match self {
+            TomlValue::LocalDateTime(dt) => Some(dt),
+            _ => None,
+        }
+    }
+
+    pub fn as_local_date(&self) -> Option<&NaiveDate> {
+        match self {
+            TomlValue::LocalDate(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    pub fn as_local_time(&self) -> Option<&NaiveTime> {
+        match self {
+            TomlValue::LocalTime(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<TomlValue>> {
+        match self {
+            TomlValue::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_array_mut(&mut self) -> Option<&mut Vec<TomlValue>> {
+        match self {
+            TomlValue::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_table(&self) -> Option<&Table> {
+        match self {
+            TomlValue::Table(table) => Some(table),
+            _ => None,
+        }
+    }
+
+    pub fn as_table_mut(&mut self) -> Option<&mut Table> {
+        match self {
+            TomlValue::Table(table) => Some(table),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn invalid_type(&self, expected: &'static str) -> Error {
+        Error::conversion(ErrorKind::InvalidType {
+            expected,
+            found: self.type_name(),
+        })
+    }
+}
+
+/// Converts integers into the other integer types, failing if they do not fit
+macro_rules! try_from_integer {
+    ($($int:ty),*) => {
+        $(
+            impl TryFrom<&TomlValue> for $int {
+                type Error = Error;
+
+                fn try_from(value: &TomlValue) -> Result<Self, Self::Error> {
+                    let i = value
+                        .as_integer()
+                        .ok_or_else(|| value.invalid_type(stringify!($int)))?;
+                    <$int>::try_from(i).map_err(|_| {
+                        Error::conversion(ErrorKind::OutOfRange {
+                            expected: stringify!($int),
+                            value: i,
+                        })
+                    })
+                }
+            }
+        )*
+    };
+}
+
+try_from_integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl TryFrom<&TomlValue> for f64 {
+    type Error = Error;
+
+    fn try_from(value: &TomlValue) -> Result<Self, Self::Error> {
+        value.as_float().ok_or_else(|| value.invalid_type("f64"))
+    }
+}
+
+impl TryFrom<&TomlValue> for bool {
+    type Error = Error;
+
+    fn try_from(value: &TomlValue) -> Result<Self, Self::Error> {
+        value.as_bool().ok_or_else(|| value.invalid_type("bool"))
+    }
+}
+
+impl<'a> TryFrom<&'a TomlValue> for &'a str {
+    type Error = Error;
+
+    fn try_from(value: &'a TomlValue) -> Result<Self, Self::Error> {
+        value.as_str().ok_or_else(|| value.invalid_type("string"))
+    }
+}
+
+impl TryFrom<&TomlValue> for String {
+    type Error = Error;
+
+    fn try_from(value: &TomlValue) -> Result<Self, Self::Error> {
+        <&str>::try_from(value).map(String::from)
+    }
+}
+
+impl TryFrom<&TomlValue> for PathBuf {
+    type Error = Error;
+
+    fn try_from(value: &TomlValue) -> Result<Self, Self::Error> {
+        value
+            .as_str()
+            .map(PathBuf::from)
+            .ok_or_else(|| value.invalid_type("path"))
+    }
+}
+
+impl TryFrom<&TomlValue> for DateTime<FixedOffset> {
+    type Error = Error;
+
+    fn try_from(value: &TomlValue) -> Result<Self, Self::Error> {
+        value
+            .as_datetime()
+            .copied()
+            .ok_or_else(|| value.invalid_type("offset datetime"))
+    }
+}
+
+impl TryFrom<&TomlValue> for NaiveDate {
+    type Error = Error;
+
+    fn try_from(value: &TomlValue) -> Result<Self, Self::Error> {
+        value
+            .as_local_date()
+            .copied()
+            .ok_or_else(|| value.invalid_type("local date"))
+    }
+}
+
+impl<'a> TryFrom<&'a TomlValue> for &'a Table {
+    type Error = Error;
+
+    fn try_from(value: &'a TomlValue) -> Result<Self, Self::Error> {
+        value.as_table().ok_or_else(|| value.invalid_type("table"))
+    }
+}
+
+impl<'a> TryFrom<&'a TomlValue> for &'a [TomlValue] {
+    type Error = Error;
+
+    fn try_from(value: &'a TomlValue) -> Result<Self, Self::Error> {
+        value
+            .as_array()
+            .map(Vec::as_slice)
+            .ok_or_else(|| value.invalid_type("array"))
+    }
+}
+
+/// Writes the value the way it appears on the right side of a key value pair, with tables
+/// written as inline tables
+impl Display for TomlValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write_value(f, self)
+    }
+}
+
+/// A map from keys to values that remembers the order the keys were inserted in.
+///
+/// Tables are small enough in practice that looking keys up by walking the entries is
+/// cheaper than hashing them, and the order is what a reader of the document expects to see.
+/// Two tables are equal if they hold the same keys and values, in any order.
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    entries: Vec<(String, TomlValue)>,
+}
+
+impl PartialEq for Table {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl Table {
+    pub fn new() -> Self {
+        Table::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&TomlValue> {
+        self.entries
+            .iter()
+            .find(|(entry_key, _)| entry_key == key)
+            .map(|(_, value)| value)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut TomlValue> {
+        self.entries
+            .iter_mut()
+            .find(|(entry_key, _)| entry_key == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Inserts a value and returns the one it replaced. A replaced key keeps its position,
+    /// new keys are added at the end.
+    pub fn insert(&mut self, key: impl Into<String>, value: TomlValue) -> Option<TomlValue> {
+        let key = key.into();
+        match self.get_mut(&key) {
+            Some(existing) => Some(std::mem::replace(existing, value)),
+            None => {
+                self.entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    /// Removes a key, keeping the order of the remaining entries
+    pub fn remove(&mut self, key: &str) -> Option<TomlValue> {
+        let index = self
+            .entries
+            .iter()
+            .position(|(entry_key, _)| entry_key == key)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &TomlValue)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&String, &mut TomlValue)> {
+        self.entries.iter_mut().map(|(key, value)| (&*key, value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &TomlValue> {
+        self.entries.iter().map(|(_, value)| value)
+    }
+
+    /// Returns the table called `key`, creating it if it does not exist yet. For an array of
+    /// tables, its last element is returned. `None` if `key` holds any other value.
+    pub(crate) fn subtable_mut(&mut self, key: &str) -> Option<&mut Table> {
+        if !self.contains_key(key) {
+            self.insert(key, TomlValue::Table(Table::new()));
+        }
+        match self.get_mut(key)? {
+            TomlValue::Table(table) => Some(table),
+            TomlValue::Array(values) => match values.last_mut() {
+                Some(TomlValue::Table(table)) => Some(table),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Inserts a value under a dotted key, creating the tables its segments lead through.
+    /// Returns `false`, without changing anything, if the key is already defined or if one
+    /// of its segments holds something other than a table.
+    pub(crate) fn insert_dotted(&mut self, segments: &[String], value: TomlValue) -> bool {
+        let Some((name, parents)) = segments.split_last() else {
+            return false;
+        };
+        let mut table = self;
+        for parent in parents {
+            if matches!(table.get(parent), Some(value) if !matches!(value, TomlValue::Table(_))) {
+                return false;
+            }
+            table = match table.subtable_mut(parent) {
+                Some(table) => table,
+                None => return false,
+            };
+        }
+        if table.contains_key(name) {
+            return false;
+        }
+        table.insert(name.as_str(), value);
+        true
+    }
+}
+
+/// Follows a path through nested tables and arrays, starting at `value`
+fn lookup<'a>(mut value: &'a TomlValue, path: &[PathSegment]) -> Option<&'a TomlValue> {
+    for segment in path {
+        value = match (value, segment) {
+            (TomlValue::Table(table), PathSegment::Key(key)) => table.get(key)?,
+            (TomlValue::Array(values), PathSegment::Index(index)) => values.get(*index)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+fn lookup_mut<'a>(mut value: &'a mut TomlValue, path: &[PathSegment]) -> Option<&'a mut TomlValue> {
+    for segment in path {
+        value = match (value, segment) {
+            (TomlValue::Table(table), PathSegment::Key(key)) => table.get_mut(key)?,
+            (TomlValue::Array(values), PathSegment::Index(index)) => values.get_mut(*index)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+impl Table {
+    /// Returns the value a path points to. Paths start with a key of this table.
+    pub(crate) fn lookup(&self, path: &[PathSegment]) -> Option<&TomlValue> {
+        match path.split_first()? {
+            (PathSegment::Key(key), rest) => lookup(self.get(key)?, rest),
+            (PathSegment::Index(_), _) => None,
+        }
+    }
+
+    pub(crate) fn lookup_mut(&mut self, path: &[PathSegment]) -> Option<&mut TomlValue> {
+        match path.split_first()? {
+            (PathSegment::Key(key), rest) => lookup_mut(self.get_mut(key)?, rest),
+            (PathSegment::Index(_), _) => None,
+        }
+    }
+}
+
+/// Writes the table as a TOML document, with a header for each of its subtables
+impl Display for Table {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write_document(f, self)
+    }
+}
+
+/// Panics if the table does not contain `key`, like indexing a `HashMap` does
+impl Index<&str> for Table {
+    type Output = TomlValue;
+
+    fn index(&self, key: &str) -> &TomlValue {
+        match self.get(key) {
+            Some(value) => value,
+            None => panic!("no key `{}` in table", key),
+        }
+    }
+}
+
+impl IndexMut<&str> for Table {
+    fn index_mut(&mut self, key: &str) -> &mut TomlValue {
+        match self.get_mut(key) {
+            Some(value) => value,
+            None => panic!("no key `{}` in table", key),
+        }
+    }
+}
+
+/// Panics if the value is not a table or does not contain `key`
+impl Index<&str> for TomlValue {
+    type Output = TomlValue;
+
+    fn index(&self, key: &str) -> &TomlValue {
+        match self {
+            TomlValue::Table(table) => &table[key],
+            _ => panic!("cannot index a value that is not a table with `{}`", key),
+        }
+    }
+}
+
+impl IndexMut<&str> for TomlValue {
+    fn index_mut(&mut self, key: &str) -> &mut TomlValue {
+        match self {
+            TomlValue::Table(table) => &mut table[key],
+            _ => panic!("cannot index a value that is not a table with `{}`", key),
+        }
+    }
+}
+
+/// Panics if the value is not an array or `index` is out of bounds
+impl Index<usize> for TomlValue {
+    type Output = TomlValue;
+
+    fn index(&self, index: usize) -> &TomlValue {
+        match self {
+            TomlValue::Array(values) => &values[index],
+            _ => panic!("cannot index a value that is not an array with `{}`", index),
+        }
+    }
+}
+
+impl IndexMut<usize> for TomlValue {
+    fn index_mut(&mut self, index: usize) -> &mut TomlValue {
+        match self {
+            TomlValue::Array(values) => &mut values[index],
+            _ => panic!("cannot index a value that is not an array with `{}`", index),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Table {
+    type Item = (&'a String, &'a TomlValue);
+    type IntoIter = Box<dyn Iterator<Item = (&'a String, &'a TomlValue)> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
+    }
+}
+
+impl IntoIterator for Table {
+    type Item = (String, TomlValue);
+    type IntoIter = std::vec::IntoIter<(String, TomlValue)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<K: Into<String>> FromIterator<(K, TomlValue)> for Table {
+    fn from_iter<T: IntoIterator<Item = (K, TomlValue)>>(iter: T) -> Self {
+        let mut table = Table::new();
+        for (key, value) in iter {
+            table.insert(key, value);
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests_value {
+    use super::*;
+
+    #[test]
+    fn test_table_keeps_insertion_order() {
+        let mut table = Table::new();
+        table.insert("b", TomlValue::Integer(1));
+        table.insert("a", TomlValue::Integer(2));
+        assert_eq!(
+            table.insert("b", TomlValue::Integer(3)),
+            Some(TomlValue::Integer(1))
+        );
+        let keys: Vec<&String> = table.keys().collect();
+        assert_eq!(keys, vec!["b", "a"]);
+        assert_eq!(
+            table,
+            Table::from_iter([("a", TomlValue::Integer(2)), ("b", TomlValue::Integer(3))])
+        );
+        assert_eq!(table.remove("b"), Some(TomlValue::Integer(3)));
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn test_accessors() {
+        let value = TomlValue::Str("2021".to_string());
+        assert_eq!(value.as_str(), Some("2021"));
+        assert_eq!(value.as_integer(), None);
+        assert_eq!(value.type_name(), "string");
+        assert_eq!(TomlValue::Float(0.5).as_float(), Some(0.5));
+        assert_eq!(TomlValue::Integer(1).as_float(), None);
+        assert_eq!(TomlValue::Boolean(true).as_bool(), Some(true));
+        assert_eq!(
+            TomlValue::Array(vec![]).as_array(),
+            Some(&Vec::<TomlValue>::new())
+        );
+        assert_eq!(TomlValue::Table(Table::new()).as_table(), Some(&Table::new()));
+        assert_eq!(TomlValue::Table(Table::new()).as_array(), None);
+    }
+
+    #[test]
+    fn test_try_from_integer() {
+        assert_eq!(u16::try_from(&TomlValue::Integer(8080)), Ok(8080));
+        let error = u16::try_from(&TomlValue::Integer(70000)).unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::OutOfRange {
+                expected: "u16",
+                value: 70000
+            }
+        );
+        assert_eq!(error.to_string(), "expected u16, found 70000 which is out of range");
+        assert!(usize::try_from(&TomlValue::Integer(-1)).is_err());
+        assert_eq!(
+            u32::try_from(&TomlValue::Str("1".to_string()))
+                .unwrap_err()
+                .to_string(),
+            "expected u32, found string"
+        );
+    }
+
+    #[test]
+    fn test_try_from_other_types() {
+        let value = TomlValue::Str("src/lib.rs".to_string());
+        assert_eq!(PathBuf::try_from(&value), Ok(PathBuf::from("src/lib.rs")));
+        assert_eq!(String::try_from(&value), Ok("src/lib.rs".to_string()));
+        assert_eq!(<&str>::try_from(&value), Ok("src/lib.rs"));
+        assert_eq!(
+            bool::try_from(&value).unwrap_err().kind(),
+            &ErrorKind::InvalidType {
+                expected: "bool",
+                found: "string"
+            }
+        );
+        assert_eq!(
+            PathBuf::try_from(&TomlValue::Boolean(false))
+                .unwrap_err()
+                .to_string(),
+            "expected path, found boolean"
+        );
+    }
+
+    #[test]
+    fn test_index() {
+        let mut table = Table::from_iter([
+            (
+                "package",
+                TomlValue::Table(Table::from_iter([(
+                    "name",
+                    TomlValue::Str("nom-toml-parser".to_string()),
+                )])),
+            ),
+            (
+                "bin",
+                TomlValue::Array(vec![TomlValue::Table(Table::from_iter([(
+                    "name",
+                    TomlValue::Str("manager".to_string()),
+                )]))]),
+            ),
+        ]);
+        assert_eq!(
+            table["package"]["name"],
+            TomlValue::Str("nom-toml-parser".to_string())
+        );
+        assert_eq!(table["bin"][0]["name"], TomlValue::Str("manager".to_string()));
+
+        table["bin"][0]["name"] = TomlValue::Str("other".to_string());
+        assert_eq!(table["bin"][0]["name"], TomlValue::Str("other".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "no key `missing` in table")]
+    fn test_index_missing_key() {
+        let table = Table::new();
+        let _ = &table["missing"];
+    }
+
+    #[test]
+    fn test_insert_dotted() {
+        let mut table = Table::new();
+        let key = |s: &str| -> Vec<String> { s.split('.').map(String::from).collect() };
+        assert!(table.insert_dotted(&key("a.b"), TomlValue::Integer(1)));
+        assert!(table.insert_dotted(&key("a.c"), TomlValue::Integer(2)));
+        assert!(!table.insert_dotted(&key("a.b"), TomlValue::Integer(3)));
+        assert!(!table.insert_dotted(&key("a.b.c"), TomlValue::Integer(4)));
+        assert_eq!(
+            table.get("a"),
+            Some(&TomlValue::Table(Table::from_iter([
+                ("b", TomlValue::Integer(1)),
+                ("c", TomlValue::Integer(2)),
+            ])))
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use std::fs::{read_to_string, write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A directory of its own for each test, removed when the test is done
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(test: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("manager-{}-{}", std::process::id(), test));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    fn file(&self, name: &str, contents: &str) -> PathBuf {
+        let path = self.0.join(name);
+        write(&path, contents).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// What a run of the binary printed, and the code it exited with
+#[derive(Debug)]
+struct Run {
+    code: i32,
+    stdout: String,
+    stderr: String,
+}
+
+/// Runs `manager` in `dir`, so that file names in its output are relative
+fn manager(dir: &Path, args: &[&str]) -> Run {
+    let output = Command::new(env!("CARGO_BIN_EXE_manager"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    Run {
+        code: output.status.code().unwrap(),
+        stdout: String::from_utf8(output.stdout).unwrap(),
+        stderr: String::from_utf8(output.stderr).unwrap(),
+    }
+}
+
+const MANIFEST: &str = "\
+[package]
+name = \"x\"
+version = \"0.1.0\"
+
+[dependencies]
+nom = \"7\"
+";
+
+#[test]
+fn test_check() {
+    let dir = TempDir::new("check");
+    dir.file("Cargo.toml", MANIFEST);
+    let run = manager(&dir.0, &["check", "Cargo.toml"]);
+    assert_eq!((run.code, run.stdout.as_str(), run.stderr.as_str()), (0, "", ""));
+
+    dir.file("bad.toml", "a = \n");
+    let run = manager(&dir.0, &["check", "bad.toml"]);
+    assert_eq!(run.code, 1);
+    assert_eq!(
+        run.stderr,
+        "\
+error: expected a value in key value pair
+ --> bad.toml:1:5
+  |
+1 | a = \n  |     ^ expected a value
+
+"
+    );
+
+    dir.file("duplicate.toml", "a = 1\na = 2\n");
+    let run = manager(&dir.0, &["check", "duplicate.toml"]);
+    assert_eq!(run.code, 3);
+    let message = "error: `a` is defined more than once (first defined on line 1)\n";
+    assert!(run.stderr.starts_with(message));
+
+    // Valid TOML, but not a valid manifest
+    dir.file("Cargo.toml", "[package]\nversion = \"1\"\n");
+    let run = manager(&dir.0, &["check", "Cargo.toml"]);
+    assert_eq!(run.code, 3);
+    assert!(run.stderr.starts_with("error: missing key `package.name`"));
+
+    let run = manager(&dir.0, &["check", "missing.toml"]);
+    assert_eq!(run.code, 4);
+    assert!(run.stderr.starts_with("error: could not read missing.toml: "));
+}
+
+#[test]
+fn test_get() {
+    let dir = TempDir::new("get");
+    dir.file("Cargo.toml", MANIFEST);
+    let run = manager(&dir.0, &["get", "Cargo.toml", "package.name"]);
+    assert_eq!((run.code, run.stdout.as_str()), (0, "x\n"));
+    let run = manager(&dir.0, &["get", "Cargo.toml", "dependencies"]);
+    assert_eq!((run.code, run.stdout.as_str()), (0, "nom = \"7\"\n"));
+
+    let run = manager(&dir.0, &["get", "Cargo.toml", "package.edition"]);
+    assert_eq!(run.code, 3);
+    assert_eq!(run.stderr, "error: `package.edition` is not in Cargo.toml\n");
+}
+
+#[test]
+fn test_set() {
+    let dir = TempDir::new("set");
+    let path = dir.file("Cargo.toml", MANIFEST);
+    let run = manager(&dir.0, &["set", "Cargo.toml", "package.version", "\"0.2.0\""]);
+    assert_eq!((run.code, run.stderr.as_str()), (0, ""));
+    assert_eq!(read_to_string(&path).unwrap(), MANIFEST.replace("0.1.0", "0.2.0"));
+
+    // A name has to be a string, so the file is left as it was
+    let run = manager(&dir.0, &["set", "Cargo.toml", "package.name", "1"]);
+    assert_eq!(run.code, 3);
+    assert_eq!(read_to_string(&path).unwrap(), MANIFEST.replace("0.1.0", "0.2.0"));
+}
+
+#[test]
+fn test_fmt() {
+    let dir = TempDir::new("fmt");
+    let path = dir.file("a.toml", "a   =   1\n\n\n\n[t]\nb=2\n");
+    let run = manager(&dir.0, &["fmt", "--check", "a.toml"]);
+    assert_eq!((run.code, run.stderr.as_str()), (5, "error: a.toml is not formatted\n"));
+
+    let run = manager(&dir.0, &["fmt", "a.toml"]);
+    assert_eq!(run.code, 0);
+    assert_eq!(read_to_string(&path).unwrap(), "a = 1\n\n[t]\nb = 2\n");
+    let run = manager(&dir.0, &["fmt", "--check", "a.toml"]);
+    assert_eq!(run.code, 0);
+
+    dir.file("toml-fmt.toml", "colum-width = 80\n");
+    let run = manager(&dir.0, &["fmt", "a.toml"]);
+    assert_eq!(run.code, 3);
+    assert!(run.stderr.starts_with("error: unknown formatting option"));
+}
+
+#[test]
+fn test_convert() {
+    let dir = TempDir::new("convert");
+    dir.file("Cargo.toml", MANIFEST);
+    let run = manager(&dir.0, &["convert", "--to", "json", "Cargo.toml"]);
+    assert_eq!(run.code, 0);
+    assert_eq!(
+        run.stdout,
+        "\
+{
+  \"package\": {
+    \"name\": \"x\",
+    \"version\": \"0.1.0\"
+  },
+  \"dependencies\": {
+    \"nom\": \"7\"
+  }
+}
+"
+    );
+}
+
+#[test]
+fn test_add() {
+    let dir = TempDir::new("add");
+    let path = dir.file("Cargo.toml", MANIFEST);
+    let run = manager(
+        &dir.0,
+        &["add", "Cargo.toml", "serde@1", "--dev", "--target", "cfg(unix)", "--features", "derive"],
+    );
+    assert_eq!((run.code, run.stderr.as_str()), (0, ""));
+    assert_eq!(
+        read_to_string(&path).unwrap(),
+        format!(
+            "{}\n[target.'cfg(unix)'.dev-dependencies]\n\
+             serde = {{ version = \"1\", features = [\"derive\"] }}\n",
+            MANIFEST
+        )
+    );
+
+    let run = manager(&dir.0, &["add", "Cargo.toml", "serde"]);
+    assert_eq!(run.code, 2);
+    assert!(run.stderr.starts_with("error: `serde` needs a version"));
+}
+
+#[test]
+fn test_rm() {
+    let dir = TempDir::new("rm");
+    let path = dir.file("Cargo.toml", MANIFEST);
+    let run = manager(&dir.0, &["rm", "Cargo.toml", "nom"]);
+    assert_eq!((run.code, run.stderr.as_str()), (0, ""));
+    assert_eq!(read_to_string(&path).unwrap(), MANIFEST.replace("nom = \"7\"\n", ""));
+
+    let run = manager(&dir.0, &["rm", "Cargo.toml", "nom"]);
+    assert_eq!(run.code, 3);
+    assert_eq!(run.stderr, "error: `nom` is not a dependency in Cargo.toml\n");
+}
+
+#[test]
+fn test_upgrade() {
+    let dir = TempDir::new("upgrade");
+    let path = dir.file("Cargo.toml", MANIFEST);
+    let run = manager(&dir.0, &["upgrade", "Cargo.toml", "nom", "8"]);
+    assert_eq!((run.code, run.stderr.as_str()), (0, ""));
+    assert_eq!(read_to_string(&path).unwrap(), MANIFEST.replace("\"7\"", "\"8\""));
+
+    // The version of a dependency from the workspace is set in the workspace
+    let manifest = MANIFEST.replace("\"7\"", "{ workspace = true }");
+    dir.file("Cargo.toml", &manifest);
+    let run = manager(&dir.0, &["upgrade", "Cargo.toml", "nom", "8"]);
+    assert_eq!(run.code, 3);
+    assert_eq!(
+        run.stderr,
+        "error: `nom` is not a dependency in Cargo.toml, or takes its version from the workspace\n"
+    );
+    assert_eq!(read_to_string(&path).unwrap(), manifest);
+}
+
+#[test]
+fn test_usage() {
+    let dir = TempDir::new("usage");
+    dir.file("Cargo.toml", MANIFEST);
+    let usage = |args: &[&str]| {
+        let run = manager(&dir.0, args);
+        assert_eq!(run.code, 2, "{:?}", args);
+        assert!(run.stderr.contains("\n\nusage: manager <command>"));
+        run.stderr.lines().next().unwrap().to_string()
+    };
+    assert_eq!(usage(&[]), "error: no command given");
+    assert_eq!(usage(&["frobnicate", "Cargo.toml"]), "error: unknown command `frobnicate`");
+    assert_eq!(usage(&["get", "Cargo.toml"]), "error: wrong arguments for `get`");
+    assert_eq!(
+        usage(&["get", "--check", "Cargo.toml", "a"]),
+        "error: `get` does not take `--check`"
+    );
+    assert_eq!(
+        usage(&["convert", "--to", "yaml", "Cargo.toml"]),
+        "error: cannot convert to `yaml`, only to `json`"
+    );
+
+    let run = manager(&dir.0, &["--help"]);
+    assert_eq!(run.code, 0);
+    assert!(run.stdout.starts_with("usage: manager <command>"));
+}